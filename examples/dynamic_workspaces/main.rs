@@ -24,6 +24,7 @@ fn my_layouts() -> Vec<Layout> {
         gapless: true,
         follow_focus: true,
         allow_wrapping: false,
+        respect_size_hints: false,
     };
 
     vec![
@@ -10,6 +10,7 @@
 extern crate penrose;
 
 use penrose::{
+    bindings::{BindingModes, ClientBindings, MouseEvent},
     client::Client,
     contrib::{
         extensions::Scratchpad,
@@ -23,7 +24,6 @@ use penrose::{
 };
 
 use simplelog::{LevelFilter, SimpleLogger};
-use std::collections::HashMap;
 
 // An example of a simple custom hook. In this case we are creating a NewClientHook which will
 // be run each time a new client program is spawned.
@@ -53,6 +53,7 @@ fn main() -> Result<()> {
     // Client border colors are set based on X focus
     config.focused_border = 0xcc241d; // #cc241d
     config.unfocused_border = 0x3c3836; // #3c3836
+    config.urgent_border = 0xd79921; // #d79921
 
     // When specifying a layout, most of the time you will want LayoutConf::default() as shown
     // below, which will honour gap settings and will not be run on focus changes (only when
@@ -63,6 +64,7 @@ fn main() -> Result<()> {
         gapless: true,
         follow_focus: true,
         allow_wrapping: false,
+        respect_size_hints: false,
     };
 
     // Defauly number of clients in the main layout area
@@ -117,19 +119,25 @@ fn main() -> Result<()> {
     sp.register(&mut config);
 
     /* The gen_keybindings macro parses user friendly key binding definitions into X keycodes and
-     * modifier masks. It uses the 'xmodmap' program to determine your current keymap and create
-     * the bindings dynamically on startup. If this feels a little too magical then you can
-     * alternatively construct a  HashMap<KeyCode, FireAndForget> manually with your chosen
+     * modifier masks. It asks the X server for your current keymap and creates the bindings
+     * dynamically on startup (key names are the same as those shown by 'xmodmap -pke'). Bindings
+     * are moved to the new key codes if the keymap changes while penrose is running. If this
+     * feels a little too magical then you can
+     * alternatively construct a  HashMap<KeyCode, KeyBinding> manually with your chosen
      * keybindings (see helpers.rs and data_types.rs for details).
      * FireAndForget functions do not need to make use of the mutable WindowManager reference they
      * are passed if it is not required: the run_external macro ignores the WindowManager itself
-     * and instead spawns a new child process.
+     * and instead spawns a new child process. Patterns containing spaces are key chords: "M-a t"
+     * runs its action if t is pressed after M-a, with Escape cancelling the chord. Patterns
+     * starting with '@' run their action when the key is released rather than pressed, and
+     * ClientBindings pick their action based on the WM_CLASS of the focused client.
      */
     let key_bindings = gen_keybindings! {
         // Program launch
         "M-semicolon" => run_external!(my_program_launcher);
         "M-Return" => run_external!(my_terminal);
         "M-f" => run_external!(my_file_manager);
+        "@M-m" => run_external!("pactl set-source-mute @DEFAULT_SOURCE@ toggle");
 
         // client management
         "M-j" => run_internal!(cycle_client, Forward);
@@ -137,7 +145,13 @@ fn main() -> Result<()> {
         "M-S-j" => run_internal!(drag_client, Forward);
         "M-S-k" => run_internal!(drag_client, Backward);
         "M-S-q" => run_internal!(kill_client);
+        "M-BackSpace" => ClientBindings::new()
+            .pass_through("firefox")
+            .otherwise(run_internal!(kill_client));
+        "M-C-S-q" => run_internal!(force_kill_client);
         "M-S-f" => run_internal!(toggle_client_fullscreen, &Selector::Focused);
+        "M-t" => run_internal!(toggle_client_floating, &Selector::Focused);
+        "M-u" => run_internal!(focus_urgent);
         "M-slash" => sp.toggle();
 
         // workspace management
@@ -154,6 +168,7 @@ fn main() -> Result<()> {
         "M-A-Down" => run_internal!(update_max_main, Less);
         "M-A-Right" => run_internal!(update_main_ratio, More);
         "M-A-Left" => run_internal!(update_main_ratio, Less);
+        "M-r" => run_internal!(enter_mode, "resize");
 
         "M-A-s" => run_internal!(detect_screens);
         "M-A-Escape" => run_internal!(exit);
@@ -166,6 +181,26 @@ fn main() -> Result<()> {
         };
     };
 
+    // Additional sets of key bindings can be given a name and switched to using enter_mode. While
+    // in "resize" mode, the layout can be adjusted without holding any modifiers and Escape
+    // returns to the default bindings above.
+    let resize_bindings = gen_keybindings! {
+        "h" => run_internal!(update_main_ratio, Less);
+        "l" => run_internal!(update_main_ratio, More);
+        "j" => run_internal!(update_max_main, Less);
+        "k" => run_internal!(update_max_main, More);
+        "Escape" => run_internal!(exit_mode);
+    };
+    let modes = BindingModes::new(key_bindings).with_mode("resize", resize_bindings);
+
+    // Mouse bindings are specified as the mouse event kind, the button and the modifiers that
+    // need to be held. The built in mouse_move_client and mouse_resize_client actions will float
+    // the client under the cursor and let you drag it around until the button is released.
+    let mouse_bindings = gen_mousebindings! {
+        Press Left + [Meta] => |wm: &mut WindowManager, e: &MouseEvent| wm.mouse_move_client(e),
+        Press Right + [Meta] => |wm: &mut WindowManager, e: &MouseEvent| wm.mouse_resize_client(e)
+    };
+
     // The underlying connection to the X server is handled as a trait: XConn. XcbConnection is the
     // reference implementation of this trait that uses the XCB library to communicate with the X
     // server. You are free to provide your own implementation if you wish, see xconnection.rs for
@@ -181,7 +216,7 @@ fn main() -> Result<()> {
     // grab_keys_and_run will start listening to events from the X server and drop into the main
     // event loop. From this point on, program control passes to the WindowManager so make sure
     // that any logic you wish to run is done before here!
-    wm.grab_keys_and_run(key_bindings, HashMap::new());
+    wm.grab_keys_and_run(modes, mouse_bindings);
 
     Ok(())
 }
@@ -4,7 +4,7 @@ use crate::{
     Result, WindowManager,
 };
 
-use std::{collections::HashMap, convert::TryFrom};
+use std::{cell::RefCell, collections::HashMap, convert::TryFrom, fmt, rc::Rc};
 
 use anyhow::anyhow;
 use strum::{EnumIter, IntoEnumIterator};
@@ -16,34 +16,323 @@ pub type FireAndForget = Box<dyn FnMut(&mut WindowManager)>;
 pub type MouseEventHandler = Box<dyn FnMut(&mut WindowManager, &MouseEvent)>;
 
 /// User defined key bindings
-pub type KeyBindings = HashMap<KeyCode, FireAndForget>;
+pub type KeyBindings = HashMap<KeyCode, KeyBinding>;
 
 /// User defined mouse bindings
 pub type MouseBindings = HashMap<(MouseEventKind, MouseState), MouseEventHandler>;
 
-pub(crate) type CodeMap = HashMap<String, u8>;
+/// Key names (as shown by `xmodmap -pke`) mapped to the key codes that produce them
+pub type CodeMap = HashMap<String, u8>;
 
-/// A key press and held modifiers
+/// The keysyms produced by each key code, as reported by the X server
+pub type KeyboardMapping = HashMap<u8, Vec<u32>>;
+
+// Modifier masks defined by the core X protocol
+const MOD_MASK_SHIFT: u16 = 1;
+const MOD_MASK_CONTROL: u16 = 1 << 2;
+const MOD_MASK_1: u16 = 1 << 3;
+const MOD_MASK_4: u16 = 1 << 6;
+
+/// The name of the binding mode that is active when penrose starts
+pub const DEFAULT_MODE: &str = "default";
+
+/**
+ * Named sets of key bindings, only one of which is grabbed at any given time.
+ *
+ * The bindings for [DEFAULT_MODE] are active when the WindowManager starts and
+ * [enter_mode][WindowManager::enter_mode] switches to one of the other modes. A single set of
+ * [KeyBindings] can be used anywhere a BindingModes is expected as the default mode on its own.
+ */
+#[derive(Debug)]
+pub struct BindingModes {
+    modes: HashMap<String, KeyBindings>,
+}
+
+impl BindingModes {
+    /// Create a new BindingModes using 'bindings' for the default mode
+    pub fn new(bindings: KeyBindings) -> Self {
+        let mut modes = HashMap::new();
+        modes.insert(DEFAULT_MODE.into(), bindings);
+        Self { modes }
+    }
+
+    /// Add a named mode, replacing any existing bindings for that name
+    pub fn with_mode(mut self, name: impl Into<String>, bindings: KeyBindings) -> Self {
+        self.modes.insert(name.into(), bindings);
+        self
+    }
+
+    /// Whether or not there are bindings for the named mode
+    pub fn contains(&self, name: &str) -> bool {
+        self.modes.contains_key(name)
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&KeyBindings> {
+        self.modes.get(name)
+    }
+
+    pub(crate) fn get_mut(&mut self, name: &str) -> Option<&mut KeyBindings> {
+        self.modes.get_mut(name)
+    }
+
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = &mut KeyBindings> {
+        self.modes.values_mut()
+    }
+
+    // Add the bindings from 'other', replacing any existing bindings for the same keys
+    pub(crate) fn extend(&mut self, other: BindingModes) {
+        for (name, bindings) in other.modes {
+            self.modes.entry(name).or_default().extend(bindings);
+        }
+    }
+}
+
+impl From<KeyBindings> for BindingModes {
+    fn from(bindings: KeyBindings) -> Self {
+        Self::new(bindings)
+    }
+}
+
+/**
+ * What to do in response to a bound key press.
+ *
+ * Actions are shared so that they can be run without holding a borrow on the bindings, leaving
+ * them free to rebind or unbind keys (including their own) while they run.
+ */
+pub enum KeyBinding {
+    /// Run an action
+    Action(Rc<RefCell<FireAndForget>>),
+    /// The first key of a chord: wait for the next key and look it up in these bindings
+    Chord(KeyBindings),
+    /// Run an action based on the WM_CLASS of the focused client
+    PerClient(ClientBindings),
+}
+
+impl From<FireAndForget> for KeyBinding {
+    fn from(action: FireAndForget) -> Self {
+        Self::Action(Rc::new(RefCell::new(action)))
+    }
+}
+
+impl fmt::Debug for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Action(_) => f.write_str("Action"),
+            Self::Chord(bindings) => f.debug_tuple("Chord").field(bindings).finish(),
+            Self::PerClient(bindings) => f.debug_tuple("PerClient").field(bindings).finish(),
+        }
+    }
+}
+
+/**
+ * Actions for a single key binding that depend on the WM_CLASS of the focused client.
+ *
+ * When no action matches the focused client the key is sent on to it instead, as if it had
+ * never been grabbed.
+ *
+ * NOTE: Keys are passed through using XSendEvent rather than being replayed from a synchronous
+ * keyboard grab, so the client receives a synthetic event with the send_event flag set. Some
+ * programs ignore synthetic key events (xterm does so by default, see its allowSendEvents
+ * resource) and others may treat them differently to real key presses, so passing keys through
+ * should only be relied on for clients that are known to accept them.
+ */
+#[derive(Default)]
+pub struct ClientBindings {
+    classes: HashMap<String, Option<Rc<RefCell<FireAndForget>>>>,
+    default: Option<Rc<RefCell<FireAndForget>>>,
+}
+
+impl ClientBindings {
+    /// Create a new ClientBindings that passes every key through to the focused client
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run 'action' when the focused client has the given WM_CLASS
+    pub fn with_class(mut self, class: impl Into<String>, action: FireAndForget) -> Self {
+        self.classes
+            .insert(class.into(), Some(Rc::new(RefCell::new(action))));
+        self
+    }
+
+    /// Always pass the key through to clients with the given WM_CLASS (see the note above on
+    /// how keys are passed through)
+    pub fn pass_through(mut self, class: impl Into<String>) -> Self {
+        self.classes.insert(class.into(), None);
+        self
+    }
+
+    /// Run 'action' for any client (or no client) that does not match one of the named classes
+    pub fn otherwise(mut self, action: FireAndForget) -> Self {
+        self.default = Some(Rc::new(RefCell::new(action)));
+        self
+    }
+
+    // The action to run given the WM_CLASS of the focused client, None meaning pass through
+    pub(crate) fn action_for(&self, class: Option<&str>) -> Option<Rc<RefCell<FireAndForget>>> {
+        match class.filter(|c| self.classes.contains_key(*c)) {
+            Some(c) => self.classes.get(c).and_then(Clone::clone),
+            None => self.default.clone(),
+        }
+    }
+}
+
+impl From<ClientBindings> for KeyBinding {
+    fn from(bindings: ClientBindings) -> Self {
+        Self::PerClient(bindings)
+    }
+}
+
+impl fmt::Debug for ClientBindings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut classes: Vec<&String> = self.classes.keys().collect();
+        classes.sort();
+        f.debug_struct("ClientBindings")
+            .field("classes", &classes)
+            .field("default", &self.default.is_some())
+            .finish()
+    }
+}
+
+/**
+ * Bind an action to a sequence of key presses, adding any intermediate chords that are needed.
+ *
+ * A sequence of a single key is a normal key binding. Binding an action to a sequence that
+ * replaces an existing action is fine, but a sequence can not pass through a key that is bound
+ * to an action or end on the prefix of an existing chord. Key releases can only be bound on
+ * their own, not as part of a chord.
+ */
+pub fn bind_key_sequence(
+    bindings: &mut KeyBindings,
+    keys: &[KeyCode],
+    action: impl Into<KeyBinding>,
+) -> Result<()> {
+    let (last, prefix) = match keys.split_last() {
+        Some(split) => split,
+        None => return Err(anyhow!("empty key sequence")),
+    };
+    if !prefix.is_empty() && keys.iter().any(|k| k.release) {
+        return Err(anyhow!("key releases can not be part of a key chord"));
+    }
+
+    let mut current = bindings;
+    for k in prefix {
+        let next = current
+            .entry(*k)
+            .or_insert_with(|| KeyBinding::Chord(HashMap::new()));
+        current = match next {
+            KeyBinding::Chord(inner) => inner,
+            _ => return Err(anyhow!("{:?} is already bound to an action", k)),
+        };
+    }
+
+    if let Some(KeyBinding::Chord(_)) = current.get(last) {
+        return Err(anyhow!("{:?} is already the prefix of a key chord", last));
+    }
+    current.insert(*last, action.into());
+    Ok(())
+}
+
+// The binding for a sequence of key presses, if there is one
+pub(crate) fn find_binding<'a>(
+    bindings: &'a mut KeyBindings,
+    keys: &[KeyCode],
+) -> Option<&'a mut KeyBinding> {
+    let (first, rest) = keys.split_first()?;
+    let mut binding = bindings.get_mut(first)?;
+    for k in rest {
+        binding = match binding {
+            KeyBinding::Chord(inner) => inner.get_mut(k)?,
+            _ => return None,
+        };
+    }
+
+    Some(binding)
+}
+
+// Remove the binding for a sequence of key presses along with any chords that are left empty
+pub(crate) fn unbind_key_sequence(
+    bindings: &mut KeyBindings,
+    keys: &[KeyCode],
+) -> Option<KeyBinding> {
+    let (first, rest) = keys.split_first()?;
+    if rest.is_empty() {
+        return bindings.remove(first);
+    }
+
+    let removed = match bindings.get_mut(first)? {
+        KeyBinding::Chord(inner) => {
+            let removed = unbind_key_sequence(inner, rest)?;
+            if inner.is_empty() {
+                bindings.remove(first);
+            }
+            removed
+        }
+        _ => return None,
+    };
+
+    Some(removed)
+}
+
+// Every bound sequence of key presses
+pub(crate) fn collect_key_sequences(
+    bindings: &KeyBindings,
+    prefix: &mut Vec<KeyCode>,
+    sequences: &mut Vec<Vec<KeyCode>>,
+) {
+    for (k, binding) in bindings.iter() {
+        prefix.push(*k);
+        match binding {
+            KeyBinding::Chord(inner) => collect_key_sequences(inner, prefix, sequences),
+            _ => sequences.push(prefix.clone()),
+        }
+        prefix.pop();
+    }
+}
+
+/// A key press (or release) and held modifiers
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub struct KeyCode {
     /// The held modifier mask
     pub mask: u16,
     /// The key code that was held
     pub code: u8,
+    /// Whether the key was released rather than pressed
+    pub release: bool,
 }
 
 impl KeyCode {
+    #[cfg(feature = "xcb")]
     pub(crate) fn from_key_press(k: &xcb::KeyPressEvent) -> KeyCode {
         KeyCode {
             mask: k.state(),
             code: k.detail(),
+            release: false,
+        }
+    }
+
+    #[cfg(feature = "xcb")]
+    pub(crate) fn from_key_release(k: &xcb::KeyReleaseEvent) -> KeyCode {
+        KeyCode {
+            mask: k.state(),
+            code: k.detail(),
+            release: true,
+        }
+    }
+
+    #[cfg(feature = "x11rb")]
+    pub(crate) fn from_x11rb_key_press(k: &x11rb::protocol::xproto::KeyPressEvent) -> KeyCode {
+        KeyCode {
+            mask: k.state,
+            code: k.detail,
+            release: k.response_type & 0x7f == x11rb::protocol::xproto::KEY_RELEASE_EVENT,
         }
     }
 
     pub(crate) fn ignoring_modifier(&self, mask: u16) -> KeyCode {
         KeyCode {
             mask: self.mask & !mask,
-            code: self.code,
+            ..*self
         }
     }
 }
@@ -111,12 +400,12 @@ impl ModifierKey {
 
 impl From<ModifierKey> for u16 {
     fn from(m: ModifierKey) -> u16 {
-        (match m {
-            ModifierKey::Ctrl => xcb::MOD_MASK_CONTROL,
-            ModifierKey::Alt => xcb::MOD_MASK_1,
-            ModifierKey::Shift => xcb::MOD_MASK_SHIFT,
-            ModifierKey::Meta => xcb::MOD_MASK_4,
-        }) as u16
+        match m {
+            ModifierKey::Ctrl => MOD_MASK_CONTROL,
+            ModifierKey::Alt => MOD_MASK_1,
+            ModifierKey::Shift => MOD_MASK_SHIFT,
+            ModifierKey::Meta => MOD_MASK_4,
+        }
     }
 }
 
@@ -180,7 +469,8 @@ pub enum MouseEventKind {
 /// A mouse movement or button event
 #[derive(Debug, Clone)]
 pub struct MouseEvent {
-    /// The ID of the window that was contained the click
+    /// The ID of the window that was contained the click (or the root window if the event
+    /// did not take place over a child window)
     pub id: WinId,
     /// Absolute coordinate of the event
     pub rpt: Point,
@@ -211,10 +501,20 @@ impl MouseEvent {
         }
     }
 
+    // Grabs are made on the root window so the window under the cursor is the event child
+    fn event_window(event: WinId, child: WinId) -> WinId {
+        if child == 0 {
+            event
+        } else {
+            child
+        }
+    }
+
+    #[cfg(feature = "xcb")]
     pub(crate) fn from_press(e: &xcb::ButtonPressEvent) -> Result<Self> {
         let state = MouseState::from_event(e.detail(), e.state())?;
         Ok(Self::new(
-            e.event(),
+            Self::event_window(e.event(), e.child()),
             e.root_x(),
             e.root_y(),
             e.event_x(),
@@ -224,10 +524,11 @@ impl MouseEvent {
         ))
     }
 
+    #[cfg(feature = "xcb")]
     pub(crate) fn from_release(e: &xcb::ButtonReleaseEvent) -> Result<Self> {
         let state = MouseState::from_event(e.detail(), e.state())?;
         Ok(Self::new(
-            e.event(),
+            Self::event_window(e.event(), e.child()),
             e.root_x(),
             e.root_y(),
             e.event_x(),
@@ -237,10 +538,14 @@ impl MouseEvent {
         ))
     }
 
+    #[cfg(feature = "xcb")]
     pub(crate) fn from_motion(e: &xcb::MotionNotifyEvent) -> Result<Self> {
-        let state = MouseState::from_event(e.detail(), e.state())?;
+        // The detail of a motion event is not the button being held so we need to pull that
+        // from the button mask section of the event state instead.
+        let held = (1..=5).find(|b| e.state() & (xcb::BUTTON_MASK_1 << (b - 1)) as u16 > 0);
+        let state = MouseState::from_event(held.unwrap_or(0), e.state())?;
         Ok(Self::new(
-            e.event(),
+            Self::event_window(e.event(), e.child()),
             e.root_x(),
             e.root_y(),
             e.event_x(),
@@ -249,4 +554,39 @@ impl MouseEvent {
             MouseEventKind::Motion,
         ))
     }
+
+    #[cfg(feature = "x11rb")]
+    pub(crate) fn from_x11rb_button(
+        e: &x11rb::protocol::xproto::ButtonPressEvent,
+        kind: MouseEventKind,
+    ) -> Result<Self> {
+        let state = MouseState::from_event(e.detail, e.state)?;
+        Ok(Self::new(
+            Self::event_window(e.event, e.child),
+            e.root_x,
+            e.root_y,
+            e.event_x,
+            e.event_y,
+            state,
+            kind,
+        ))
+    }
+
+    #[cfg(feature = "x11rb")]
+    pub(crate) fn from_x11rb_motion(
+        e: &x11rb::protocol::xproto::MotionNotifyEvent,
+    ) -> Result<Self> {
+        let first = u16::from(x11rb::protocol::xproto::ButtonMask::M1);
+        let held = (1..=5).find(|b| e.state & (first << (b - 1)) > 0);
+        let state = MouseState::from_event(held.unwrap_or(0), e.state)?;
+        Ok(Self::new(
+            Self::event_window(e.event, e.child),
+            e.root_x,
+            e.root_y,
+            e.event_x,
+            e.event_y,
+            state,
+            MouseEventKind::Motion,
+        ))
+    }
 }
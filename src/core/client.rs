@@ -1,5 +1,5 @@
 //! Metadata around X clients and manipulating them
-use crate::data_types::WinId;
+use crate::data_types::{Region, SizeHints, WinId};
 
 /**
  * Meta-data around a client window that we are handling.
@@ -13,11 +13,16 @@ pub struct Client {
     wm_name: String,
     wm_class: String,
     workspace: usize,
+    pub(crate) transient_for: Option<WinId>,
+    pub(crate) size_hints: SizeHints,
     // state flags
-    floating: bool,
+    pub(crate) floating: bool,
     pub(crate) fullscreen: bool,
+    pub(crate) urgent: bool,
     pub(crate) mapped: bool,
     pub(crate) wm_managed: bool,
+    // the region the client was last given by a layout
+    pub(crate) layout_region: Option<Region>,
 }
 
 impl Client {
@@ -34,10 +39,14 @@ impl Client {
             wm_name,
             wm_class,
             workspace,
+            transient_for: None,
+            size_hints: SizeHints::default(),
             floating,
             fullscreen: false,
+            urgent: false,
             mapped: false,
             wm_managed: true,
+            layout_region: None,
         }
     }
 
@@ -66,6 +75,26 @@ impl Client {
         self.workspace = workspace
     }
 
+    /// The parent window of this client if it is a transient window (e.g. a dialog)
+    pub fn transient_for(&self) -> Option<WinId> {
+        self.transient_for
+    }
+
+    /// The WM_NORMAL_HINTS size hints that this client has set
+    pub fn size_hints(&self) -> SizeHints {
+        self.size_hints
+    }
+
+    /// Is this client currently floating?
+    pub fn is_floating(&self) -> bool {
+        self.floating
+    }
+
+    /// Is this client currently requesting the user's attention?
+    pub fn is_urgent(&self) -> bool {
+        self.urgent
+    }
+
     pub(crate) fn set_name(&mut self, name: impl Into<String>) {
         self.wm_name = name.into()
     }
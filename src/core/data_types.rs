@@ -3,6 +3,7 @@ use crate::{
     hooks,
     layout::{side_stack, Layout, LayoutConf},
 };
+use std::{cmp, time::Duration};
 
 /// Output of a Layout function: the new position a window should take
 pub type ResizeAction = (WinId, Option<Region>);
@@ -38,6 +39,10 @@ pub struct Config<'a> {
     pub focused_border: u32,
     /// Unfocused boder color
     pub unfocused_border: u32,
+    /// Border color for clients that are requesting attention
+    pub urgent_border: u32,
+    /// How to respond to clients asking to be focused through _NET_ACTIVE_WINDOW
+    pub focus_stealing: FocusStealing,
     /// The width of window borders in pixels
     pub border_px: u32,
     /// The size of gaps between windows in pixels.
@@ -48,8 +53,12 @@ pub struct Config<'a> {
     pub show_bar: bool,
     /// True if the status bar should be at the top of the screen, false if it should be at the bottom
     pub top_bar: bool,
-    /// Height of space reserved for status bars in pixels
+    /// Height of space reserved for status bars in pixels. The built in status bar does not set a
+    /// strut so this is reserved in addition to the struts of any dock windows, other than on
+    /// screens where a dock has reserved space of its own.
     pub bar_height: u32,
+    /// How long to wait for the next key of a key chord before giving up. None waits forever.
+    pub key_chord_timeout: Option<Duration>,
     /// User supplied Hooks for modifying WindowManager behaviour
     pub hooks: Vec<Box<dyn hooks::Hook>>,
 }
@@ -66,12 +75,15 @@ impl<'a> Config<'a> {
             ],
             focused_border: 0xcc241d,   // #cc241d
             unfocused_border: 0x3c3836, // #3c3836
+            urgent_border: 0xd79921,    // #d79921
+            focus_stealing: FocusStealing::PagersOnly,
             border_px: 2,
             gap_px: 5,
             main_ratio_step: 0.05,
             show_bar: true,
             top_bar: true,
             bar_height: 18,
+            key_chord_timeout: Some(Duration::from_secs(2)),
             hooks: vec![],
         }
     }
@@ -93,6 +105,42 @@ pub enum Change {
     Less,
 }
 
+/// How to respond to a _NET_ACTIVE_WINDOW request to focus a client
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FocusStealing {
+    /// Always switch to the workspace of the requested client and focus it
+    Allow,
+    /// Only honour requests from pagers and taskbars: applications asking for focus are marked
+    /// as urgent instead
+    PagersOnly,
+    /// Never change focus: the requested client is marked as urgent instead
+    Deny,
+}
+
+/// The ICCCM input focus models that a client can follow (see section 4.1.7 of the ICCCM)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FocusModel {
+    /// The client never expects keyboard input
+    NoInput,
+    /// The client expects keyboard input and relies on the window manager to set focus
+    Passive,
+    /// The client expects keyboard input and may also set focus to its other windows itself
+    LocallyActive,
+    /// The client sets input focus itself when sent WM_TAKE_FOCUS
+    GloballyActive,
+}
+
+/// The ICCCM WM_STATE of a client window (see section 4.1.3.1 of the ICCCM)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WindowState {
+    /// The window is not being managed
+    Withdrawn,
+    /// The window is visible
+    Normal,
+    /// The window is being managed but is currently hidden
+    Iconic,
+}
+
 /// X window border kind
 #[derive(Debug)]
 pub enum Border {
@@ -124,3 +172,192 @@ impl Region {
         (self.x, self.y, self.w, self.h)
     }
 }
+
+/**
+ * Space reserved at the edges of the root window by a dock, as set through the EWMH
+ * _NET_WM_STRUT_PARTIAL property.
+ *
+ * Each edge gives the thickness of the reserved space in pixels along with the (start, end)
+ * coordinates along that edge that it covers.
+ */
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Strut {
+    /// Space reserved at the left edge of the root window
+    pub left: u32,
+    /// Space reserved at the right edge of the root window
+    pub right: u32,
+    /// Space reserved at the top edge of the root window
+    pub top: u32,
+    /// Space reserved at the bottom edge of the root window
+    pub bottom: u32,
+    /// The (start, end) y coordinates covered by the left strut
+    pub left_y: (u32, u32),
+    /// The (start, end) y coordinates covered by the right strut
+    pub right_y: (u32, u32),
+    /// The (start, end) x coordinates covered by the top strut
+    pub top_x: (u32, u32),
+    /// The (start, end) x coordinates covered by the bottom strut
+    pub bottom_x: (u32, u32),
+}
+
+/**
+ * The size hints that a client has set through the ICCCM WM_NORMAL_HINTS property.
+ *
+ * All sizes are in pixels and each hint is None if the client did not set it. Aspect ratios are
+ * stored as width / height.
+ */
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct SizeHints {
+    /// The minimum (w, h) the client should be given
+    pub min: Option<(u32, u32)>,
+    /// The maximum (w, h) the client should be given
+    pub max: Option<(u32, u32)>,
+    /// The (w, h) that increments are applied on top of
+    pub base: Option<(u32, u32)>,
+    /// The (w, h) steps that the client should be resized in
+    pub inc: Option<(u32, u32)>,
+    /// The minimum and maximum aspect ratio of the client
+    pub aspect: Option<(f32, f32)>,
+}
+
+impl SizeHints {
+    /**
+     * Parse the raw 32 bit values of a WM_NORMAL_HINTS property, returning None if there are
+     * too few values for it to be a valid property.
+     *
+     * The ICCCM property is 18 values long but older clients may set the 15 value pre-ICCCM
+     * version, which has no base size or window gravity.
+     */
+    pub fn from_wm_normal_hints(v: &[u32]) -> Option<Self> {
+        // WM_SIZE_HINTS flags: see section 4.1.2.3 of the ICCCM
+        const P_MIN_SIZE: u32 = 1 << 4;
+        const P_MAX_SIZE: u32 = 1 << 5;
+        const P_RESIZE_INC: u32 = 1 << 6;
+        const P_ASPECT: u32 = 1 << 7;
+        const P_BASE_SIZE: u32 = 1 << 8;
+
+        if v.len() < 15 {
+            return None;
+        }
+
+        let flags = v[0];
+        let pair = |flag: u32, i: usize| {
+            if flags & flag > 0 && v.len() > i + 1 {
+                Some((v[i], v[i + 1]))
+            } else {
+                None
+            }
+        };
+        let ratio = |(n, d): (u32, u32)| if d > 0 { n as f32 / d as f32 } else { 0.0 };
+
+        Some(Self {
+            min: pair(P_MIN_SIZE, 5),
+            max: pair(P_MAX_SIZE, 7),
+            inc: pair(P_RESIZE_INC, 9),
+            aspect: pair(P_ASPECT, 11)
+                .and_then(|min| pair(P_ASPECT, 13).map(|max| (ratio(min), ratio(max)))),
+            base: pair(P_BASE_SIZE, 15),
+        })
+    }
+
+    /// Does this client request a fixed size? (min and max are the same)
+    pub fn is_fixed(&self) -> bool {
+        matches!((self.min, self.max), (Some(min), Some(max)) if min == max)
+    }
+
+    /**
+     * The size closest to (w, h) that satisfies these hints, following the algorithm described
+     * in section 4.1.2.3 of the ICCCM. Note that the result may be larger than the requested
+     * size if the client has a minimum size set.
+     */
+    pub fn apply(&self, w: u32, h: u32) -> (u32, u32) {
+        // Per the ICCCM, base and min are each used in place of the other if missing
+        let (bw, bh) = self.base.or(self.min).unwrap_or((0, 0));
+        let (mw, mh) = self.min.or(self.base).unwrap_or((0, 0));
+        let (mut w, mut h) = (w.saturating_sub(bw), h.saturating_sub(bh));
+
+        if let Some((min_a, max_a)) = self.aspect {
+            if h > 0 && max_a > 0.0 && w as f32 / h as f32 > max_a {
+                w = (h as f32 * max_a + 0.5) as u32;
+            } else if w > 0 && min_a > 0.0 && (w as f32 / h as f32) < min_a {
+                h = (w as f32 / min_a + 0.5) as u32;
+            }
+        }
+
+        if let Some((iw, ih)) = self.inc {
+            if iw > 0 {
+                w -= w % iw;
+            }
+            if ih > 0 {
+                h -= h % ih;
+            }
+        }
+
+        let (mut w, mut h) = (cmp::max(w + bw, mw), cmp::max(h + bh, mh));
+        if let Some((xw, xh)) = self.max {
+            if xw > 0 {
+                w = cmp::min(w, xw);
+            }
+            if xh > 0 {
+                h = cmp::min(h, xh);
+            }
+        }
+
+        (w, h)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size_hints_snap_to_increments_above_base() {
+        let hints = SizeHints {
+            base: Some((4, 2)),
+            inc: Some((10, 20)),
+            ..SizeHints::default()
+        };
+        assert_eq!(hints.apply(109, 95), (104, 82));
+    }
+
+    #[test]
+    fn size_hints_respect_min_and_max() {
+        let hints = SizeHints {
+            min: Some((50, 50)),
+            max: Some((100, 200)),
+            ..SizeHints::default()
+        };
+        assert_eq!(hints.apply(20, 500), (50, 200));
+        assert!(!hints.is_fixed());
+    }
+
+    #[test]
+    fn size_hints_preserve_aspect_ratio() {
+        let hints = SizeHints {
+            aspect: Some((1.0, 1.0)),
+            ..SizeHints::default()
+        };
+        assert_eq!(hints.apply(300, 200), (200, 200));
+        assert_eq!(hints.apply(200, 300), (200, 200));
+    }
+
+    #[test]
+    fn pre_icccm_size_hints_have_no_base_size() {
+        // flags: PMinSize | PMaxSize | PBaseSize, min 10x20, max 30x40
+        let mut v = vec![0; 15];
+        v[0] = (1 << 4) | (1 << 5) | (1 << 8);
+        v[5..9].copy_from_slice(&[10, 20, 30, 40]);
+
+        let hints = SizeHints::from_wm_normal_hints(&v).unwrap();
+        assert_eq!(hints.min, Some((10, 20)));
+        assert_eq!(hints.max, Some((30, 40)));
+        assert_eq!(hints.base, None);
+
+        v.extend_from_slice(&[5, 6, 0]);
+        let hints = SizeHints::from_wm_normal_hints(&v).unwrap();
+        assert_eq!(hints.base, Some((5, 6)));
+
+        assert_eq!(SizeHints::from_wm_normal_hints(&v[..14]), None);
+    }
+}
@@ -1,10 +1,14 @@
 //! Utility functions for use in other parts of penrose
 use crate::{
-    bindings::{CodeMap, KeyCode},
+    bindings::{CodeMap, KeyCode, ModifierKey},
     Result, Selector,
 };
 
+#[cfg(any(feature = "xcb", feature = "x11rb"))]
+use crate::xconnection::XConn;
+
 use std::{
+    convert::TryFrom,
     io::Read,
     process::{Command, Stdio},
 };
@@ -93,6 +97,40 @@ pub fn keycodes_from_xmodmap() -> CodeMap {
     }
 }
 
+/**
+ * Fetch the current keyboard mapping from the X server and convert it into a map of key names
+ * to key codes.
+ *
+ * This opens a short lived connection to the X server so that key bindings can be parsed before
+ * the WindowManager is started, using [XcbConnection][crate::XcbConnection] if the `xcb` feature
+ * is enabled and [X11rbConnection][crate::X11rbConnection] otherwise. The names are the same as
+ * those shown by 'xmodmap -pke'.
+ */
+#[cfg(any(feature = "xcb", feature = "x11rb"))]
+pub fn keycodes_from_x_server() -> Result<CodeMap> {
+    #[cfg(feature = "xcb")]
+    let conn = crate::XcbConnection::new()?;
+    #[cfg(not(feature = "xcb"))]
+    let conn = crate::X11rbConnection::new()?;
+    conn.keycodes()
+}
+
+/**
+ * Fetch the keyboard mapping for [gen_keybindings][crate::gen_keybindings], logging rather than
+ * returning any error so that a failure to reach the X server results in no key bindings being
+ * generated instead of a panic.
+ */
+#[cfg(any(feature = "xcb", feature = "x11rb"))]
+pub fn keycodes_for_key_bindings() -> Option<CodeMap> {
+    match keycodes_from_x_server() {
+        Ok(codes) => Some(codes),
+        Err(e) => {
+            error!("unable to fetch keycodes from the X server: {}", e);
+            None
+        }
+    }
+}
+
 /**
  * Convert user friendly key bindings into X keycodes.
  *
@@ -107,41 +145,64 @@ pub fn keycodes_from_xmodmap() -> CodeMap {
  *   C - Ctrl
  *   S - Shift
  *
+ * Prefixing a binding with '@' (for example '@M-a') matches the key being
+ * released rather than pressed.
+ *
  * The user friendly patterns are parsed into a modifier mask and X key code
- * pair that is then grabbed by penrose to trigger the bound action.
+ * pair that is then grabbed by penrose to trigger the bound action. Returns
+ * None if the key name or any of the modifiers are unknown.
  */
 pub fn parse_key_binding(pattern: impl Into<String>, known_codes: &CodeMap) -> Option<KeyCode> {
     let s = pattern.into();
-    let mut parts: Vec<&str> = s.split('-').collect();
+    let (release, key) = match s.strip_prefix('@') {
+        Some(key) => (true, key),
+        None => (false, s.as_ref()),
+    };
+    let mut parts: Vec<&str> = key.split('-').collect();
     match known_codes.get(parts.remove(parts.len() - 1)) {
         Some(code) => {
             let mask = parts
                 .iter()
-                .map(|&s| match s {
-                    "A" => xcb::MOD_MASK_1,
-                    "M" => xcb::MOD_MASK_4,
-                    "S" => xcb::MOD_MASK_SHIFT,
-                    "C" => xcb::MOD_MASK_CONTROL,
-                    _ => panic!("invalid key binding prefix: {}", s),
-                })
-                .fold(0, |acc, v| acc | v);
+                .map(|&s| ModifierKey::try_from(s).ok().map(u16::from))
+                .try_fold(0, |acc, v| v.map(|v| acc | v))?;
 
             debug!("binding '{}' as [{}, {}]", s, mask, code);
             Some(KeyCode {
-                mask: mask as u16,
+                mask,
                 code: *code,
+                release,
             })
         }
         None => None,
     }
 }
 
+/**
+ * Convert a space separated sequence of user friendly key bindings into X keycodes.
+ *
+ * Each key in the sequence follows the same rules as [parse_key_binding]: "M-a t" is the
+ * chord of Super+a followed by t. Returns None if any of the keys are unknown.
+ */
+pub fn parse_key_sequence(
+    pattern: impl Into<String>,
+    known_codes: &CodeMap,
+) -> Option<Vec<KeyCode>> {
+    let s = pattern.into();
+    let keys: Option<Vec<KeyCode>> = s
+        .split_whitespace()
+        .map(|k| parse_key_binding(k, known_codes))
+        .collect();
+
+    keys.filter(|ks| !ks.is_empty())
+}
+
 /// Create a Vec of index selectors for the given input slice
 pub fn index_selectors<'a, T>(len: usize) -> Vec<Selector<'a, T>> {
     (0..len).map(Selector::Index).collect()
 }
 
 // Helper functions for XCB based operations
+#[cfg(feature = "xcb")]
 pub(crate) mod xcb_util {
     use crate::{data_types::Region, Result};
     use anyhow::anyhow;
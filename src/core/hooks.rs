@@ -3,8 +3,11 @@ use crate::{
     client::Client,
     data_types::{Region, WinId},
     manager::WindowManager,
+    xconnection::XError,
 };
 
+use std::time::Duration;
+
 /**
  * impls of Hook can be registered to receive events during WindowManager operation. Each hook
  * point is documented as individual methods detailing when and how they will be called. All Hook
@@ -110,14 +113,61 @@ pub trait Hook {
      */
     fn focus_change(&mut self, _wm: &mut WindowManager, _id: WinId) {}
 
+    /**
+     * Called after a Client is toggled between floating and tiled.
+     * Arguments are the ID of the Client and whether or not it is now floating.
+     */
+    fn floating_change(&mut self, _wm: &mut WindowManager, _id: WinId, _floating: bool) {}
+
+    /**
+     * Called when a Client that does not currently have focus marks itself as urgent, either
+     * through the ICCCM urgency hint or _NET_WM_STATE_DEMANDS_ATTENTION.
+     * Argument is the ID of the Client.
+     */
+    fn client_urgent(&mut self, _wm: &mut WindowManager, _id: WinId) {}
+
+    /**
+     * Called when the X server reports an error, either in response to a request made by the
+     * WindowManager or asynchronously through the event loop.
+     * Errors such as BadWindow are expected from time to time when a client is destroyed while
+     * requests are in flight, so the WindowManager logs the error and carries on.
+     */
+    fn x_error(&mut self, _wm: &mut WindowManager, _err: &XError) {}
+
+    /**
+     * Called when a key chord is started, continued or finished.
+     * Argument is the keys pressed so far (e.g. "M-a t") while the WindowManager is waiting for
+     * the next key of the chord, or None once the chord has run its action, been cancelled or
+     * timed out.
+     */
+    fn key_chord_pending(&mut self, _wm: &mut WindowManager, _pending: Option<&str>) {}
+
+    /**
+     * Called after the WindowManager switches to a different set of key bindings.
+     * Argument is the name of the newly active mode.
+     */
+    fn mode_changed(&mut self, _wm: &mut WindowManager, _mode: &str) {}
+
     /**
      * Called at the end of the main WindowManager event loop once each XEvent has been handled.
      *
      * Usefull if you want to ensure that all other event processing has taken place before you
-     * take action in response to another hook.
+     * take action in response to another hook. Also called when no XEvent arrives within the
+     * shortest [poll_interval][Hook::poll_interval] requested by any hook.
      */
     fn event_handled(&mut self, _wm: &mut WindowManager) {}
 
+    /**
+     * The longest time that the WindowManager should wait for an XEvent before calling
+     * [event_handled][Hook::event_handled] anyway.
+     *
+     * Hooks that need to react to something other than the WindowManager's X connection (such
+     * as a second connection of their own) should return Some here.
+     */
+    fn poll_interval(&self) -> Option<Duration> {
+        None
+    }
+
     /**
      * Called once at window manager startup
      */
@@ -0,0 +1,542 @@
+/*! Converting between X keysyms and the key names used in key bindings
+ *
+ *  The X server reports the keyboard layout as a list of keysyms for each key code (the symbol
+ *  produced when the key is pressed on its own, followed by the symbol produced with shift held
+ *  and so on). Key bindings are written using the same keysym names that are shown by
+ *  `xmodmap -pke`, so the names of the keysyms penrose knows about are listed here in order to
+ *  build a [CodeMap] from the server's [KeyboardMapping].
+ *
+ *  The names used are those from X11/keysymdef.h and X11/XF86keysym.h. Unicode keysyms that do
+ *  not have a name are given the name "U<hex code point>", as they are by xmodmap.
+ */
+use crate::bindings::{CodeMap, KeyCode, KeyboardMapping, ModifierKey};
+
+use std::collections::HashMap;
+
+use strum::IntoEnumIterator;
+
+// Latin-1 keysyms have the same value as the character they represent
+const LATIN1: &[&str] = &[
+    "space",
+    "exclam",
+    "quotedbl",
+    "numbersign",
+    "dollar",
+    "percent",
+    "ampersand",
+    "apostrophe",
+    "parenleft",
+    "parenright",
+    "asterisk",
+    "plus",
+    "comma",
+    "minus",
+    "period",
+    "slash",
+    "0",
+    "1",
+    "2",
+    "3",
+    "4",
+    "5",
+    "6",
+    "7",
+    "8",
+    "9",
+    "colon",
+    "semicolon",
+    "less",
+    "equal",
+    "greater",
+    "question",
+    "at",
+    "A",
+    "B",
+    "C",
+    "D",
+    "E",
+    "F",
+    "G",
+    "H",
+    "I",
+    "J",
+    "K",
+    "L",
+    "M",
+    "N",
+    "O",
+    "P",
+    "Q",
+    "R",
+    "S",
+    "T",
+    "U",
+    "V",
+    "W",
+    "X",
+    "Y",
+    "Z",
+    "bracketleft",
+    "backslash",
+    "bracketright",
+    "asciicircum",
+    "underscore",
+    "grave",
+    "a",
+    "b",
+    "c",
+    "d",
+    "e",
+    "f",
+    "g",
+    "h",
+    "i",
+    "j",
+    "k",
+    "l",
+    "m",
+    "n",
+    "o",
+    "p",
+    "q",
+    "r",
+    "s",
+    "t",
+    "u",
+    "v",
+    "w",
+    "x",
+    "y",
+    "z",
+    "braceleft",
+    "bar",
+    "braceright",
+    "asciitilde",
+];
+
+const LATIN1_SUPPLEMENT: &[&str] = &[
+    "nobreakspace",
+    "exclamdown",
+    "cent",
+    "sterling",
+    "currency",
+    "yen",
+    "brokenbar",
+    "section",
+    "diaeresis",
+    "copyright",
+    "ordfeminine",
+    "guillemotleft",
+    "notsign",
+    "hyphen",
+    "registered",
+    "macron",
+    "degree",
+    "plusminus",
+    "twosuperior",
+    "threesuperior",
+    "acute",
+    "mu",
+    "paragraph",
+    "periodcentered",
+    "cedilla",
+    "onesuperior",
+    "masculine",
+    "guillemotright",
+    "onequarter",
+    "onehalf",
+    "threequarters",
+    "questiondown",
+    "Agrave",
+    "Aacute",
+    "Acircumflex",
+    "Atilde",
+    "Adiaeresis",
+    "Aring",
+    "AE",
+    "Ccedilla",
+    "Egrave",
+    "Eacute",
+    "Ecircumflex",
+    "Ediaeresis",
+    "Igrave",
+    "Iacute",
+    "Icircumflex",
+    "Idiaeresis",
+    "ETH",
+    "Ntilde",
+    "Ograve",
+    "Oacute",
+    "Ocircumflex",
+    "Otilde",
+    "Odiaeresis",
+    "multiply",
+    "Oslash",
+    "Ugrave",
+    "Uacute",
+    "Ucircumflex",
+    "Udiaeresis",
+    "Yacute",
+    "THORN",
+    "ssharp",
+    "agrave",
+    "aacute",
+    "acircumflex",
+    "atilde",
+    "adiaeresis",
+    "aring",
+    "ae",
+    "ccedilla",
+    "egrave",
+    "eacute",
+    "ecircumflex",
+    "ediaeresis",
+    "igrave",
+    "iacute",
+    "icircumflex",
+    "idiaeresis",
+    "eth",
+    "ntilde",
+    "ograve",
+    "oacute",
+    "ocircumflex",
+    "otilde",
+    "odiaeresis",
+    "division",
+    "oslash",
+    "ugrave",
+    "uacute",
+    "ucircumflex",
+    "udiaeresis",
+    "yacute",
+    "thorn",
+    "ydiaeresis",
+];
+
+// Function keys F1 - F35 are contiguous
+const F1: u32 = 0xffbe;
+const F35: u32 = 0xffe0;
+
+// Keypad digits KP_0 - KP_9 are contiguous
+const KP_0: u32 = 0xffb0;
+const KP_9: u32 = 0xffb9;
+
+// Keysyms for unicode characters are the code point with this bit set
+const UNICODE_OFFSET: u32 = 0x0100_0000;
+const UNICODE_MAX: u32 = 0x0110_ffff;
+
+// Cancels an in progress key chord
+pub(crate) const ESCAPE: u32 = 0xff1b;
+
+const NAMED: &[(u32, &str)] = &[
+    // TTY function keys
+    (0xff08, "BackSpace"),
+    (0xff09, "Tab"),
+    (0xff0a, "Linefeed"),
+    (0xff0b, "Clear"),
+    (0xff0d, "Return"),
+    (0xff13, "Pause"),
+    (0xff14, "Scroll_Lock"),
+    (0xff15, "Sys_Req"),
+    (0xff1b, "Escape"),
+    (0xffff, "Delete"),
+    (0xff20, "Multi_key"),
+    // cursor control
+    (0xff50, "Home"),
+    (0xff51, "Left"),
+    (0xff52, "Up"),
+    (0xff53, "Right"),
+    (0xff54, "Down"),
+    (0xff55, "Prior"),
+    (0xff56, "Next"),
+    (0xff57, "End"),
+    (0xff58, "Begin"),
+    // misc functions
+    (0xff60, "Select"),
+    (0xff61, "Print"),
+    (0xff62, "Execute"),
+    (0xff63, "Insert"),
+    (0xff65, "Undo"),
+    (0xff66, "Redo"),
+    (0xff67, "Menu"),
+    (0xff68, "Find"),
+    (0xff69, "Cancel"),
+    (0xff6a, "Help"),
+    (0xff6b, "Break"),
+    (0xff7e, "Mode_switch"),
+    (0xff7f, "Num_Lock"),
+    // keypad
+    (0xff80, "KP_Space"),
+    (0xff89, "KP_Tab"),
+    (0xff8d, "KP_Enter"),
+    (0xff91, "KP_F1"),
+    (0xff92, "KP_F2"),
+    (0xff93, "KP_F3"),
+    (0xff94, "KP_F4"),
+    (0xff95, "KP_Home"),
+    (0xff96, "KP_Left"),
+    (0xff97, "KP_Up"),
+    (0xff98, "KP_Right"),
+    (0xff99, "KP_Down"),
+    (0xff9a, "KP_Prior"),
+    (0xff9b, "KP_Next"),
+    (0xff9c, "KP_End"),
+    (0xff9d, "KP_Begin"),
+    (0xff9e, "KP_Insert"),
+    (0xff9f, "KP_Delete"),
+    (0xffaa, "KP_Multiply"),
+    (0xffab, "KP_Add"),
+    (0xffac, "KP_Separator"),
+    (0xffad, "KP_Subtract"),
+    (0xffae, "KP_Decimal"),
+    (0xffaf, "KP_Divide"),
+    (0xffbd, "KP_Equal"),
+    // modifiers
+    (0xffe1, "Shift_L"),
+    (0xffe2, "Shift_R"),
+    (0xffe3, "Control_L"),
+    (0xffe4, "Control_R"),
+    (0xffe5, "Caps_Lock"),
+    (0xffe6, "Shift_Lock"),
+    (0xffe7, "Meta_L"),
+    (0xffe8, "Meta_R"),
+    (0xffe9, "Alt_L"),
+    (0xffea, "Alt_R"),
+    (0xffeb, "Super_L"),
+    (0xffec, "Super_R"),
+    (0xffed, "Hyper_L"),
+    (0xffee, "Hyper_R"),
+    // ISO 9995
+    (0xfe03, "ISO_Level3_Shift"),
+    (0xfe08, "ISO_Next_Group"),
+    (0xfe11, "ISO_Level5_Shift"),
+    (0xfe20, "ISO_Left_Tab"),
+    // dead keys
+    (0xfe50, "dead_grave"),
+    (0xfe51, "dead_acute"),
+    (0xfe52, "dead_circumflex"),
+    (0xfe53, "dead_tilde"),
+    (0xfe54, "dead_macron"),
+    (0xfe55, "dead_breve"),
+    (0xfe56, "dead_abovedot"),
+    (0xfe57, "dead_diaeresis"),
+    (0xfe58, "dead_abovering"),
+    (0xfe59, "dead_doubleacute"),
+    (0xfe5a, "dead_caron"),
+    (0xfe5b, "dead_cedilla"),
+    (0xfe5c, "dead_ogonek"),
+    // currency
+    (0x20ac, "EuroSign"),
+    // XF86 vendor specific keys
+    (0x1008_ff02, "XF86MonBrightnessUp"),
+    (0x1008_ff03, "XF86MonBrightnessDown"),
+    (0x1008_ff10, "XF86Standby"),
+    (0x1008_ff11, "XF86AudioLowerVolume"),
+    (0x1008_ff12, "XF86AudioMute"),
+    (0x1008_ff13, "XF86AudioRaiseVolume"),
+    (0x1008_ff14, "XF86AudioPlay"),
+    (0x1008_ff15, "XF86AudioStop"),
+    (0x1008_ff16, "XF86AudioPrev"),
+    (0x1008_ff17, "XF86AudioNext"),
+    (0x1008_ff18, "XF86HomePage"),
+    (0x1008_ff19, "XF86Mail"),
+    (0x1008_ff1b, "XF86Search"),
+    (0x1008_ff1d, "XF86Calculator"),
+    (0x1008_ff26, "XF86Back"),
+    (0x1008_ff27, "XF86Forward"),
+    (0x1008_ff2a, "XF86PowerOff"),
+    (0x1008_ff2d, "XF86ScreenSaver"),
+    (0x1008_ff2f, "XF86Sleep"),
+    (0x1008_ff31, "XF86AudioPause"),
+    (0x1008_ff32, "XF86AudioMedia"),
+    (0x1008_ff33, "XF86MyComputer"),
+    (0x1008_ff59, "XF86Display"),
+    (0x1008_ff8f, "XF86WebCam"),
+    (0x1008_ff93, "XF86Battery"),
+    (0x1008_ff95, "XF86WLAN"),
+    (0x1008_ffa9, "XF86TouchpadToggle"),
+    (0x1008_ffb2, "XF86AudioMicMute"),
+];
+
+/// The name of a keysym as it would be written in a key binding.
+///
+/// Keysyms without a known name are written in hex, matching the output of 'xmodmap -pke'.
+/// Returns None for NoSymbol.
+pub fn keysym_name(keysym: u32) -> Option<String> {
+    match keysym {
+        0 => None,
+        0x20..=0x7e => Some(LATIN1[(keysym - 0x20) as usize].into()),
+        0xa0..=0xff => Some(LATIN1_SUPPLEMENT[(keysym - 0xa0) as usize].into()),
+        F1..=F35 => Some(format!("F{}", keysym - F1 + 1)),
+        KP_0..=KP_9 => Some(format!("KP_{}", keysym - KP_0)),
+        UNICODE_OFFSET..=UNICODE_MAX => {
+            let code_point = keysym - UNICODE_OFFSET;
+            match code_point {
+                // keysyms for Latin-1 characters are always given by their Latin-1 value
+                0x20..=0x7e | 0xa0..=0xff => keysym_name(code_point),
+                _ => Some(format!("U{:04X}", code_point)),
+            }
+        }
+        _ => NAMED
+            .iter()
+            .find(|(sym, _)| *sym == keysym)
+            .map(|(_, name)| String::from(*name))
+            .or_else(|| Some(format!("0x{:04x}", keysym))),
+    }
+}
+
+/// The key code for each key name in a [KeyboardMapping].
+///
+/// Names that can be produced by more than one key are mapped to the key that produces them
+/// with the fewest modifiers held, and then to the lowest key code.
+pub fn code_map(mapping: &KeyboardMapping) -> CodeMap {
+    let mut codes: Vec<&u8> = mapping.keys().collect();
+    codes.sort();
+    let columns = mapping.values().map(|syms| syms.len()).max().unwrap_or(0);
+
+    let mut map = CodeMap::new();
+    for col in 0..columns {
+        for &code in codes.iter() {
+            let name = mapping[code].get(col).and_then(|&sym| keysym_name(sym));
+            if let Some(name) = name {
+                map.entry(name).or_insert(*code);
+            }
+        }
+    }
+
+    map
+}
+
+// The name of the keysym that each key produces when no modifiers are held
+pub(crate) fn primary_names(mapping: &KeyboardMapping) -> HashMap<u8, String> {
+    mapping
+        .iter()
+        .flat_map(|(&code, syms)| syms.iter().find_map(|&s| keysym_name(s)).map(|n| (code, n)))
+        .collect()
+}
+
+// Matches the IsModifierKey macro from Xutil.h: Shift, Control, Alt, Super etc along with the
+// ISO level shifts and locks
+pub(crate) fn is_modifier(keysym: u32) -> bool {
+    matches!(keysym, 0xffe1..=0xffee | 0xfe01..=0xfe13 | 0xff7e | 0xff7f)
+}
+
+// A key press written out in the same form as the key bindings that are parsed by
+// [parse_key_binding][crate::helpers::parse_key_binding]
+pub(crate) fn key_name(k: KeyCode, mapping: &KeyboardMapping) -> String {
+    let key = mapping
+        .get(&k.code)
+        .and_then(|syms| syms.iter().find_map(|&s| keysym_name(s)))
+        .unwrap_or_else(|| k.code.to_string());
+
+    let name = ModifierKey::iter()
+        .filter(|m| m.was_held(k.mask))
+        .map(|m| match m {
+            ModifierKey::Ctrl => "C",
+            ModifierKey::Alt => "A",
+            ModifierKey::Shift => "S",
+            ModifierKey::Meta => "M",
+        })
+        .chain(std::iter::once(key.as_ref()))
+        .collect::<Vec<&str>>()
+        .join("-");
+
+    if k.release {
+        format!("@{}", name)
+    } else {
+        name
+    }
+}
+
+// Split the flat list of keysyms from a GetKeyboardMapping reply into a list for each key code,
+// dropping trailing NoSymbol entries.
+pub(crate) fn mapping_from_reply(
+    min_keycode: u8,
+    keysyms_per_keycode: u8,
+    keysyms: &[u32],
+) -> KeyboardMapping {
+    if keysyms_per_keycode == 0 {
+        return KeyboardMapping::new();
+    }
+
+    keysyms
+        .chunks(keysyms_per_keycode as usize)
+        .zip(min_keycode..=u8::MAX)
+        .map(|(syms, code)| {
+            let len = syms.iter().rposition(|&s| s != 0).map_or(0, |i| i + 1);
+            (code, syms[..len].to_vec())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two keys from a US layout and the same keys from a DE layout
+    fn us() -> KeyboardMapping {
+        mapping_from_reply(29, 2, &[0x79, 0x59, 0x7a, 0x5a])
+    }
+
+    fn de() -> KeyboardMapping {
+        mapping_from_reply(29, 2, &[0x7a, 0x5a, 0x79, 0x59])
+    }
+
+    #[test]
+    fn keysym_names() {
+        assert_eq!(keysym_name(0x61), Some("a".into()));
+        assert_eq!(keysym_name(0xe4), Some("adiaeresis".into()));
+        assert_eq!(keysym_name(0xff0d), Some("Return".into()));
+        assert_eq!(keysym_name(0xffbe + 11), Some("F12".into()));
+        assert_eq!(keysym_name(0xffb3), Some("KP_3".into()));
+        assert_eq!(keysym_name(0x1008_ff12), Some("XF86AudioMute".into()));
+        assert_eq!(keysym_name(0x0100_20ac), Some("U20AC".into()));
+        assert_eq!(keysym_name(0xfd01), Some("0xfd01".into()));
+        assert_eq!(keysym_name(0), None);
+    }
+
+    #[test]
+    fn key_names_match_binding_patterns() {
+        let k = KeyCode {
+            mask: u16::from(ModifierKey::Meta) | u16::from(ModifierKey::Shift),
+            code: 29,
+            release: false,
+        };
+        assert_eq!(key_name(k, &us()), "S-M-y");
+        assert_eq!(key_name(KeyCode { release: true, ..k }, &us()), "@S-M-y");
+        let unknown = KeyCode {
+            mask: 0,
+            code: 99,
+            release: false,
+        };
+        assert_eq!(key_name(unknown, &us()), "99");
+    }
+
+    #[test]
+    fn trailing_no_symbol_entries_are_dropped() {
+        let mapping = mapping_from_reply(8, 3, &[0x61, 0x41, 0, 0, 0, 0]);
+        assert_eq!(mapping[&8], vec![0x61, 0x41]);
+        assert_eq!(mapping[&9], vec![]);
+    }
+
+    #[test]
+    fn code_map_follows_the_layout() {
+        assert_eq!(code_map(&us())["y"], 29);
+        assert_eq!(code_map(&de())["y"], 30);
+        assert_eq!(code_map(&de())["Z"], 29);
+    }
+
+    #[test]
+    fn unmodified_keysyms_are_preferred() {
+        // '2' with '@' above it on 11, and '@' on its own on 12
+        let mapping = mapping_from_reply(11, 2, &[0x32, 0x40, 0x40, 0]);
+        assert_eq!(code_map(&mapping)["at"], 12);
+    }
+
+    #[test]
+    fn primary_names_use_the_first_keysym() {
+        let names = primary_names(&us());
+        assert_eq!(names[&29], "y");
+        assert_eq!(names[&30], "z");
+    }
+}
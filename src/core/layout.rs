@@ -29,6 +29,8 @@ pub struct LayoutConf {
     pub follow_focus: bool,
     /// Should cycling clients wrap at the first and last client?
     pub allow_wrapping: bool,
+    /// Should tiled clients be shrunk to satisfy their WM_NORMAL_HINTS size hints?
+    pub respect_size_hints: bool,
 }
 
 impl LayoutConf {
@@ -40,6 +42,7 @@ impl LayoutConf {
             gapless: false,
             follow_focus: false,
             allow_wrapping: true,
+            respect_size_hints: false,
         }
     }
 }
@@ -130,6 +133,7 @@ impl Layout {
                 gapless: false,
                 follow_focus: false,
                 allow_wrapping: true,
+                respect_size_hints: false,
             },
             f: floating,
             max_main: 1,
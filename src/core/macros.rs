@@ -45,6 +45,12 @@ macro_rules! map(
 );
 
 /// make creating all of the key bindings less verbose
+///
+/// Patterns containing spaces are bound as key chords: "M-a t" runs its action when t is
+/// pressed after M-a.
+///
+/// Key names are resolved using the keyboard mapping from the X server: if it can not be fetched
+/// then the error is logged and no key bindings are generated.
 #[macro_export]
 macro_rules! gen_keybindings(
     // parse a single simple key binding
@@ -52,9 +58,13 @@ macro_rules! gen_keybindings(
         $binding:expr => $action:expr;
         $($tail:tt)*
     } => {
-        match $crate::helpers::parse_key_binding($binding, &$codes) {
+        match $crate::helpers::parse_key_sequence($binding, &$codes) {
             None => panic!("invalid key binding: {}", $binding),
-            Some(key_code) => $map.insert(key_code, $action),
+            Some(keys) => {
+                if let Err(e) = $crate::bindings::bind_key_sequence(&mut $map, &keys, $action) {
+                    panic!("invalid key binding: {}: {}", $binding, e);
+                }
+            }
         };
         gen_keybindings!(@parse $map, $codes, $($tail)*);
     };
@@ -68,13 +78,18 @@ macro_rules! gen_keybindings(
             $(
                 for (k, arg) in $from.into_iter().zip($to.clone()) {
                     let binding = format!($patt, k);
-                    match $crate::helpers::parse_key_binding(binding.clone(), &$codes) {
+                    match $crate::helpers::parse_key_sequence(binding.clone(), &$codes) {
                         None => panic!("invalid key binding: {}", binding),
-                        Some(key_code) => $map.insert(key_code, run_internal!($method, arg)),
+                        Some(keys) => {
+                            let action = run_internal!($method, arg);
+                            if let Err(e) = $crate::bindings::bind_key_sequence(&mut $map, &keys, action) {
+                                panic!("invalid key binding: {}: {}", binding, e);
+                            }
+                        }
                     };
                 }
             )+
-            gen__keybindings!(@parse $map, $codes, $($tail)*);
+            gen_keybindings!(@parse $map, $codes, $($tail)*);
         }
     };
 
@@ -87,9 +102,14 @@ macro_rules! gen_keybindings(
             $(
                 for (k, arg) in $from.into_iter().zip($to.clone()) {
                     let binding = format!($patt, k);
-                    match $crate::helpers::parse_key_binding(binding.clone(), &$codes) {
+                    match $crate::helpers::parse_key_sequence(binding.clone(), &$codes) {
                         None => panic!("invalid key binding: {}", binding),
-                        Some(key_code) => $map.insert(key_code, run_internal!($method, &arg)),
+                        Some(keys) => {
+                            let action = run_internal!($method, &arg);
+                            if let Err(e) = $crate::bindings::bind_key_sequence(&mut $map, &keys, action) {
+                                panic!("invalid key binding: {}: {}", binding, e);
+                            }
+                        }
                     };
                 }
             )+
@@ -121,8 +141,9 @@ macro_rules! gen_keybindings(
     { $($tokens:tt)+ } => {
         {
             let mut map = ::std::collections::HashMap::new();
-            let codes = $crate::helpers::keycodes_from_xmodmap();
-            gen_keybindings!(@parse map, codes, $($tokens)+);
+            if let Some(codes) = $crate::helpers::keycodes_for_key_bindings() {
+                gen_keybindings!(@parse map, codes, $($tokens)+);
+            }
             map
         }
     };
@@ -146,7 +167,7 @@ macro_rules! gen_keybindings_depricated(
             $(
                 match $crate::helpers::parse_key_binding($binding, &keycodes) {
                     None => panic!("invalid key binding: {}", $binding),
-                    Some(key_code) => _map.insert(key_code, $action),
+                    Some(key_code) => _map.insert(key_code, $crate::bindings::KeyBinding::from($action)),
                 };
             )+
 
@@ -157,10 +178,10 @@ macro_rules! gen_keybindings_depricated(
                         None => panic!("invalid key binding: {}", for_ws),
                         Some(key_code) => _map.insert(
                             key_code,
-                            run_internal!(
+                            $crate::bindings::KeyBinding::from(run_internal!(
                                 $ws_action,
                                 &$crate::core::ring::Selector::Index(i)
-                            )
+                            ))
                         ),
                     };
                 )+
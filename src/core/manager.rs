@@ -1,18 +1,35 @@
 //! Main logic for running Penrose
 use crate::{
-    bindings::{KeyBindings, KeyCode, MouseBindings, MouseEvent},
+    bindings::{
+        bind_key_sequence, collect_key_sequences, find_binding, unbind_key_sequence, BindingModes,
+        ClientBindings, CodeMap, FireAndForget, KeyBinding, KeyBindings, KeyCode, KeyboardMapping,
+        MouseBindings, MouseEvent, MouseEventKind, DEFAULT_MODE,
+    },
     client::Client,
     core::ring::{Direction, InsertPoint, Ring, Selector},
-    data_types::{Change, Config, Point, Region, WinId},
-    hooks,
+    data_types::{Change, Config, FocusStealing, Point, Region, Strut, WinId, WindowState},
+    helpers::parse_key_sequence,
+    hooks, keysyms,
     screen::Screen,
     workspace::Workspace,
-    xconnection::{XConn, XEvent},
+    xconnection::{Atom, XConn, XError, XEvent},
+    Result,
 };
 
-use nix::sys::signal::{signal, SigHandler, Signal};
+use anyhow::anyhow;
+use nix::{
+    sys::signal::{kill, signal, SigHandler, Signal},
+    unistd::{gethostname, Pid},
+};
 
-use std::{cell::Cell, collections::HashMap};
+use std::{
+    cell::Cell,
+    cmp,
+    collections::{hash_map::Entry, HashMap},
+    mem,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
 // Relies on all hooks taking &mut WindowManager as the first arg.
 macro_rules! run_hooks(
@@ -23,6 +40,22 @@ macro_rules! run_hooks(
     };
 );
 
+// An in progress mouse drag of a floating client
+#[derive(Debug, Clone, Copy)]
+struct MouseDrag {
+    id: WinId,
+    resize: bool,
+    start: Point,
+    region: Region,
+}
+
+// An in progress key chord: the keys pressed so far and when to stop waiting for the next one
+#[derive(Debug, Clone)]
+struct KeyChord {
+    keys: Vec<KeyCode>,
+    deadline: Option<Instant>,
+}
+
 /**
  * WindowManager is the primary struct / owner of the event loop for penrose.
  * It handles most (if not all) of the communication with XCB and responds to
@@ -35,10 +68,14 @@ pub struct WindowManager<'a> {
     screens: Ring<Screen>,
     workspaces: Ring<Workspace>,
     client_map: HashMap<WinId, Client>,
+    client_list: Vec<WinId>,
+    stacking_order: Vec<WinId>,
     previous_workspace: usize,
     floating_classes: &'static [&'static str],
     focused_border: u32,
     unfocused_border: u32,
+    urgent_border: u32,
+    focus_stealing: FocusStealing,
     border_px: u32,
     gap_px: u32,
     main_ratio_step: f32,
@@ -48,6 +85,17 @@ pub struct WindowManager<'a> {
     hooks: Cell<Vec<Box<dyn hooks::Hook>>>,
     client_insert_point: InsertPoint,
     focused_client: Option<WinId>,
+    docks: HashMap<WinId, Strut>,
+    pending_unmaps: HashMap<WinId, usize>,
+    urgent_clients: Vec<WinId>,
+    mouse_drag: Option<MouseDrag>,
+    keyboard_mapping: KeyboardMapping,
+    key_chord_timeout: Option<Duration>,
+    key_chord: Option<KeyChord>,
+    passed_through: HashMap<u8, WinId>,
+    key_bindings: BindingModes,
+    mouse_bindings: MouseBindings,
+    mode: String,
     running: bool,
 }
 
@@ -60,10 +108,14 @@ impl<'a> WindowManager<'a> {
             screens: Ring::new(vec![]),
             workspaces: Ring::new(vec![]),
             client_map: HashMap::new(),
+            client_list: vec![],
+            stacking_order: vec![],
             previous_workspace: 0,
             floating_classes: config.floating_classes,
             focused_border: config.focused_border,
             unfocused_border: config.unfocused_border,
+            urgent_border: config.urgent_border,
+            focus_stealing: config.focus_stealing,
             border_px: config.border_px,
             gap_px: config.gap_px,
             main_ratio_step: config.main_ratio_step,
@@ -73,6 +125,17 @@ impl<'a> WindowManager<'a> {
             hooks: Cell::new(config.hooks),
             client_insert_point: InsertPoint::First,
             focused_client: None,
+            docks: HashMap::new(),
+            pending_unmaps: HashMap::new(),
+            urgent_clients: vec![],
+            mouse_drag: None,
+            keyboard_mapping: KeyboardMapping::new(),
+            key_chord_timeout: config.key_chord_timeout,
+            key_chord: None,
+            passed_through: HashMap::new(),
+            key_bindings: BindingModes::new(HashMap::new()),
+            mouse_bindings: HashMap::new(),
+            mode: DEFAULT_MODE.into(),
             running: false,
         };
 
@@ -83,13 +146,30 @@ impl<'a> WindowManager<'a> {
                 .map(|name| Workspace::new(*name, layouts.to_vec()))
                 .collect(),
         );
+        wm.keyboard_mapping = wm.x_request(conn.keyboard_mapping()).unwrap_or_default();
         wm.detect_screens();
-        conn.set_wm_properties(&config.workspaces);
-        wm.conn.warp_cursor(None, &wm.screens[0]);
+        wm.x_request(conn.set_wm_properties(&config.workspaces));
+        wm.x_request(conn.warp_cursor(None, &wm.screens[0]));
 
         wm
     }
 
+    // Requests to the X server can fail at any time (e.g. a client destroying its window while
+    // we are still configuring it) so errors are logged and passed on to hooks rather than
+    // treated as fatal.
+    fn x_request<T>(&mut self, res: Result<T>) -> Option<T> {
+        match res {
+            Ok(t) => Some(t),
+            Err(e) => {
+                match e.downcast_ref::<XError>() {
+                    Some(&err) => self.handle_x_error(err),
+                    None => error!("error communicating with the X server: {}", e),
+                }
+                None
+            }
+        }
+    }
+
     fn apply_layout(&mut self, wix: usize) {
         let ws = match self.workspaces.get(wix) {
             Some(ws) => ws,
@@ -104,31 +184,47 @@ impl<'a> WindowManager<'a> {
         if let Some((i, s)) = self.indexed_screen_for_workspace(wix) {
             let lc = ws.layout_conf();
             if !lc.floating {
-                let reg = s.region(self.show_bar);
+                let reg = s.region(true);
                 let gpx = if lc.gapless { 0 } else { self.gap_px };
                 let padding = 2 * (self.border_px + gpx);
+                let floating = self.floating_clients(wix);
 
                 for (id, region) in ws.arrange(reg, &self.client_map) {
                     debug!("configuring {} with {:?}", id, region);
                     if let Some(region) = region {
                         let (x, y, w, h) = region.values();
-                        let reg = Region::new(x + gpx, y + gpx, w - padding, h - padding);
-                        self.conn.position_window(id, reg, self.border_px, false);
+                        let mut reg = Region::new(x + gpx, y + gpx, w - padding, h - padding);
+                        if lc.respect_size_hints {
+                            reg = self.fit_size_hints_within(id, reg);
+                        }
+                        self.x_request(self.conn.position_window(id, reg, self.border_px, false));
+                        if let Some(c) = self.client_map.get_mut(&id) {
+                            c.layout_region = Some(reg);
+                        }
                         self.map_window_if_needed(id);
                     } else {
                         self.unmap_window_if_needed(id);
                     }
                 }
+
+                // floating clients are always kept above tiled clients
+                floating.iter().for_each(|id| self.raise_client(*id));
+                self.update_client_list();
             }
             run_hooks!(layout_applied, self, wix, i);
         }
     }
 
+    // Transient windows are shown and hidden along with their parent
     fn map_window_if_needed(&mut self, id: WinId) {
         if let Some(c) = self.client_map.get_mut(&id) {
             if !c.mapped {
                 c.mapped = true;
-                self.conn.map_window(id);
+                self.x_request(self.conn.map_window(id));
+                self.x_request(self.conn.set_client_state(id, WindowState::Normal));
+                self.transients_of(id)
+                    .iter()
+                    .for_each(|t| self.map_window_if_needed(*t));
             }
         }
     }
@@ -137,8 +233,76 @@ impl<'a> WindowManager<'a> {
         if let Some(c) = self.client_map.get_mut(&id) {
             if c.mapped {
                 c.mapped = false;
-                self.conn.unmap_window(id);
+                self.x_request(self.conn.unmap_window(id));
+                self.x_request(self.conn.set_client_state(id, WindowState::Iconic));
+                *self.pending_unmaps.entry(id).or_insert(0) += 1;
+                self.transients_of(id)
+                    .iter()
+                    .for_each(|t| self.unmap_window_if_needed(*t));
+            }
+        }
+    }
+
+    // Resize r to satisfy the size hints of the client, keeping the same top left corner
+    fn apply_size_hints(&self, id: WinId, r: Region) -> Region {
+        match self.client_map.get(&id) {
+            Some(c) => {
+                let (x, y, w, h) = r.values();
+                let (w, h) = c.size_hints.apply(w, h);
+                Region::new(x, y, w, h)
             }
+            None => r,
+        }
+    }
+
+    // Shrink the client to satisfy its size hints and then center it inside of r
+    fn fit_size_hints_within(&self, id: WinId, r: Region) -> Region {
+        let (x, y, w, h) = r.values();
+        let (_, _, hw, hh) = self.apply_size_hints(id, r).values();
+        let (hw, hh) = (cmp::min(w, hw), cmp::min(h, hh));
+        Region::new(x + (w - hw) / 2, y + (h - hh) / 2, hw, hh)
+    }
+
+    fn raise_client(&mut self, id: WinId) {
+        self.x_request(self.conn.raise_window(id));
+        self.stacking_order.retain(|&s| s != id);
+        self.stacking_order.push(id);
+    }
+
+    fn update_client_list(&mut self) {
+        self.x_request(
+            self.conn
+                .update_client_list(&self.client_list, &self.stacking_order),
+        );
+    }
+
+    fn transients_of(&self, id: WinId) -> Vec<WinId> {
+        self.client_map
+            .values()
+            .filter(|c| c.transient_for == Some(id))
+            .map(|c| c.id())
+            .collect()
+    }
+
+    // Only windows that we are already managing are treated as transient parents
+    fn transient_parent(&self, id: WinId) -> Option<WinId> {
+        match self.conn.atom_prop(id, "WM_TRANSIENT_FOR") {
+            Ok(parent) if parent != id && self.client_map.contains_key(&parent) => Some(parent),
+            _ => None,
+        }
+    }
+
+    fn center_over_parent(&mut self, id: WinId, parent: WinId) {
+        let (px, py, pw, ph) = match self.conn.window_geometry(parent) {
+            Ok(r) => r.values(),
+            Err(_) => return,
+        };
+        if let Ok(r) = self.conn.window_geometry(id) {
+            let (_, _, w, h) = r.values();
+            let x = px + pw.saturating_sub(w) / 2;
+            let y = py + ph.saturating_sub(h) / 2;
+            let r = Region::new(x, y, w, h);
+            self.x_request(self.conn.position_window(id, r, self.border_px, true));
         }
     }
 
@@ -151,16 +315,63 @@ impl<'a> WindowManager<'a> {
                 if let Some(c) = self.client_map.remove(&id) {
                     debug!("removing ref to client {} ({})", c.id(), c.class());
                 }
+                self.client_map
+                    .values_mut()
+                    .filter(|c| c.transient_for == Some(id))
+                    .for_each(|c| c.transient_for = None);
 
                 if self.focused_client == Some(id) {
                     self.focused_client = None;
                 }
+                self.pending_unmaps.remove(&id);
+                self.client_list.retain(|&c| c != id);
+                self.stacking_order.retain(|&c| c != id);
+                self.update_client_list();
+                self.urgent_clients.retain(|&u| u != id);
                 run_hooks!(remove_client, self, id);
             }
             None => warn!("attempt to remove unknown client {}", id),
         }
     }
 
+    // Struts are relative to the root window, which covers every screen
+    fn reserve_screen_space(&self, mut screens: Vec<Screen>) -> Vec<Screen> {
+        let root = screens.iter().fold((0, 0), |(rw, rh), s| {
+            let (x, y, w, h) = s.region(false).values();
+            (cmp::max(rw, x + w), cmp::max(rh, y + h))
+        });
+        let bar_height = if self.show_bar { self.bar_height } else { 0 };
+        let struts: Vec<Strut> = self.docks.values().cloned().collect();
+
+        // Space for the bar is only reserved on screens without a dock so that an external bar
+        // with a strut is not counted twice
+        screens.iter_mut().for_each(|s| {
+            s.update_effective_region(0, self.top_bar);
+            s.reserve_struts(&struts, root);
+            if s.region(true) == s.region(false) {
+                s.update_effective_region(bar_height, self.top_bar);
+            }
+        });
+
+        screens
+    }
+
+    fn update_dock_space(&mut self) {
+        let screens = self.reserve_screen_space(self.screens.as_vec());
+        if screens == self.screens.as_vec() {
+            return;
+        }
+
+        screens
+            .into_iter()
+            .enumerate()
+            .for_each(|(i, s)| self.screens[i] = s);
+        let visible_workspaces: Vec<_> = self.screens.iter().map(|s| s.wix).collect();
+        visible_workspaces
+            .iter()
+            .for_each(|wix| self.apply_layout(*wix));
+    }
+
     fn update_x_workspace_details(&mut self) {
         let string_names: Vec<String> = self
             .workspaces
@@ -169,7 +380,7 @@ impl<'a> WindowManager<'a> {
             .collect();
         let names: Vec<&str> = string_names.iter().map(|s| s.as_ref()).collect();
 
-        self.conn.update_desktops(&names);
+        self.x_request(self.conn.update_desktops(&names));
         run_hooks!(workspaces_updated, self, &names, self.active_ws_index());
     }
 
@@ -231,8 +442,9 @@ impl<'a> WindowManager<'a> {
             self.client_lost_focus(id)
         }
 
-        self.conn.set_client_border_color(id, self.focused_border);
-        self.conn.focus_client(id);
+        self.set_urgent(id, false);
+        self.x_request(self.conn.set_client_border_color(id, self.focused_border));
+        self.x_request(self.conn.focus_client(id));
 
         if let Some(wix) = self.workspace_index_for_client(id) {
             if let Some(ws) = self.workspaces.get_mut(wix) {
@@ -248,56 +460,140 @@ impl<'a> WindowManager<'a> {
         run_hooks!(focus_change, self, id);
     }
 
-    fn client_lost_focus(&self, id: WinId) {
+    fn client_lost_focus(&mut self, id: WinId) {
         let color = self.unfocused_border;
-        self.conn.set_client_border_color(id, color);
+        self.x_request(self.conn.set_client_border_color(id, color));
+    }
+
+    // Urgent clients are tracked in the order that they became urgent. The focused client is
+    // never marked as urgent as it already has the user's attention.
+    fn set_urgent(&mut self, id: WinId, urgent: bool) {
+        let focused = self.focused_client == Some(id);
+        let c = match self.client_map.get_mut(&id) {
+            Some(c) if c.urgent != urgent && !(urgent && focused) => c,
+            _ => return,
+        };
+
+        c.urgent = urgent;
+        if urgent {
+            self.urgent_clients.push(id);
+            self.x_request(self.conn.set_client_border_color(id, self.urgent_border));
+            run_hooks!(client_urgent, self, id);
+        } else {
+            self.urgent_clients.retain(|&u| u != id);
+            if !focused {
+                self.x_request(self.conn.set_client_border_color(id, self.unfocused_border));
+            }
+        }
     }
 
     /**
      * main event loop for the window manager.
      * Everything is driven by incoming events from the X server with each event type being
      * mapped to a handler
+     *
+     * 'bindings' can either be a single set of KeyBindings or a set of named [BindingModes]
+     * that can be switched between using [enter_mode][WindowManager::enter_mode]. They are
+     * added to any bindings that have already been made using
+     * [bind_key][WindowManager::bind_key], replacing existing bindings for the same keys.
      */
     pub fn grab_keys_and_run(
         &mut self,
-        mut bindings: KeyBindings,
-        mut mouse_bindings: MouseBindings,
+        bindings: impl Into<BindingModes>,
+        mouse_bindings: MouseBindings,
     ) {
         // ignore SIGCHILD and allow child / inherited processes to be inherited by pid1
         unsafe { signal(Signal::SIGCHLD, SigHandler::SigIgn) }.unwrap();
 
-        self.conn.grab_keys(&bindings, &mouse_bindings);
+        self.key_bindings.extend(bindings.into());
+        self.mouse_bindings.extend(mouse_bindings);
+        self.grab_bindings();
         self.focus_workspace(&Selector::Index(0));
+        let existing = self
+            .x_request(self.conn.query_for_active_windows())
+            .unwrap_or_default();
+        for &id in existing.iter() {
+            self.adopt_existing_window(id);
+        }
+        self.focus_adopted_client(&existing);
         run_hooks!(startup, self,);
         self.running = true;
 
         while self.running {
-            if let Some(event) = self.conn.wait_for_event() {
+            let poll_interval = self.hook_poll_interval();
+            if let Some(event) = self.next_event(poll_interval) {
                 debug!("got XEvent: {:?}", event);
                 match event {
-                    XEvent::MouseEvent(e) => self.handle_mouse_event(e, &mut mouse_bindings),
-                    XEvent::KeyPress(code) => self.handle_key_press(code, &mut bindings),
+                    XEvent::MouseEvent(e) => self.handle_mouse_event(e),
+                    XEvent::KeyPress(code) => self.handle_key_press(code),
                     XEvent::MapRequest { id, ignore } => self.handle_map_request(id, ignore),
                     XEvent::Enter { id, rpt, wpt } => self.handle_enter_notify(id, rpt, wpt),
                     XEvent::Leave { id, rpt, wpt } => self.handle_leave_notify(id, rpt, wpt),
                     XEvent::Destroy { id } => self.handle_destroy_notify(id),
+                    XEvent::UnmapNotify { id } => self.handle_unmap_notify(id),
                     XEvent::ScreenChange => self.handle_screen_change(),
                     XEvent::RandrNotify => self.detect_screens(),
                     XEvent::ConfigureNotify { id, r, is_root } => {
                         self.handle_configure_notify(id, r, is_root)
                     }
-                    XEvent::PropertyNotify { id, atom, is_root } => {
-                        self.handle_property_notify(id, &atom, is_root)
+                    XEvent::ConfigureRequest { id, r, border } => {
+                        self.handle_configure_request(id, r, border)
                     }
-                    XEvent::ClientMessage { id, dtype, data } => {
-                        self.handle_client_message(id, &dtype, &data)
+                    XEvent::PropertyNotify { id, atom, is_root } => {
+                        match self.conn.atom_name(atom) {
+                            Ok(name) => self.handle_property_notify(id, &name, is_root),
+                            Err(e) => warn!("unable to resolve atom {}: {}", atom, e),
+                        }
                     }
+                    XEvent::ClientMessage { id, dtype, data } => match self.conn.atom_name(dtype) {
+                        Ok(name) => self.handle_client_message(id, &name, &data),
+                        Err(e) => warn!("unable to resolve atom {}: {}", dtype, e),
+                    },
+                    XEvent::MappingNotify => self.handle_mapping_notify(),
+                    XEvent::Error(err) => self.handle_x_error(err),
                 }
                 run_hooks!(event_handled, self,);
+            } else if poll_interval.is_some() {
+                run_hooks!(event_handled, self,);
+            }
+
+            self.x_request(self.conn.flush());
+        }
+    }
+
+    fn hook_poll_interval(&self) -> Option<Duration> {
+        let hooks = self.hooks.take();
+        let interval = hooks.iter().filter_map(|h| h.poll_interval()).min();
+        self.hooks.set(hooks);
+        interval
+    }
+
+    // While a key chord is in progress we only wait until its deadline for the next key, and
+    // hooks that poll for their own events are never made to wait longer than they asked for
+    fn next_event(&mut self, poll_interval: Option<Duration>) -> Option<XEvent> {
+        let deadline = match self.key_chord.as_ref().and_then(|c| c.deadline) {
+            Some(deadline) => deadline,
+            None => {
+                return match poll_interval {
+                    Some(interval) => self.conn.wait_for_event_timeout(interval),
+                    None => self.conn.wait_for_event(),
+                }
             }
+        };
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let timeout = poll_interval.map_or(remaining, |i| cmp::min(i, remaining));
+        let event = if timeout > Duration::from_secs(0) {
+            self.conn.wait_for_event_timeout(timeout)
+        } else {
+            None
+        };
 
-            self.conn.flush();
+        if event.is_none() && Instant::now() >= deadline {
+            debug!("key chord timed out");
+            self.end_key_chord();
         }
+        event
     }
 
     /*
@@ -307,22 +603,233 @@ impl<'a> WindowManager<'a> {
      * received from the X event loop (i.e. to avoid emitting and picking up the event
      * ourselves)
      */
-    fn handle_key_press(&mut self, key_code: KeyCode, bindings: &mut KeyBindings) {
+    fn handle_key_press(&mut self, key_code: KeyCode) {
         debug!("handling key code: {:?}", key_code);
-        if let Some(action) = bindings.get_mut(&key_code) {
-            action(self); // ignoring Child handlers and SIGCHILD
+        // The client that was passed a key press also needs to see the key being released
+        if key_code.release {
+            if let Some(id) = self.passed_through.remove(&key_code.code) {
+                self.x_request(self.conn.send_key(id, key_code));
+                return;
+            }
+        }
+
+        let mut keys = match &self.key_chord {
+            // Releasing the keys that make up a chord does not affect it
+            Some(_) if key_code.release => return,
+            Some(chord) => {
+                // The keyboard is grabbed so we also see modifiers being pressed for the next key
+                match self.primary_keysym(key_code.code) {
+                    Some(sym) if keysyms::is_modifier(sym) => return,
+                    Some(keysyms::ESCAPE) => return self.end_key_chord(),
+                    _ => chord.keys.clone(),
+                }
+            }
+            None => vec![],
+        };
+        keys.push(key_code);
+
+        // Per client bindings pick their action based on the focused client, passing the key
+        // through to it if there isn't one
+        let focused = self
+            .focused_client()
+            .map(|c| (c.id(), c.wm_class().to_string()));
+        let class = focused.as_ref().map(|(_, class)| class.as_str());
+
+        // Actions are able to bind and unbind keys so the action to run is taken as a shared
+        // handle rather than being run while the bindings are borrowed
+        let action = match self
+            .key_bindings
+            .get_mut(&self.mode)
+            .and_then(|bindings| find_binding(bindings, &keys))
+        {
+            Some(KeyBinding::Chord(_)) => return self.continue_key_chord(keys),
+            Some(KeyBinding::Action(action)) => Some(Rc::clone(action)),
+            Some(KeyBinding::PerClient(bindings)) => bindings.action_for(class),
+            None => return self.end_key_chord(),
+        };
+
+        self.end_key_chord();
+        match (action, focused) {
+            // ignoring Child handlers and SIGCHILD
+            (Some(action), _) => (*action.borrow_mut())(self),
+            (None, Some((id, _))) => {
+                debug!("passing {:?} through to {}", key_code, id);
+                if self.x_request(self.conn.send_key(id, key_code)).is_some() {
+                    self.passed_through.insert(key_code.code, id);
+                }
+            }
+            (None, None) => (),
+        }
+    }
+
+    fn primary_keysym(&self, code: u8) -> Option<u32> {
+        self.keyboard_mapping
+            .get(&code)
+            .and_then(|syms| syms.first().copied())
+    }
+
+    fn continue_key_chord(&mut self, keys: Vec<KeyCode>) {
+        if self.key_chord.is_none() && self.x_request(self.conn.grab_keyboard()).is_none() {
+            return;
+        }
+
+        let pending = self.key_sequence_name(&keys);
+        debug!("waiting for the next key after '{}'", pending);
+
+        self.key_chord = Some(KeyChord {
+            keys,
+            deadline: self.key_chord_timeout.map(|t| Instant::now() + t),
+        });
+        run_hooks!(key_chord_pending, self, Some(&pending));
+    }
+
+    fn key_sequence_name(&self, keys: &[KeyCode]) -> String {
+        keys.iter()
+            .map(|&k| keysyms::key_name(k, &self.keyboard_mapping))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    fn end_key_chord(&mut self) {
+        if self.key_chord.take().is_some() {
+            self.x_request(self.conn.ungrab_keyboard());
+            run_hooks!(key_chord_pending, self, None);
         }
     }
 
-    fn handle_mouse_event(&mut self, e: MouseEvent, bindings: &mut MouseBindings) {
+    fn handle_mouse_event(&mut self, e: MouseEvent) {
         debug!("handling mouse event: {:?} {:?}", e.state, e.kind);
-        if let Some(action) = bindings.get_mut(&(e.kind, e.state.clone())) {
+        if let Some(drag) = self.mouse_drag {
+            match e.kind {
+                MouseEventKind::Motion => self.update_mouse_drag(drag, e.rpt),
+                MouseEventKind::Release => self.end_mouse_drag(),
+                MouseEventKind::Press => (),
+            }
+            return;
+        }
+
+        // Mouse bindings can not be changed at runtime so the handler is always put back
+        let key = (e.kind, e.state.clone());
+        if let Some(handler) = self.mouse_bindings.get_mut(&key) {
+            let mut action = mem::replace(handler, Box::new(|_, _| {}));
             action(self, &e); // ignoring Child handlers and SIGCHILD
+            self.mouse_bindings.insert(key, action);
+        }
+    }
+
+    fn start_mouse_drag(&mut self, e: &MouseEvent, resize: bool) {
+        if e.kind != MouseEventKind::Press || self.mouse_drag.is_some() {
+            return;
+        }
+
+        let (id, floating) = match self.client_map.get(&e.id) {
+            Some(c) if c.wm_managed && !c.fullscreen => (c.id(), c.floating),
+            _ => return,
+        };
+        let region = match self.conn.window_geometry(id) {
+            Ok(r) => r,
+            Err(_) => return,
+        };
+
+        if !floating {
+            self.toggle_client_floating(&Selector::WinId(id));
         }
+        self.raise_client(id);
+        self.update_client_list();
+        self.client_gained_focus(id);
+        self.x_request(self.conn.grab_pointer());
+
+        self.mouse_drag = Some(MouseDrag {
+            id,
+            resize,
+            start: e.rpt,
+            region,
+        });
+    }
+
+    fn update_mouse_drag(&mut self, drag: MouseDrag, pt: Point) {
+        let dx = pt.x as i32 - drag.start.x as i32;
+        let dy = pt.y as i32 - drag.start.y as i32;
+        let (x, y, w, h) = drag.region.values();
+        let shift = |val: u32, delta: i32, min: i32| cmp::max(val as i32 + delta, min) as u32;
+
+        let r = if drag.resize {
+            self.apply_size_hints(drag.id, Region::new(x, y, shift(w, dx, 1), shift(h, dy, 1)))
+        } else {
+            Region::new(shift(x, dx, 0), shift(y, dy, 0), w, h)
+        };
+        self.x_request(self.conn.position_window(drag.id, r, self.border_px, true));
+    }
+
+    fn end_mouse_drag(&mut self) {
+        self.mouse_drag = None;
+        self.x_request(self.conn.ungrab_pointer());
     }
 
     fn handle_map_request(&mut self, id: WinId, override_redirect: bool) {
-        if override_redirect || self.client_map.contains_key(&id) {
+        if override_redirect {
+            return;
+        }
+        self.manage_window(id, None, true);
+    }
+
+    // Windows that already exist when we start are placed back on the workspace they were
+    // last on if it is known. They are not focused as they are adopted: focus is set once all
+    // of them have been managed by focus_adopted_client.
+    fn adopt_existing_window(&mut self, id: WinId) {
+        let wix = match self.conn.atom_prop(id, "_NET_WM_DESKTOP") {
+            Ok(wix) if (wix as usize) < self.workspaces.len() => Some(wix as usize),
+            _ => None,
+        };
+        self.manage_window(id, wix, false);
+
+        // The window is already mapped so hide it if its workspace is not visible
+        let hide = match self.client_map.get_mut(&id) {
+            Some(c) if !c.mapped => {
+                c.mapped = true;
+                true
+            }
+            _ => false,
+        };
+        if hide {
+            self.unmap_window_if_needed(id);
+        }
+    }
+
+    // Restore focus to the window that was active before we started if it is visible, falling
+    // back to the first visible client that was adopted.
+    fn focus_adopted_client(&mut self, adopted: &[WinId]) {
+        let active = self.conn.active_window().ok();
+        let visible = |id: &WinId| match self.client_map.get(id) {
+            Some(c) => self.screens.iter().any(|s| s.wix == c.workspace()),
+            None => false,
+        };
+
+        if let Some(id) = active.iter().chain(adopted).copied().find(visible) {
+            self.client_gained_focus(id);
+            let s = self.screens.focused().unwrap();
+            self.x_request(self.conn.warp_cursor(Some(id), s));
+        }
+    }
+
+    fn manage_window(&mut self, id: WinId, target_wix: Option<usize>, focus: bool) {
+        if self.client_map.contains_key(&id) || self.docks.contains_key(&id) {
+            return;
+        }
+
+        // Docks are never managed: they are mapped as requested and the space they reserve is
+        // removed from the screen regions available to layouts. If we are unable to query the
+        // window at all then it has most likely already been destroyed.
+        let is_dock = match self.x_request(self.conn.window_is_dock(id)) {
+            Some(is_dock) => is_dock,
+            None => return,
+        };
+        if is_dock {
+            let strut = self.conn.window_strut(id).unwrap_or_default();
+            self.docks.insert(id, strut);
+            self.x_request(self.conn.mark_new_window(id));
+            self.x_request(self.conn.map_window(id));
+            self.update_dock_space();
             return;
         }
 
@@ -336,26 +843,55 @@ impl<'a> WindowManager<'a> {
             Err(_) => String::from("n/a"),
         };
 
-        let floating = self.conn.window_should_float(id, self.floating_classes);
-        let wix = self.active_ws_index();
+        // Transient windows always float and open on the same workspace as their parent, as do
+        // windows that are unable to resize.
+        let transient_for = self.transient_parent(id);
+        let size_hints = self.conn.size_hints(id).unwrap_or_default();
+        let floating = transient_for.is_some()
+            || size_hints.is_fixed()
+            || self
+                .x_request(self.conn.window_should_float(id, self.floating_classes))
+                .unwrap_or(false);
+        let wix = match transient_for.and_then(|p| self.client_map.get(&p)) {
+            Some(parent) => parent.workspace(),
+            None => target_wix.unwrap_or_else(|| self.active_ws_index()),
+        };
         let mut client = Client::new(id, wm_name, wm_class, wix, floating);
+        client.transient_for = transient_for;
+        client.size_hints = size_hints;
         run_hooks!(new_client, self, &mut client);
 
-        if client.wm_managed && !floating {
+        if client.wm_managed {
             self.add_client_to_workspace(wix, id);
         }
 
         self.client_map.insert(id, client);
-        self.conn.mark_new_window(id);
-        self.conn.focus_client(id);
-        self.client_gained_focus(id);
+        self.client_list.push(id);
+        self.stacking_order.push(id);
+        self.update_client_list();
+        self.x_request(self.conn.mark_new_window(id));
+        self.x_request(self.conn.set_client_workspace(id, wix));
+
+        if let Some(parent) = transient_for {
+            self.center_over_parent(id, parent);
+        }
+
+        // The parent of a transient window may be on a workspace that is not currently visible
+        if !self.screens.iter().any(|s| s.wix == wix) {
+            return;
+        }
 
-        self.conn.set_client_workspace(id, wix);
+        if focus {
+            self.x_request(self.conn.focus_client(id));
+            self.client_gained_focus(id);
+        }
         self.apply_layout(wix);
         self.map_window_if_needed(id);
 
-        let s = self.screens.focused().unwrap();
-        self.conn.warp_cursor(Some(id), s);
+        if focus {
+            let s = self.screens.focused().unwrap();
+            self.x_request(self.conn.warp_cursor(Some(id), s));
+        }
     }
 
     fn add_client_to_workspace(&mut self, wix: usize, id: WinId) {
@@ -366,6 +902,10 @@ impl<'a> WindowManager<'a> {
     }
 
     fn handle_enter_notify(&mut self, id: WinId, rpt: Point, _wpt: Point) {
+        if self.docks.contains_key(&id) {
+            return;
+        }
+
         if let Some(current) = self.focused_client() {
             if current.id() != id {
                 self.client_lost_focus(current.id());
@@ -377,6 +917,10 @@ impl<'a> WindowManager<'a> {
     }
 
     fn handle_leave_notify(&mut self, id: WinId, rpt: Point, _wpt: Point) {
+        if self.docks.contains_key(&id) {
+            return;
+        }
+
         self.client_lost_focus(id);
         self.set_screen_from_cursor(rpt);
     }
@@ -387,18 +931,110 @@ impl<'a> WindowManager<'a> {
         }
     }
 
+    // Floating and unmanaged clients are free to position themselves however they like but tiled
+    // clients are told where the layout has placed them instead. Unmanaged windows also keep the
+    // border width that they asked for.
+    fn handle_configure_request(&mut self, id: WinId, r: Region, border: u32) {
+        let honour_request = match self.client_map.get(&id) {
+            None => true,
+            Some(c) => {
+                let ws_floating = match self.workspaces.get(c.workspace()) {
+                    Some(ws) => ws.layout_conf().floating,
+                    None => true,
+                };
+                !c.fullscreen && (!c.wm_managed || c.floating || ws_floating)
+            }
+        };
+
+        if honour_request {
+            let border = if self.client_map.contains_key(&id) {
+                self.border_px
+            } else {
+                border
+            };
+            let r = self.apply_size_hints(id, r);
+            self.x_request(self.conn.position_window(id, r, border, false));
+            return;
+        }
+
+        let layout_region = match self.client_map.get(&id) {
+            Some(c) if !c.fullscreen => c.layout_region,
+            _ => None,
+        };
+        let region = match layout_region {
+            Some(region) => Some(region),
+            None => self.conn.window_geometry(id).ok(),
+        };
+        if let Some(region) = region {
+            self.x_request(self.conn.send_configure_notify(id, region, self.border_px));
+        }
+    }
+
     fn handle_screen_change(&mut self) {
-        self.set_screen_from_cursor(self.conn.cursor_position());
+        if let Some(p) = self.x_request(self.conn.cursor_position()) {
+            self.set_screen_from_cursor(p);
+        }
         let wix = self.screens.focused().unwrap().wix;
         self.workspaces.focus(&Selector::Index(wix));
     }
 
+    fn handle_x_error(&mut self, err: XError) {
+        warn!("{}", err);
+        run_hooks!(x_error, self, &err);
+    }
+
     fn handle_destroy_notify(&mut self, win_id: WinId) {
         debug!("DESTROY_NOTIFY for {}", win_id);
+        if self.docks.remove(&win_id).is_some() {
+            self.update_dock_space();
+            return;
+        }
+
         self.remove_client(win_id);
         self.apply_layout(self.active_ws_index());
     }
 
+    // Key bindings were parsed using the keyboard mapping that was in place when they were
+    // created, so each binding is moved to whichever key now produces the same key name.
+    fn handle_mapping_notify(&mut self) {
+        let mapping = match self.x_request(self.conn.keyboard_mapping()) {
+            Some(mapping) => mapping,
+            None => return,
+        };
+        let names = keysyms::primary_names(&self.keyboard_mapping);
+        let codes = keysyms::code_map(&mapping);
+
+        self.end_key_chord();
+        self.key_bindings
+            .iter_mut()
+            .for_each(|bindings| remap_key_bindings(bindings, &names, &codes));
+        self.keyboard_mapping = mapping;
+        self.grab_bindings();
+    }
+
+    // Unmaps that we did not trigger ourselves mean that the client has withdrawn its window
+    fn handle_unmap_notify(&mut self, id: WinId) {
+        if self.docks.remove(&id).is_some() {
+            self.update_dock_space();
+            return;
+        }
+
+        if let Some(n) = self.pending_unmaps.get_mut(&id) {
+            *n -= 1;
+            if *n == 0 {
+                self.pending_unmaps.remove(&id);
+            }
+            return;
+        }
+
+        if self.client_map.contains_key(&id) {
+            debug!("client {} withdrew its window", id);
+            self.x_request(self.conn.set_client_state(id, WindowState::Withdrawn));
+            self.remove_client(id);
+            self.apply_layout(self.active_ws_index());
+        }
+    }
+
     fn handle_property_notify(&mut self, id: WinId, atom: &str, is_root: bool) {
         if atom == "WM_NAME" || atom == "_NET_WM_NAME" {
             if let Ok(name) = self.conn.str_prop(id, atom) {
@@ -407,27 +1043,106 @@ impl<'a> WindowManager<'a> {
                 }
                 run_hooks!(client_name_updated, self, id, &name, is_root);
             }
+        } else if atom == "WM_HINTS" {
+            if let Some(urgent) = self.x_request(self.conn.window_is_urgent(id)) {
+                self.set_urgent(id, urgent);
+            }
+        } else if atom == "_NET_WM_STRUT" || atom == "_NET_WM_STRUT_PARTIAL" {
+            if self.docks.contains_key(&id) {
+                let strut = self.conn.window_strut(id).unwrap_or_default();
+                self.docks.insert(id, strut);
+                self.update_dock_space();
+            }
+        } else if atom == "WM_NORMAL_HINTS" {
+            if let Ok(hints) = self.conn.size_hints(id) {
+                if let Some(c) = self.client_map.get_mut(&id) {
+                    c.size_hints = hints;
+                }
+            }
         }
     }
 
     fn handle_client_message(&mut self, id: WinId, dtype: &str, data: &[usize]) {
-        if dtype == "_NET_WM_STATE" {
-            let full_screen = self.conn.intern_atom("_NET_WM_STATE_FULLSCREEN").unwrap() as usize;
-            if data.get(1) == Some(&full_screen) || data.get(2) == Some(&full_screen) {
-                let client_is_fullscreen = match self.client_map.get(&id) {
-                    None => return, // unknown client
-                    Some(c) => c.fullscreen,
-                };
-                // _NET_WM_STATE_ADD == 1, _NET_WM_STATE_TOGGLE == 2
-                let should_fullscreen = [1, 2].contains(&data[0]) && !client_is_fullscreen;
-                self.set_fullscreen(id, should_fullscreen, client_is_fullscreen);
+        match dtype {
+            "_NET_ACTIVE_WINDOW" => self.handle_active_window_request(id, data),
+            "_NET_CURRENT_DESKTOP" => {
+                if let Some(&wix) = data.first() {
+                    self.focus_workspace(&Selector::Index(wix));
+                }
+            }
+            "_NET_WM_DESKTOP" => {
+                // 0xFFFFFFFF requests that the client is shown on all desktops: not supported
+                if let Some(&wix) = data.first() {
+                    if wix < self.workspaces.len() && self.client_map.contains_key(&id) {
+                        self.move_client_to_workspace(id, wix);
+                    }
+                }
             }
+            "_NET_CLOSE_WINDOW" if self.client_map.contains_key(&id) => {
+                self.close_client(id);
+                self.x_request(self.conn.flush());
+            }
+            "_NET_WM_STATE" => self.handle_wm_state_request(id, data),
+            _ => (),
+        }
+    }
+
+    // source indication (data[0]) is 1 for applications and 2 for pagers. Old clients send 0
+    // and are treated as pagers.
+    fn handle_active_window_request(&mut self, id: WinId, data: &[usize]) {
+        if !self.client_map.contains_key(&id) || self.focused_client == Some(id) {
+            return;
+        }
+
+        let from_application = data.first() == Some(&1);
+        let allowed = match self.focus_stealing {
+            FocusStealing::Allow => true,
+            FocusStealing::PagersOnly => !from_application,
+            FocusStealing::Deny => false,
+        };
+
+        if allowed {
+            self.focus_client_on_workspace(id);
+        } else {
+            self.set_urgent(id, true);
+        }
+    }
+
+    fn handle_wm_state_request(&mut self, id: WinId, data: &[usize]) {
+        let full_screen = self.conn.intern_atom(Atom::NetWmStateFullscreen.as_ref());
+        let full_screen = self.x_request(full_screen);
+        let attention = self
+            .conn
+            .intern_atom(Atom::NetWmStateDemandsAttention.as_ref());
+        let attention = self.x_request(attention);
+        let (full_screen, attention) = match (full_screen, attention) {
+            (Some(f), Some(a)) => (f as usize, a as usize),
+            _ => return,
+        };
+
+        if data.get(1) == Some(&full_screen) || data.get(2) == Some(&full_screen) {
+            let client_is_fullscreen = match self.client_map.get(&id) {
+                None => return, // unknown client
+                Some(c) => c.fullscreen,
+            };
+            // _NET_WM_STATE_ADD == 1, _NET_WM_STATE_TOGGLE == 2
+            let should_fullscreen = [1, 2].contains(&data[0]) && !client_is_fullscreen;
+            self.set_fullscreen(id, should_fullscreen, client_is_fullscreen);
+        }
+
+        if data.get(1) == Some(&attention) || data.get(2) == Some(&attention) {
+            let urgent = match (data[0], self.client_map.get(&id)) {
+                (1, _) => true,
+                (2, Some(c)) => !c.urgent,
+                _ => false,
+            };
+            self.set_urgent(id, urgent);
         }
     }
 
     fn set_fullscreen(&mut self, id: WinId, should_fullscreen: bool, client_is_fullscreen: bool) {
         if should_fullscreen && !client_is_fullscreen {
-            self.conn.toggle_client_fullscreen(id, client_is_fullscreen);
+            self.x_request(self.conn.toggle_client_fullscreen(id, client_is_fullscreen));
             if let Some(ws) = self.workspaces.get(self.active_ws_index()) {
                 ws.clients().iter().for_each(|&i| {
                     if i != id {
@@ -436,13 +1151,13 @@ impl<'a> WindowManager<'a> {
                 });
             }
             let r = self.screen(&Selector::Focused).unwrap().region(false);
-            self.conn.position_window(id, r, 0, false);
+            self.x_request(self.conn.position_window(id, r, 0, false));
             self.map_window_if_needed(id);
             if let Some(c) = self.client_map.get_mut(&id) {
                 c.fullscreen = true
             };
         } else if !should_fullscreen && client_is_fullscreen {
-            self.conn.toggle_client_fullscreen(id, client_is_fullscreen);
+            self.x_request(self.conn.toggle_client_fullscreen(id, client_is_fullscreen));
             if let Some(ws) = self.workspaces.get(self.active_ws_index()) {
                 ws.clients().iter().for_each(|&i| {
                     if i != id {
@@ -466,17 +1181,19 @@ impl<'a> WindowManager<'a> {
 
     /// Reset the current known screens based on currently detected outputs
     pub fn detect_screens(&mut self) {
-        let screens: Vec<Screen> = self
-            .conn
-            .current_outputs()
+        let outputs = match self.x_request(self.conn.current_outputs()) {
+            Some(outputs) => outputs,
+            None => return,
+        };
+        let screens: Vec<Screen> = outputs
             .into_iter()
             .enumerate()
             .map(|(i, mut s)| {
-                s.update_effective_region(self.bar_height, self.top_bar);
                 s.wix = i;
                 s
             })
             .collect();
+        let screens = self.reserve_screen_space(screens);
 
         info!("updating known screens: {} screens detected", screens.len());
         for (i, s) in screens.iter().enumerate() {
@@ -508,9 +1225,9 @@ impl<'a> WindowManager<'a> {
             self.screens.cycle_focus(direction);
             let i = self.screens.focused().unwrap().wix;
             self.workspaces.focus(&Selector::Index(i));
-            self.conn.warp_cursor(None, self.screens.focused().unwrap());
+            self.x_request(self.conn.warp_cursor(None, self.screens.focused().unwrap()));
             let wix = self.workspaces.focused_index();
-            self.conn.set_current_workspace(wix);
+            self.x_request(self.conn.set_current_workspace(wix));
 
             let i = self.screens.focused_index();
             run_hooks!(screen_change, self, i);
@@ -545,7 +1262,7 @@ impl<'a> WindowManager<'a> {
             self.client_lost_focus(prev);
             self.client_gained_focus(new);
             let screen = self.screens.focused().unwrap();
-            self.conn.warp_cursor(Some(new), screen);
+            self.x_request(self.conn.warp_cursor(Some(new), screen));
         }
     }
 
@@ -566,8 +1283,8 @@ impl<'a> WindowManager<'a> {
                 .and_then(|ws| ws.drag_client(direction));
             self.apply_layout(wix);
             self.client_gained_focus(id);
-            self.conn
-                .warp_cursor(Some(id), self.screens.focused().unwrap());
+            let s = self.screens.focused().unwrap();
+            self.x_request(self.conn.warp_cursor(Some(id), s));
         }
     }
 
@@ -602,35 +1319,149 @@ impl<'a> WindowManager<'a> {
 
     /// Shut down the WindowManager, running any required cleanup and exiting penrose
     pub fn exit(&mut self) {
-        self.conn.cleanup();
-        self.conn.flush();
+        self.x_request(self.conn.cleanup());
+        self.x_request(self.conn.flush());
         self.running = false;
     }
 
-    /// The layout symbol for the Layout currently being used on the active workspace
-    pub fn current_layout_symbol(&self) -> &str {
-        self.layout_symbol(self.active_ws_index())
-    }
-
-    fn layout_symbol(&self, wix: usize) -> &str {
-        match self.workspaces.get(wix) {
-            Some(ws) => ws.layout_symbol(),
-            None => "???",
+    /**
+     * Switch to the named set of key bindings from the [BindingModes] passed to
+     * [grab_keys_and_run][WindowManager::grab_keys_and_run].
+     *
+     * Unknown mode names are logged and ignored.
+     */
+    pub fn enter_mode(&mut self, name: &str) {
+        if name == self.mode {
+            return;
+        }
+        if !self.key_bindings.contains(name) {
+            warn!("unknown key binding mode: {}", name);
+            return;
         }
+
+        self.end_key_chord();
+        self.mode = name.into();
+        self.grab_bindings();
+        run_hooks!(mode_changed, self, name);
     }
 
-    /// Set the root X window name. Useful for exposing information to external programs
-    pub fn set_root_window_name(&self, s: &str) {
-        self.conn.set_root_window_name(s);
+    /// Switch back to the default key bindings
+    pub fn exit_mode(&mut self) {
+        self.enter_mode(DEFAULT_MODE);
     }
 
-    /// Set the insert point for new clients. Default is to insert at index 0.
-    pub fn set_client_insert_point(&mut self, cip: InsertPoint) {
-        self.client_insert_point = cip;
+    /// The name of the active key binding mode
+    pub fn current_mode(&self) -> &str {
+        &self.mode
     }
 
     /**
-     * Set the displayed workspace for the focused screen to be `index` in the list of
+     * Bind an action to a key binding pattern in the active mode, replacing any existing binding
+     * for the same keys. Patterns are parsed in the same way as they are for
+     * [gen_keybindings][crate::gen_keybindings] so "M-a t" binds a key chord and "@M-a" binds
+     * the release of M-a.
+     *
+     * The new binding is grabbed immediately.
+     */
+    pub fn bind_key(&mut self, pattern: &str, action: FireAndForget) -> Result<()> {
+        self.bind_key_pattern(pattern, action.into())
+    }
+
+    /**
+     * Bind a key binding pattern in the active mode to actions that depend on the focused client,
+     * as [bind_key][WindowManager::bind_key] does for a single action. Keys that are passed
+     * through are sent as synthetic events: see [ClientBindings] for the limitations of this.
+     */
+    pub fn bind_client_key(&mut self, pattern: &str, bindings: ClientBindings) -> Result<()> {
+        self.bind_key_pattern(pattern, bindings.into())
+    }
+
+    /**
+     * Remove the binding for a key binding pattern from the active mode. Unbinding the prefix
+     * of a key chord removes every binding that starts with that prefix.
+     *
+     * The keys are ungrabbed immediately.
+     */
+    pub fn unbind_key(&mut self, pattern: &str) -> Result<()> {
+        let keys = self.parse_key_pattern(pattern)?;
+        let bindings = self.active_bindings()?;
+        if unbind_key_sequence(bindings, &keys).is_none() {
+            return Err(anyhow!("'{}' is not bound", pattern));
+        }
+        self.grab_bindings();
+        Ok(())
+    }
+
+    /// The patterns of all key bindings in the active mode, in sorted order
+    pub fn list_bindings(&self) -> Vec<String> {
+        let mut sequences = vec![];
+        if let Some(bindings) = self.key_bindings.get(&self.mode) {
+            collect_key_sequences(bindings, &mut vec![], &mut sequences);
+        }
+
+        let mut patterns: Vec<String> = sequences
+            .iter()
+            .map(|keys| self.key_sequence_name(keys))
+            .collect();
+        patterns.sort();
+        patterns
+    }
+
+    fn bind_key_pattern(&mut self, pattern: &str, binding: KeyBinding) -> Result<()> {
+        let keys = self.parse_key_pattern(pattern)?;
+        let bindings = self.active_bindings()?;
+        bind_key_sequence(bindings, &keys, binding)?;
+        self.grab_bindings();
+        Ok(())
+    }
+
+    fn parse_key_pattern(&self, pattern: &str) -> Result<Vec<KeyCode>> {
+        let codes = keysyms::code_map(&self.keyboard_mapping);
+        parse_key_sequence(pattern, &codes)
+            .ok_or_else(|| anyhow!("invalid key binding: {}", pattern))
+    }
+
+    fn active_bindings(&mut self) -> Result<&mut KeyBindings> {
+        let mode = &self.mode;
+        self.key_bindings
+            .get_mut(mode)
+            .ok_or_else(|| anyhow!("unknown key binding mode: {}", mode))
+    }
+
+    // (Re)grab the key bindings for the active mode along with the mouse bindings
+    fn grab_bindings(&mut self) {
+        if let Some(bindings) = self.key_bindings.get(&self.mode) {
+            let res = self.conn.grab_keys(bindings, &self.mouse_bindings);
+            self.x_request(res);
+        }
+    }
+
+    /// The layout symbol for the Layout currently being used on the active workspace
+    pub fn current_layout_symbol(&self) -> &str {
+        self.layout_symbol(self.active_ws_index())
+    }
+
+    fn layout_symbol(&self, wix: usize) -> &str {
+        match self.workspaces.get(wix) {
+            Some(ws) => ws.layout_symbol(),
+            None => "???",
+        }
+    }
+
+    /// Set the root X window name. Useful for exposing information to external programs
+    pub fn set_root_window_name(&self, s: &str) {
+        if let Err(e) = self.conn.set_root_window_name(s) {
+            error!("unable to set root window name: {}", e);
+        }
+    }
+
+    /// Set the insert point for new clients. Default is to insert at index 0.
+    pub fn set_client_insert_point(&mut self, cip: InsertPoint) {
+        self.client_insert_point = cip;
+    }
+
+    /**
+     * Set the displayed workspace for the focused screen to be `index` in the list of
      * workspaces passed at `init`. This will panic if the index passed is out of
      * bounds which is only possible if you manually bind an action to this with an
      * invalid index. You should almost always be using the `gen_keybindings!` macro
@@ -654,9 +1485,14 @@ impl<'a> WindowManager<'a> {
                     self.screens[i].wix = self.screens.focused().unwrap().wix;
                     self.screens.focused_mut().unwrap().wix = index;
 
-                    // re-apply layouts as screen dimensions may differ
-                    self.apply_layout(active);
-                    self.apply_layout(index);
+                    // re-apply layouts as screen dimensions may differ and move floating
+                    // clients across as the layouts leave them where they are
+                    for &wix in &[active, index] {
+                        self.apply_layout(wix);
+                        for id in self.floating_clients(wix) {
+                            self.move_floating_client_to_screen(id, wix);
+                        }
+                    }
 
                     let ws = self.workspaces.get(index);
                     if let Some(id) = ws.and_then(|ws| ws.focused_client()) {
@@ -685,7 +1521,7 @@ impl<'a> WindowManager<'a> {
 
             self.screens.focused_mut().unwrap().wix = index;
             self.apply_layout(index);
-            self.conn.set_current_workspace(index);
+            self.x_request(self.conn.set_current_workspace(index));
 
             let ws = self.workspaces.get(index);
             if let Some(id) = ws.and_then(|ws| ws.focused_client()) {
@@ -712,31 +1548,93 @@ impl<'a> WindowManager<'a> {
         if let Some(index) = self.workspaces.index(&selector) {
             let res = self
                 .workspaces
-                .get_mut(self.active_ws_index())
-                .and_then(|ws| ws.remove_focused_client());
+                .get(self.active_ws_index())
+                .and_then(|ws| ws.focused_client());
 
             if let Some(id) = res {
-                self.add_client_to_workspace(index, id);
-                if let Some(c) = self.client_map.get_mut(&id) {
-                    c.set_workspace(index)
-                };
-                self.conn.set_client_workspace(id, index);
-                self.apply_layout(self.active_ws_index());
+                self.move_client_to_workspace(id, index);
 
-                // layout & focus the screen we just landed on if the workspace is displayed
-                // otherwise unmap the window because we're no longer visible
+                // focus the screen we just landed on if the workspace is displayed
                 if self.screens.iter().any(|s| s.wix == index) {
-                    self.apply_layout(index);
                     let s = self.screens.focused().unwrap();
-                    self.conn.warp_cursor(Some(id), s);
+                    self.x_request(self.conn.warp_cursor(Some(id), s));
                     self.focus_screen(&Selector::Index(self.active_screen_index()));
-                } else {
-                    self.unmap_window_if_needed(id);
                 }
             };
         }
     }
 
+    // Transient windows follow their parent
+    fn move_client_to_workspace(&mut self, id: WinId, index: usize) {
+        let wix = match self.client_map.get(&id) {
+            Some(c) if c.workspace() != index => c.workspace(),
+            _ => return,
+        };
+
+        let moved: Vec<WinId> = std::iter::once(id).chain(self.transients_of(id)).collect();
+        for &c in moved.iter() {
+            if let Some(ws) = self.workspaces.get_mut(wix) {
+                ws.remove_client(c);
+            }
+            self.add_client_to_workspace(index, c);
+            if let Some(c) = self.client_map.get_mut(&c) {
+                c.set_workspace(index)
+            };
+            self.x_request(self.conn.set_client_workspace(c, index));
+        }
+        self.apply_layout(wix);
+
+        // layout the workspace we just moved to if it is displayed otherwise unmap the window
+        // because we're no longer visible. Floating clients are not positioned by the layout
+        // so they are moved across to the new screen explicitly.
+        if self.screens.iter().any(|s| s.wix == index) {
+            self.apply_layout(index);
+            for c in moved {
+                if matches!(self.client_map.get(&c), Some(client) if client.floating) {
+                    self.move_floating_client_to_screen(c, index);
+                }
+                self.map_window_if_needed(c);
+            }
+        } else {
+            self.unmap_window_if_needed(id);
+        }
+    }
+
+    fn floating_clients(&self, wix: usize) -> Vec<WinId> {
+        match self.workspaces.get(wix) {
+            Some(ws) => ws
+                .iter()
+                .filter(|id| matches!(self.client_map.get(id), Some(c) if c.floating))
+                .cloned()
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    // Floating clients keep their offset from the top left of the screen they were on
+    fn move_floating_client_to_screen(&mut self, id: WinId, wix: usize) {
+        let (tx, ty, tw, th) = match self.indexed_screen_for_workspace(wix) {
+            Some((_, s)) => s.region(true).values(),
+            None => return,
+        };
+        let (x, y, w, h) = match self.conn.window_geometry(id) {
+            Ok(r) => r.values(),
+            Err(_) => return,
+        };
+        let (dx, dy) = match self.screens.iter().find(|s| s.contains(Point::new(x, y))) {
+            Some(s) => {
+                let (sx, sy, _, _) = s.region(true).values();
+                (x.saturating_sub(sx), y.saturating_sub(sy))
+            }
+            None => (0, 0),
+        };
+
+        let x = tx + cmp::min(dx, tw.saturating_sub(w));
+        let y = ty + cmp::min(dy, th.saturating_sub(h));
+        let r = Region::new(x, y, w, h);
+        self.x_request(self.conn.position_window(id, r, self.border_px, true));
+    }
+
     /// Move the focused client to the active workspace on the screen matching 'selector'.
     pub fn client_to_screen(&mut self, selector: &Selector<Screen>) {
         let i = match self.screen(selector) {
@@ -755,14 +1653,135 @@ impl<'a> WindowManager<'a> {
         self.set_fullscreen(id, !client_is_fullscreen, client_is_fullscreen);
     }
 
-    /// Kill the focused client window.
+    /**
+     * Toggle the floating state of the selected client. Clients that become floating keep their
+     * current position and are stacked above the tiled clients on their workspace. Clients that
+     * become tiled rejoin their workspace at the current client insert point.
+     */
+    pub fn toggle_client_floating(&mut self, selector: &Selector<Client>) {
+        let (id, wix, floating) = match self.client(selector) {
+            Some(c) if c.wm_managed && !c.fullscreen => (c.id(), c.workspace(), c.floating),
+            _ => return,
+        };
+
+        if let Some(c) = self.client_map.get_mut(&id) {
+            c.floating = !floating;
+        }
+
+        if floating {
+            if let Some(ws) = self.workspaces.get_mut(wix) {
+                ws.remove_client(id);
+            }
+            self.add_client_to_workspace(wix, id);
+            if let Some(ws) = self.workspaces.get_mut(wix) {
+                ws.focus_client(id);
+            }
+        } else {
+            self.raise_client(id);
+        }
+
+        self.apply_layout(wix);
+        run_hooks!(floating_change, self, id, !floating);
+    }
+
+    /**
+     * Move the client under the cursor with the mouse. This should be bound to a mouse button
+     * press: the pointer will then be grabbed and the client will follow the cursor until the
+     * button is released. Tiled clients will be made floating before they are moved.
+     */
+    pub fn mouse_move_client(&mut self, e: &MouseEvent) {
+        self.start_mouse_drag(e, false);
+    }
+
+    /**
+     * Resize the client under the cursor with the mouse. This should be bound to a mouse button
+     * press: the pointer will then be grabbed and the bottom right corner of the client will
+     * follow the cursor until the button is released. Tiled clients will be made floating before
+     * they are resized.
+     */
+    pub fn mouse_resize_client(&mut self, e: &MouseEvent) {
+        self.start_mouse_drag(e, true);
+    }
+
+    /**
+     * Focus the client that has been requesting attention for the longest, switching to its
+     * workspace if needed. Does nothing if there are no urgent clients.
+     */
+    pub fn focus_urgent(&mut self) {
+        if let Some(&id) = self.urgent_clients.first() {
+            self.focus_client_on_workspace(id);
+        }
+    }
+
+    // Switch to the workspace holding the client and then focus it
+    fn focus_client_on_workspace(&mut self, id: WinId) {
+        let wix = match self.client_map.get(&id) {
+            Some(c) => c.workspace(),
+            None => return,
+        };
+
+        self.focus_workspace(&Selector::Index(wix));
+        self.client_gained_focus(id);
+        let s = self.screens.focused().unwrap();
+        self.x_request(self.conn.warp_cursor(Some(id), s));
+    }
+
+    /**
+     * Kill the focused client window.
+     *
+     * Clients that support WM_DELETE_WINDOW are asked to close themselves, otherwise their
+     * connection to the X server is closed. The client is removed once its window is destroyed.
+     */
     pub fn kill_client(&mut self) {
-        let id = self.conn.focused_client();
-        self.conn.send_client_event(id, "WM_DELETE_WINDOW").unwrap();
-        self.conn.flush();
+        if let Some(id) = self.focused_client().map(|c| c.id()) {
+            self.close_client(id);
+            self.x_request(self.conn.flush());
+        }
+    }
 
-        self.remove_client(id);
-        self.apply_layout(self.active_ws_index());
+    fn close_client(&mut self, id: WinId) {
+        let supports_delete = self
+            .x_request(self.conn.window_supports_protocol(id, "WM_DELETE_WINDOW"))
+            .unwrap_or(false);
+        if supports_delete {
+            if let Err(e) = self.conn.send_client_event(id, "WM_DELETE_WINDOW") {
+                warn!("unable to send WM_DELETE_WINDOW to {}: {}", id, e);
+                self.x_request(self.conn.kill_client(id));
+            }
+        } else {
+            self.x_request(self.conn.kill_client(id));
+        }
+    }
+
+    /**
+     * Forcibly kill the focused client window, ignoring WM_DELETE_WINDOW.
+     *
+     * If the client is running on this machine and has set _NET_WM_PID then its process is sent
+     * SIGKILL, otherwise its connection to the X server is closed.
+     */
+    pub fn force_kill_client(&mut self) {
+        let id = match self.focused_client() {
+            Some(c) => c.id(),
+            None => return,
+        };
+        let mut buf = [0u8; 256];
+        let is_local = match (
+            gethostname(&mut buf),
+            self.conn.str_prop(id, "WM_CLIENT_MACHINE"),
+        ) {
+            (Ok(host), Ok(machine)) => host.to_str() == Ok(machine.trim_end_matches('\0')),
+            _ => false,
+        };
+
+        let killed = match self.conn.atom_prop(id, "_NET_WM_PID").map(killable_pid) {
+            Ok(Some(pid)) if is_local => kill(pid, Signal::SIGKILL).is_ok(),
+            _ => false,
+        };
+
+        if !killed {
+            self.x_request(self.conn.kill_client(id));
+        }
+        self.x_request(self.conn.flush());
     }
 
     /// Get a reference to the first Screen satisfying 'selector'. WinId selectors will return
@@ -971,18 +1990,16 @@ impl<'a> WindowManager<'a> {
     }
 
     /// The current effective screen size of the target screen. Effective screen size is the
-    /// physical screen size minus any space reserved for a status bar.
+    /// physical screen size minus any space reserved for a status bar or dock windows.
     pub fn screen_size(&self, screen_index: usize) -> Option<Region> {
-        self.screens
-            .get(screen_index)
-            .map(|s| s.region(self.show_bar))
+        self.screens.get(screen_index).map(|s| s.region(true))
     }
 
     /// Position an individual client on the display. (x,y) coordinates are absolute (i.e. relative
     /// to the root window not any individual screen).
-    pub fn position_client(&self, id: WinId, region: Region, stack_above: bool) {
-        self.conn
-            .position_window(id, region, self.border_px, stack_above);
+    pub fn position_client(&mut self, id: WinId, region: Region, stack_above: bool) {
+        let border = self.border_px;
+        self.x_request(self.conn.position_window(id, region, border, stack_above));
     }
 
     /// Make the Client with ID 'id' visible at its last known position.
@@ -1008,15 +2025,72 @@ impl<'a> WindowManager<'a> {
     }
 }
 
+// _NET_WM_PID is set by the client so we refuse to signal process groups (0 or anything that
+// would wrap negative), init or ourselves
+fn killable_pid(pid: u32) -> Option<Pid> {
+    if pid <= 1 || pid > i32::MAX as u32 || pid == std::process::id() {
+        warn!("refusing to kill pid {}", pid);
+        None
+    } else {
+        Some(Pid::from_raw(pid as i32))
+    }
+}
+
+// Move bindings (including those inside of key chords) to the key codes that now produce the
+// same key names
+//
+// Bindings whose key is no longer in the mapping stay on their old key code unless a remapped
+// binding now uses it, in which case the remapped binding is kept.
+fn remap_key_bindings(bindings: &mut KeyBindings, names: &HashMap<u8, String>, codes: &CodeMap) {
+    let mut remapped = KeyBindings::new();
+    let mut missing = vec![];
+
+    for (k, mut binding) in bindings.drain() {
+        if let KeyBinding::Chord(inner) = &mut binding {
+            remap_key_bindings(inner, names, codes);
+        }
+
+        match names.get(&k.code).and_then(|name| codes.get(name)) {
+            Some(&code) => {
+                let new = KeyCode { code, ..k };
+                if remapped.insert(new, binding).is_some() {
+                    warn!(
+                        "{:?} is bound more than once in the new keyboard mapping",
+                        new
+                    );
+                }
+            }
+            None => {
+                warn!("{:?} is not in the new keyboard mapping", k);
+                missing.push((k, binding));
+            }
+        }
+    }
+
+    for (k, binding) in missing {
+        match remapped.entry(k) {
+            Entry::Occupied(_) => warn!("dropping {:?}: the key is now used by another binding", k),
+            Entry::Vacant(e) => {
+                e.insert(binding);
+            }
+        }
+    }
+
+    *bindings = remapped;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bindings::*;
     use crate::core::ring::Direction::*;
     use crate::data_types::*;
     use crate::layout::*;
     use crate::screen::*;
     use crate::xconnection::*;
 
+    use std::{cell::RefCell, rc::Rc};
+
     fn wm_with_mock_conn<'a>(layouts: Vec<Layout>, conn: &'a MockXConn) -> WindowManager<'a> {
         let mut conf = Config::default();
         conf.layouts = layouts;
@@ -1068,10 +2142,92 @@ mod tests {
         let mut wm = wm_with_mock_conn(test_layouts(), &conn);
         add_n_clients(&mut wm, 1, 0);
         wm.kill_client();
+        wm.handle_destroy_notify(conn.focused_client().unwrap());
 
         assert_eq!(wm.workspaces[0].len(), 0);
     }
 
+    #[test]
+    fn killing_without_a_focused_client_does_nothing() {
+        use crate::testing::*;
+
+        let conn = SimulatedXConn::new(test_screens(), vec![]);
+        let mut wm = WindowManager::init(Config::default(), &conn);
+        wm.kill_client();
+        wm.force_kill_client();
+
+        assert!(conn.exists(ROOT));
+    }
+
+    #[test]
+    fn only_other_processes_are_force_killed() {
+        for &pid in &[0, 1, std::process::id(), i32::MAX as u32 + 1, u32::MAX] {
+            assert_eq!(killable_pid(pid), None);
+        }
+        assert_eq!(killable_pid(1234), Some(Pid::from_raw(1234)));
+    }
+
+    #[test]
+    fn force_killing_an_invalid_pid_closes_the_connection() {
+        use crate::testing::*;
+
+        let conn = SimulatedXConn::new(test_screens(), vec![]);
+        conn.add_window(1, Region::new(0, 0, 100, 100));
+        let mut buf = [0u8; 256];
+        let host = gethostname(&mut buf).unwrap().to_str().unwrap();
+        conn.set_prop(1, "WM_CLIENT_MACHINE", host);
+        conn.set_u32_prop(1, "_NET_WM_PID", &[std::process::id()]);
+        let mut wm = WindowManager::init(Config::default(), &conn);
+        wm.handle_map_request(1, false);
+        wm.force_kill_client();
+
+        assert!(!conn.exists(1));
+    }
+
+    #[test]
+    fn net_close_window_closes_clients_without_wm_delete_window() {
+        use crate::testing::*;
+
+        let conn = SimulatedXConn::new(test_screens(), vec![]);
+        conn.add_window(1, Region::new(0, 0, 100, 100));
+        let mut wm = WindowManager::init(Config::default(), &conn);
+        wm.handle_map_request(1, false);
+        wm.handle_client_message(1, "_NET_CLOSE_WINDOW", &[0, 0, 0, 0, 0]);
+
+        assert!(!conn.exists(1));
+    }
+
+    #[test]
+    fn unmanaged_windows_keep_their_requested_border() {
+        use crate::testing::*;
+
+        let conn = SimulatedXConn::new(test_screens(), vec![]);
+        conn.add_window(1, Region::new(0, 0, 100, 100));
+        let mut wm = WindowManager::init(Config::default(), &conn);
+        wm.handle_configure_request(1, Region::new(10, 20, 30, 40), 3);
+
+        assert_eq!(conn.geometry(1), Some(Region::new(10, 20, 30, 40)));
+        assert_eq!(conn.border_width(1), Some(3));
+    }
+
+    #[test]
+    fn tiled_clients_are_sent_their_layout_region() {
+        use crate::testing::*;
+
+        let conn = SimulatedXConn::new(test_screens(), vec![]);
+        conn.add_window(1, Region::new(0, 0, 100, 100));
+        let mut wm = WindowManager::init(Config::default(), &conn);
+        wm.handle_map_request(1, false);
+        let tiled = conn.geometry(1).unwrap();
+
+        conn.position_window(1, Region::new(5, 5, 50, 50), 0, false)
+            .unwrap();
+        wm.handle_configure_request(1, Region::new(10, 20, 30, 40), 0);
+
+        assert_eq!(conn.configure_notify(1), Some(tiled));
+        assert_eq!(conn.geometry(1), Some(Region::new(5, 5, 50, 50)));
+    }
+
     #[test]
     fn kill_client_kills_focused_not_first() {
         let conn = MockXConn::new(test_screens(), vec![]);
@@ -1081,6 +2237,7 @@ mod tests {
         wm.cycle_client(Forward); // 40 focused
         assert_eq!(wm.workspaces[0].focused_client(), Some(40));
         wm.kill_client(); // remove 40, focus 30
+        wm.handle_destroy_notify(conn.focused_client().unwrap());
 
         let ids: Vec<WinId> = wm.workspaces[0].iter().cloned().collect();
         assert_eq!(ids, vec![50, 30, 20, 10]);
@@ -1096,6 +2253,7 @@ mod tests {
         wm.client_to_workspace(&Selector::Index(1));
         wm.focus_workspace(&Selector::Index(1));
         wm.kill_client();
+        wm.handle_destroy_notify(conn.focused_client().unwrap());
 
         // should have removed first client on ws::1 (last sent from ws::0)
         assert_eq!(wm.workspaces[1].iter().collect::<Vec<&WinId>>(), vec![&20]);
@@ -1160,6 +2318,366 @@ mod tests {
         assert_eq!(wm.client_map.get(&10).map(|c| c.workspace()), Some(0));
     }
 
+    #[test]
+    fn floating_clients_are_tracked_on_their_workspace() {
+        let conn = MockXConn::new(test_screens(), vec![]);
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 3, 0); // [30, 20, 10]
+        wm.client_map.get_mut(&20).unwrap().floating = true;
+
+        wm.cycle_client(Forward);
+        assert_eq!(wm.workspaces[0].focused_client(), Some(20));
+
+        wm.client_to_workspace(&Selector::Index(1));
+        assert_eq!(wm.workspaces[0].clients(), vec![30, 10]);
+        assert_eq!(wm.workspaces[1].clients(), vec![20]);
+        assert_eq!(wm.client_map.get(&20).map(|c| c.workspace()), Some(1));
+    }
+
+    #[test]
+    fn toggling_floating_rejoins_at_insert_point() {
+        let conn = MockXConn::new(test_screens(), vec![]);
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 3, 0); // [30, 20, 10]
+
+        wm.toggle_client_floating(&Selector::WinId(10));
+        assert!(wm.client_map.get(&10).unwrap().is_floating());
+        assert_eq!(wm.workspaces[0].clients(), vec![30, 20, 10]);
+
+        wm.toggle_client_floating(&Selector::WinId(10));
+        assert!(!wm.client_map.get(&10).unwrap().is_floating());
+        assert_eq!(wm.workspaces[0].clients(), vec![10, 30, 20]);
+        assert_eq!(wm.workspaces[0].focused_client(), Some(10));
+    }
+
+    fn mouse_event(id: WinId, kind: MouseEventKind) -> MouseEvent {
+        MouseEvent {
+            id,
+            rpt: Point::new(10, 10),
+            wpt: Point::new(0, 0),
+            state: MouseState::new(MouseButton::Left, vec![ModifierKey::Meta]),
+            kind,
+        }
+    }
+
+    #[test]
+    fn mouse_dragging_a_tiled_client_makes_it_float() {
+        let conn = MockXConn::new(test_screens(), vec![]);
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+
+        wm.mouse_move_client(&mouse_event(10, MouseEventKind::Press));
+        assert!(wm.mouse_drag.is_some());
+        assert!(wm.client_map.get(&10).unwrap().is_floating());
+        assert_eq!(wm.focused_client().map(|c| c.id()), Some(10));
+
+        wm.handle_mouse_event(mouse_event(10, MouseEventKind::Motion));
+        assert!(wm.mouse_drag.is_some());
+        wm.handle_mouse_event(mouse_event(10, MouseEventKind::Release));
+        assert!(wm.mouse_drag.is_none());
+    }
+
+    #[test]
+    fn floating_clients_are_stacked_above_tiled_clients() {
+        let conn = MockXConn::new(test_screens(), vec![]);
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 3, 0);
+        assert_eq!(wm.client_list, vec![10, 20, 30]);
+        assert_eq!(wm.stacking_order, vec![10, 20, 30]);
+
+        wm.toggle_client_floating(&Selector::WinId(10));
+        assert_eq!(wm.stacking_order, vec![20, 30, 10]);
+
+        wm.remove_client(20);
+        assert_eq!(wm.client_list, vec![10, 30]);
+        assert_eq!(wm.stacking_order, vec![30, 10]);
+    }
+
+    #[test]
+    fn active_window_requests_respect_focus_stealing_policy() {
+        let conn = MockXConn::new(test_screens(), vec![]);
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 1, 0); // [10] on 0
+        wm.focus_workspace(&Selector::Index(2));
+        add_n_clients(&mut wm, 1, 1); // [20] on 2
+
+        // applications are not allowed to steal focus by default
+        wm.handle_client_message(10, "_NET_ACTIVE_WINDOW", &[1, 0, 0, 0, 0]);
+        assert_eq!(wm.active_ws_index(), 2);
+        assert!(wm.client_map.get(&10).unwrap().urgent);
+
+        // pagers are
+        wm.handle_client_message(10, "_NET_ACTIVE_WINDOW", &[2, 0, 0, 0, 0]);
+        assert_eq!(wm.active_ws_index(), 0);
+        assert_eq!(wm.focused_client, Some(10));
+    }
+
+    #[test]
+    fn focus_stealing_policies_decide_who_can_activate_clients() {
+        use crate::testing::*;
+
+        // client 1 is on workspace 0 and client 2 on the focused workspace 2, with source
+        // indications of 1 for applications, 2 for pagers and 0 for old clients
+        let cases = &[
+            (FocusStealing::Allow, 1, true),
+            (FocusStealing::PagersOnly, 1, false),
+            (FocusStealing::PagersOnly, 2, true),
+            (FocusStealing::PagersOnly, 0, true),
+            (FocusStealing::Deny, 2, false),
+        ];
+
+        for &(policy, source, allowed) in cases {
+            let conn = SimulatedXConn::new(test_screens(), vec![]);
+            conn.add_window(1, Region::new(0, 0, 100, 100));
+            conn.add_window(2, Region::new(0, 0, 100, 100));
+            let mut config = Config::default();
+            config.focus_stealing = policy;
+            let mut wm = WindowManager::init(config, &conn);
+            wm.handle_map_request(1, false);
+            wm.focus_workspace(&Selector::Index(2));
+            wm.handle_map_request(2, false);
+
+            wm.handle_client_message(1, "_NET_ACTIVE_WINDOW", &[source, 0, 0, 0, 0]);
+            let (wix, focused, urgent) = if allowed { (0, 1, false) } else { (2, 2, true) };
+            assert_eq!(wm.active_ws_index(), wix, "{:?} {}", policy, source);
+            assert_eq!(wm.focused_client, Some(focused), "{:?} {}", policy, source);
+            assert_eq!(conn.focused(), Some(focused), "{:?} {}", policy, source);
+            assert_eq!(wm.client_map[&1].urgent, urgent, "{:?} {}", policy, source);
+        }
+    }
+
+    #[test]
+    fn net_wm_desktop_requests_move_the_client() {
+        let conn = MockXConn::new(test_screens(), vec![]);
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0); // [20, 10] on 0
+
+        wm.handle_client_message(10, "_NET_WM_DESKTOP", &[3, 0, 0, 0, 0]);
+        assert_eq!(wm.workspaces[0].clients(), vec![20]);
+        assert_eq!(wm.workspaces[3].clients(), vec![10]);
+        assert_eq!(wm.client_map.get(&10).unwrap().workspace(), 3);
+
+        wm.handle_client_message(0, "_NET_CURRENT_DESKTOP", &[3, 0, 0, 0, 0]);
+        assert_eq!(wm.active_ws_index(), 3);
+    }
+
+    #[test]
+    fn existing_windows_are_adopted_onto_their_previous_workspace() {
+        let conn = MockXConn::new(test_screens(), vec![]);
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+
+        // MockXConn returns the window id as the value of _NET_WM_DESKTOP
+        wm.adopt_existing_window(3);
+        wm.adopt_existing_window(40);
+        assert_eq!(wm.workspaces[3].clients(), vec![3]);
+        assert_eq!(wm.workspaces[0].clients(), vec![40]);
+        assert!(!wm.client_map.get(&3).unwrap().mapped);
+        assert!(wm.client_map.get(&40).unwrap().mapped);
+    }
+
+    // Windows 1, 2 and 3 are mapped before starting with 1 last seen on workspace 2
+    fn run_with_existing_windows(conn: &crate::testing::SimulatedXConn) -> WindowManager<'_> {
+        for id in 1..=3 {
+            conn.add_window(id, Region::new(0, 0, 100, 100));
+            conn.map_window(id).unwrap();
+        }
+        conn.set_u32_prop(1, "_NET_WM_DESKTOP", &[2]);
+        conn.set_u32_prop(2, "_NET_WM_DESKTOP", &[0]);
+
+        let exit = KeyCode {
+            mask: 0,
+            code: 99,
+            release: false,
+        };
+        conn.push_event(XEvent::KeyPress(exit));
+        let mut bindings: KeyBindings = HashMap::new();
+        bindings.insert(exit, run_internal!(exit).into());
+        let mut wm = WindowManager::init(Config::default(), conn);
+        wm.grab_keys_and_run(bindings, HashMap::new());
+
+        wm
+    }
+
+    #[test]
+    fn adopted_windows_return_to_their_workspace_and_the_active_window_keeps_focus() {
+        use crate::testing::*;
+
+        let conn = SimulatedXConn::new(test_screens(), vec![]);
+        conn.set_u32_prop(ROOT, "_NET_ACTIVE_WINDOW", &[2]);
+        let wm = run_with_existing_windows(&conn);
+
+        assert_eq!(wm.workspaces[2].clients(), vec![1]);
+        assert!(wm.workspaces[0].clients().contains(&2));
+        assert!(wm.workspaces[0].clients().contains(&3));
+        assert!(!conn.is_mapped(1));
+        assert!(conn.is_mapped(2) && conn.is_mapped(3));
+        assert_eq!(wm.focused_client, Some(2));
+        assert_eq!(conn.focused(), Some(2));
+    }
+
+    #[test]
+    fn the_first_visible_adopted_window_is_focused_without_an_active_window() {
+        use crate::testing::*;
+
+        let conn = SimulatedXConn::new(test_screens(), vec![]);
+        let wm = run_with_existing_windows(&conn);
+
+        assert_eq!(wm.focused_client, Some(2));
+        assert_eq!(conn.focused(), Some(2));
+    }
+
+    #[test]
+    fn only_client_initiated_unmaps_remove_clients() {
+        let conn = MockXConn::new(test_screens(), vec![]);
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0); // [20, 10] on 0
+
+        // Hiding the workspace unmaps both clients
+        wm.focus_workspace(&Selector::Index(2));
+        wm.handle_unmap_notify(10);
+        wm.handle_unmap_notify(20);
+        assert_eq!(wm.workspaces[0].clients(), vec![20, 10]);
+
+        wm.focus_workspace(&Selector::Index(0));
+        wm.handle_unmap_notify(10);
+        assert_eq!(wm.workspaces[0].clients(), vec![20]);
+        assert!(!wm.client_map.contains_key(&10));
+    }
+
+    #[test]
+    fn focus_urgent_jumps_to_the_oldest_urgent_client() {
+        let conn = MockXConn::new(test_screens(), vec![]);
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0); // [20, 10] on 0
+        wm.focus_workspace(&Selector::Index(2));
+        add_n_clients(&mut wm, 1, 2); // [30] on 2
+        wm.focus_workspace(&Selector::Index(0));
+
+        // The focused client is never marked as urgent
+        wm.set_urgent(20, true);
+        wm.set_urgent(30, true);
+        wm.set_urgent(10, true);
+        assert_eq!(wm.urgent_clients, vec![30, 10]);
+
+        wm.focus_urgent();
+        assert_eq!(wm.active_ws_index(), 2);
+        assert_eq!(wm.focused_client, Some(30));
+        assert!(!wm.client_map.get(&30).unwrap().urgent);
+        assert_eq!(wm.urgent_clients, vec![10]);
+    }
+
+    #[test]
+    fn transient_clients_follow_their_parent() {
+        let conn = MockXConn::new(test_screens(), vec![]);
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0); // [20, 10]
+        if let Some(c) = wm.client_map.get_mut(&20) {
+            c.floating = true;
+            c.transient_for = Some(10);
+        }
+
+        wm.client_gained_focus(10);
+        wm.client_to_workspace(&Selector::Index(2));
+        assert_eq!(wm.workspaces[0].len(), 0);
+        assert_eq!(wm.workspaces[2].clients(), vec![20, 10]);
+        assert_eq!(wm.client_map.get(&20).map(|c| c.workspace()), Some(2));
+        assert!(!wm.client_map.get(&20).unwrap().mapped);
+    }
+
+    #[test]
+    fn floating_clients_are_moved_to_the_screen_of_their_new_workspace() {
+        use crate::testing::*;
+
+        let conn = SimulatedXConn::new(test_screens(), vec![]);
+        conn.add_window(1, Region::new(100, 50, 200, 150));
+        let mut wm = WindowManager::init(Config::default(), &conn);
+        wm.handle_map_request(1, false);
+        wm.toggle_client_floating(&Selector::Focused);
+        let (x, y, w, h) = conn.geometry(1).unwrap().values();
+
+        wm.client_to_workspace(&Selector::Index(1));
+        assert_eq!(wm.workspaces[1].clients(), vec![1]);
+        assert_eq!(conn.geometry(1), Some(Region::new(x + 1366, y, w, h)));
+        assert!(conn.is_mapped(1));
+    }
+
+    #[test]
+    fn floating_clients_follow_their_workspace_when_screens_swap() {
+        use crate::testing::*;
+
+        let conn = SimulatedXConn::new(test_screens(), vec![]);
+        conn.add_window(1, Region::new(100, 50, 200, 150));
+        conn.add_window(2, Region::new(300, 60, 200, 150));
+        let mut wm = WindowManager::init(Config::default(), &conn);
+        for id in &[2, 1] {
+            wm.handle_map_request(*id, false);
+            wm.toggle_client_floating(&Selector::Focused);
+        }
+        wm.client_to_workspace(&Selector::Index(1)); // 1 moves to the second screen
+        let r1 = conn.geometry(1).unwrap();
+        let r2 = conn.geometry(2).unwrap();
+        assert!(r1.values().0 >= 1366);
+        assert!(r2.values().0 < 1366);
+
+        wm.focus_screen(&Selector::Index(0));
+        wm.focus_workspace(&Selector::Index(1));
+        assert_eq!(wm.screens.get(0).map(|s| s.wix), Some(1));
+        assert_eq!(wm.screens.get(1).map(|s| s.wix), Some(0));
+
+        let (x1, y1, w1, h1) = r1.values();
+        let (x2, y2, w2, h2) = r2.values();
+        assert_eq!(conn.geometry(1), Some(Region::new(x1 - 1366, y1, w1, h1)));
+        assert_eq!(conn.geometry(2), Some(Region::new(x2 + 1366, y2, w2, h2)));
+    }
+
+    #[test]
+    fn docks_replace_the_bar_space_while_they_are_mapped() {
+        use crate::testing::*;
+
+        let conn = SimulatedXConn::new(test_screens(), vec![]);
+        conn.add_window(1, Region::new(0, 0, 100, 100));
+        conn.add_window(9, Region::new(0, 738, 1366, 30));
+        conn.set_prop(9, "_NET_WM_WINDOW_TYPE", "_NET_WM_WINDOW_TYPE_DOCK");
+        conn.set_strut(
+            9,
+            Strut {
+                bottom: 30,
+                bottom_x: (0, 1365),
+                ..Default::default()
+            },
+        );
+        let mut wm = WindowManager::init(Config::default(), &conn);
+        wm.handle_map_request(1, false);
+        let tiled = conn.geometry(1).unwrap();
+        assert_eq!(wm.screens[0].region(true), Region::new(0, 18, 1366, 750));
+
+        wm.handle_map_request(9, false);
+        assert_eq!(wm.screens[0].region(true), Region::new(0, 0, 1366, 738));
+        assert_eq!(wm.screens[1].region(true), Region::new(1366, 18, 1366, 750));
+        let (_, y, _, h) = conn.geometry(1).unwrap().values();
+        assert!(y < 18 && y + h <= 738);
+
+        wm.handle_destroy_notify(9);
+        assert_eq!(wm.screens[0].region(true), Region::new(0, 18, 1366, 750));
+        assert_eq!(conn.geometry(1), Some(tiled));
+    }
+
+    #[test]
+    fn windows_destroyed_before_their_map_request_is_handled_are_not_managed() {
+        use crate::testing::*;
+
+        let conn = SimulatedXConn::new(test_screens(), vec![]);
+        conn.add_window(1, Region::new(0, 0, 100, 100));
+        let mut wm = WindowManager::init(Config::default(), &conn);
+        wm.handle_map_request(1, false);
+        wm.handle_map_request(2, false);
+
+        assert!(wm.client_map.contains_key(&1));
+        assert!(!wm.client_map.contains_key(&2));
+        assert!(!wm.docks.contains_key(&2));
+        assert_eq!(wm.workspaces[0].len(), 1);
+    }
+
     #[test]
     fn x_focus_events_set_workspace_focus() {
         let conn = MockXConn::new(test_screens(), vec![]);
@@ -1252,6 +2770,472 @@ mod tests {
         );
     }
 
+    #[test]
+    fn key_bindings_follow_keyboard_layout_changes() {
+        use crate::testing::*;
+
+        // 'y' and 'z' swap places between US and DE layouts
+        let us: KeyboardMapping = vec![(29, vec![0x79]), (52, vec![0x7a])]
+            .into_iter()
+            .collect();
+        let de: KeyboardMapping = vec![(29, vec![0x7a]), (52, vec![0x79])]
+            .into_iter()
+            .collect();
+
+        let conn = SimulatedXConn::new(test_screens(), vec![]);
+        conn.set_keyboard_mapping(us);
+        let mut wm = WindowManager::init(Config::default(), &conn);
+        wm.bind_key("M-y", Box::new(|wm| wm.set_root_window_name("y")))
+            .unwrap();
+
+        let y = KeyCode {
+            mask: u16::from(ModifierKey::Meta),
+            code: 29,
+            release: false,
+        };
+        assert!(conn.is_grabbed(y));
+
+        conn.set_keyboard_mapping(de);
+        wm.handle_mapping_notify();
+        let new_y = KeyCode {
+            mask: y.mask,
+            code: 52,
+            release: false,
+        };
+        assert!(conn.is_grabbed(new_y));
+        assert!(!conn.is_grabbed(y));
+
+        wm.handle_key_press(new_y);
+        assert_eq!(conn.prop(ROOT, "WM_NAME"), Some("y".into()));
+    }
+
+    #[test]
+    fn remapped_key_bindings_win_collisions() {
+        use crate::testing::*;
+
+        // 'y' moves to the key that used to be 'z', and 'z' is no longer on the keyboard
+        let before: KeyboardMapping = vec![(29, vec![0x79]), (52, vec![0x7a])]
+            .into_iter()
+            .collect();
+        let after: KeyboardMapping = vec![(29, vec![0x61]), (52, vec![0x79])]
+            .into_iter()
+            .collect();
+
+        let conn = SimulatedXConn::new(test_screens(), vec![]);
+        conn.set_keyboard_mapping(before);
+        let mut wm = WindowManager::init(Config::default(), &conn);
+        wm.bind_key("y", Box::new(|wm| wm.set_root_window_name("y")))
+            .unwrap();
+        wm.bind_key("z", Box::new(|wm| wm.set_root_window_name("z")))
+            .unwrap();
+
+        conn.set_keyboard_mapping(after);
+        wm.handle_mapping_notify();
+        wm.handle_key_press(KeyCode {
+            mask: 0,
+            code: 52,
+            release: false,
+        });
+
+        assert_eq!(conn.prop(ROOT, "WM_NAME"), Some("y".into()));
+    }
+
+    struct ChordHook(Rc<RefCell<Vec<Option<String>>>>);
+    impl hooks::Hook for ChordHook {
+        fn key_chord_pending(&mut self, _: &mut WindowManager, pending: Option<&str>) {
+            self.0.borrow_mut().push(pending.map(String::from));
+        }
+    }
+
+    fn chord_mapping() -> KeyboardMapping {
+        // Escape, t, a, Shift_L
+        vec![
+            (9, vec![0xff1b]),
+            (28, vec![0x74]),
+            (38, vec![0x61]),
+            (50, vec![0xffe1]),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn bind_chord(wm: &mut WindowManager) {
+        wm.bind_key("M-a t", Box::new(|wm| wm.set_root_window_name("t")))
+            .unwrap();
+    }
+
+    #[test]
+    fn key_chords_run_their_action_once_complete() {
+        use crate::testing::*;
+
+        let calls = Rc::new(RefCell::new(vec![]));
+        let mut config = Config::default();
+        config.hooks = vec![Box::new(ChordHook(Rc::clone(&calls)))];
+        let conn = SimulatedXConn::new(test_screens(), vec![]);
+        conn.set_keyboard_mapping(chord_mapping());
+        let mut wm = WindowManager::init(config, &conn);
+        bind_chord(&mut wm);
+
+        let prefix = KeyCode {
+            mask: u16::from(ModifierKey::Meta),
+            code: 38,
+            release: false,
+        };
+        wm.handle_key_press(prefix);
+        assert!(conn.is_keyboard_grabbed());
+
+        // modifiers held for the next key are ignored
+        wm.handle_key_press(KeyCode {
+            mask: 0,
+            code: 50,
+            release: false,
+        });
+        assert!(conn.is_keyboard_grabbed());
+
+        wm.handle_key_press(KeyCode {
+            mask: 0,
+            code: 28,
+            release: false,
+        });
+        assert!(!conn.is_keyboard_grabbed());
+        assert_eq!(conn.prop(ROOT, "WM_NAME"), Some("t".into()));
+        assert_eq!(*calls.borrow(), vec![Some("M-a".into()), None]);
+    }
+
+    #[test]
+    fn key_chords_are_cancelled_by_escape_and_unbound_keys() {
+        use crate::testing::*;
+
+        let conn = SimulatedXConn::new(test_screens(), vec![]);
+        conn.set_keyboard_mapping(chord_mapping());
+        let mut wm = WindowManager::init(Config::default(), &conn);
+        bind_chord(&mut wm);
+        let prefix = KeyCode {
+            mask: u16::from(ModifierKey::Meta),
+            code: 38,
+            release: false,
+        };
+        let t = KeyCode {
+            mask: 0,
+            code: 28,
+            release: false,
+        };
+
+        for &cancel in &[
+            KeyCode {
+                mask: 0,
+                code: 9,
+                release: false,
+            },
+            prefix,
+        ] {
+            wm.handle_key_press(prefix);
+            wm.handle_key_press(cancel);
+            assert!(!conn.is_keyboard_grabbed());
+
+            wm.handle_key_press(t);
+            assert_ne!(conn.prop(ROOT, "WM_NAME"), Some("t".into()));
+        }
+    }
+
+    #[test]
+    fn key_chords_time_out() {
+        use crate::testing::*;
+
+        let prefix = KeyCode {
+            mask: u16::from(ModifierKey::Meta),
+            code: 38,
+            release: false,
+        };
+        let exit = KeyCode {
+            mask: 0,
+            code: 99,
+            release: false,
+        };
+        let events = vec![
+            XEvent::KeyPress(prefix),
+            XEvent::KeyPress(KeyCode {
+                mask: 0,
+                code: 28,
+                release: false,
+            }),
+            XEvent::KeyPress(exit),
+        ];
+        let conn = SimulatedXConn::new(test_screens(), events);
+        conn.set_keyboard_mapping(chord_mapping());
+
+        let mut config = Config::default();
+        config.key_chord_timeout = Some(Duration::from_secs(0));
+        let mut wm = WindowManager::init(config, &conn);
+        bind_chord(&mut wm);
+        let mut bindings: KeyBindings = HashMap::new();
+        bindings.insert(exit, run_internal!(exit).into());
+        wm.grab_keys_and_run(bindings, HashMap::new());
+
+        assert!(!conn.is_keyboard_grabbed());
+        assert_ne!(conn.prop(ROOT, "WM_NAME"), Some("t".into()));
+    }
+
+    #[test]
+    fn key_bindings_can_be_changed_at_runtime() {
+        use crate::testing::*;
+
+        let conn = SimulatedXConn::new(test_screens(), vec![]);
+        conn.set_keyboard_mapping(chord_mapping());
+        let mut wm = WindowManager::init(Config::default(), &conn);
+        let a = KeyCode {
+            mask: 0,
+            code: 38,
+            release: false,
+        };
+        let prefix = KeyCode {
+            mask: u16::from(ModifierKey::Meta),
+            code: 38,
+            release: false,
+        };
+
+        bind_chord(&mut wm);
+        wm.bind_key("a", Box::new(|wm| wm.set_root_window_name("a")))
+            .unwrap();
+        assert_eq!(wm.list_bindings(), vec!["M-a t", "a"]);
+        assert!(conn.is_grabbed(a) && conn.is_grabbed(prefix));
+
+        assert!(wm.bind_key("M-a", Box::new(|_| ())).is_err());
+        assert!(wm.bind_key("M-b", Box::new(|_| ())).is_err());
+        assert!(wm.bind_key("X-a", Box::new(|_| ())).is_err());
+        assert!(wm.unbind_key("X-a").is_err());
+
+        wm.unbind_key("M-a t").unwrap();
+        assert_eq!(wm.list_bindings(), vec!["a"]);
+        assert!(!conn.is_grabbed(prefix));
+        assert!(wm.unbind_key("M-a t").is_err());
+
+        wm.handle_key_press(a);
+        assert_eq!(conn.prop(ROOT, "WM_NAME"), Some("a".into()));
+        assert_eq!(wm.list_bindings(), vec!["a"]);
+    }
+
+    #[test]
+    fn key_bindings_can_remove_themselves() {
+        use crate::testing::*;
+
+        let conn = SimulatedXConn::new(test_screens(), vec![]);
+        conn.set_keyboard_mapping(chord_mapping());
+        let mut wm = WindowManager::init(Config::default(), &conn);
+        wm.bind_key(
+            "t",
+            Box::new(|wm| {
+                wm.unbind_key("t").unwrap();
+                wm.bind_key("a", Box::new(|_| ())).unwrap();
+            }),
+        )
+        .unwrap();
+
+        wm.handle_key_press(KeyCode {
+            mask: 0,
+            code: 28,
+            release: false,
+        });
+        assert_eq!(wm.list_bindings(), vec!["a"]);
+        assert!(!conn.is_grabbed(KeyCode {
+            mask: 0,
+            code: 28,
+            release: false
+        }));
+    }
+
+    #[test]
+    fn running_key_bindings_are_listed_and_can_rebind_themselves() {
+        use crate::testing::*;
+
+        let conn = SimulatedXConn::new(test_screens(), vec![]);
+        conn.set_keyboard_mapping(chord_mapping());
+        let mut wm = WindowManager::init(Config::default(), &conn);
+        wm.bind_key(
+            "t",
+            Box::new(|wm| {
+                let bound = wm.list_bindings().join(" ");
+                wm.set_root_window_name(&bound);
+                wm.bind_key("t", Box::new(|wm| wm.set_root_window_name("rebound")))
+                    .unwrap();
+            }),
+        )
+        .unwrap();
+        let t = KeyCode {
+            mask: 0,
+            code: 28,
+            release: false,
+        };
+
+        wm.handle_key_press(t);
+        assert_eq!(conn.prop(ROOT, "WM_NAME"), Some("t".into()));
+        assert_eq!(wm.list_bindings(), vec!["t"]);
+
+        wm.handle_key_press(t);
+        assert_eq!(conn.prop(ROOT, "WM_NAME"), Some("rebound".into()));
+    }
+
+    #[test]
+    fn key_releases_are_bound_separately_from_presses() {
+        use crate::testing::*;
+
+        let conn = SimulatedXConn::new(test_screens(), vec![]);
+        conn.set_keyboard_mapping(chord_mapping());
+        let mut wm = WindowManager::init(Config::default(), &conn);
+        bind_chord(&mut wm);
+        wm.bind_key("@a", Box::new(|wm| wm.set_root_window_name("released")))
+            .unwrap();
+        assert!(wm.bind_key("M-a @t", Box::new(|_| ())).is_err());
+
+        let a = KeyCode {
+            mask: 0,
+            code: 38,
+            release: false,
+        };
+        assert_eq!(wm.list_bindings(), vec!["@a", "M-a t"]);
+        assert!(conn.is_grabbed(a));
+
+        wm.handle_key_press(a);
+        assert_eq!(conn.prop(ROOT, "WM_NAME"), None);
+        wm.handle_key_press(KeyCode { release: true, ..a });
+        assert_eq!(conn.prop(ROOT, "WM_NAME"), Some("released".into()));
+
+        // releasing the prefix of a chord leaves it pending
+        let prefix = KeyCode {
+            mask: u16::from(ModifierKey::Meta),
+            ..a
+        };
+        wm.handle_key_press(prefix);
+        wm.handle_key_press(KeyCode {
+            release: true,
+            ..prefix
+        });
+        assert!(conn.is_keyboard_grabbed());
+        wm.handle_key_press(KeyCode { code: 28, ..a });
+        assert_eq!(conn.prop(ROOT, "WM_NAME"), Some("t".into()));
+    }
+
+    #[test]
+    fn client_bindings_depend_on_the_focused_client() {
+        use crate::testing::*;
+
+        let conn = SimulatedXConn::new(test_screens(), vec![]);
+        conn.set_keyboard_mapping(chord_mapping());
+        for (id, class) in &[(1, "st"), (2, "firefox"), (3, "xterm")] {
+            conn.add_window(*id, Region::new(0, 0, 100, 100));
+            conn.set_prop(*id, "WM_CLASS", &format!("{}\0{}", class, class));
+        }
+        let mut wm = WindowManager::init(Config::default(), &conn);
+        let bindings = ClientBindings::new()
+            .with_class("st", run_internal!(set_root_window_name, "st"))
+            .pass_through("firefox");
+        wm.bind_client_key("C-t", bindings).unwrap();
+
+        let t = KeyCode {
+            mask: u16::from(ModifierKey::Ctrl),
+            code: 28,
+            release: false,
+        };
+        // Ctrl is let go of before t so the release is not modified
+        let release = KeyCode {
+            mask: 0,
+            release: true,
+            ..t
+        };
+        for id in 1..=3 {
+            wm.handle_map_request(id, false);
+            wm.handle_key_press(t);
+            wm.handle_key_press(release);
+        }
+        assert_eq!(conn.prop(ROOT, "WM_NAME"), Some("st".into()));
+        assert_eq!(conn.sent_keys(1), vec![]);
+        assert_eq!(conn.sent_keys(2), vec![t, release]);
+        assert_eq!(conn.sent_keys(3), vec![t, release]);
+
+        let bindings = ClientBindings::new()
+            .pass_through("firefox")
+            .otherwise(run_internal!(set_root_window_name, "other"));
+        wm.bind_client_key("C-t", bindings).unwrap();
+        wm.handle_key_press(t);
+        wm.handle_key_press(release);
+        assert_eq!(conn.prop(ROOT, "WM_NAME"), Some("other".into()));
+        assert_eq!(conn.sent_keys(3), vec![t, release]);
+    }
+
+    struct ModeHook(Rc<RefCell<Vec<String>>>);
+    impl hooks::Hook for ModeHook {
+        fn mode_changed(&mut self, _: &mut WindowManager, mode: &str) {
+            self.0.borrow_mut().push(mode.into());
+        }
+    }
+
+    #[test]
+    fn binding_modes_swap_the_grabbed_keys() {
+        use crate::testing::*;
+
+        let key = |code| KeyCode {
+            mask: 0,
+            code,
+            release: false,
+        };
+        let (resize, unknown, h, escape, exit) = (key(27), key(28), key(43), key(9), key(99));
+        let events = vec![
+            XEvent::KeyPress(unknown),
+            XEvent::KeyPress(resize),
+            XEvent::KeyPress(h),
+            XEvent::KeyPress(escape),
+            XEvent::KeyPress(exit),
+        ];
+        let conn = SimulatedXConn::new(test_screens(), events);
+
+        let mut default: KeyBindings = HashMap::new();
+        default.insert(resize, run_internal!(enter_mode, "resize").into());
+        default.insert(unknown, run_internal!(enter_mode, "unknown").into());
+        default.insert(exit, run_internal!(exit).into());
+        let mut resize_mode: KeyBindings = HashMap::new();
+        resize_mode.insert(h, run_internal!(set_root_window_name, "h").into());
+        resize_mode.insert(escape, run_internal!(exit_mode).into());
+        let modes = BindingModes::new(default).with_mode("resize", resize_mode);
+
+        let calls = Rc::new(RefCell::new(vec![]));
+        let mut config = Config::default();
+        config.hooks = vec![Box::new(ModeHook(Rc::clone(&calls)))];
+        let mut wm = WindowManager::init(config, &conn);
+        wm.grab_keys_and_run(modes, HashMap::new());
+
+        assert_eq!(conn.prop(ROOT, "WM_NAME"), Some("h".into()));
+        assert_eq!(*calls.borrow(), vec!["resize", "default"]);
+        assert_eq!(wm.current_mode(), DEFAULT_MODE);
+        assert!(conn.is_grabbed(resize));
+        assert!(!conn.is_grabbed(h));
+    }
+
+    struct PollingHook(Rc<RefCell<usize>>);
+    impl hooks::Hook for PollingHook {
+        fn event_handled(&mut self, wm: &mut WindowManager) {
+            *self.0.borrow_mut() += 1;
+            wm.exit();
+        }
+
+        fn poll_interval(&self) -> Option<Duration> {
+            Some(Duration::from_millis(1))
+        }
+    }
+
+    #[test]
+    fn polling_hooks_run_without_x_events() {
+        use crate::testing::*;
+
+        let conn = SimulatedXConn::new(test_screens(), vec![]);
+        let calls = Rc::new(RefCell::new(0));
+        let mut config = Config::default();
+        config.hooks = vec![Box::new(PollingHook(Rc::clone(&calls)))];
+        let mut wm = WindowManager::init(config, &conn);
+        wm.grab_keys_and_run(KeyBindings::new(), HashMap::new());
+
+        assert_eq!(*calls.borrow(), 1);
+    }
+
     #[test]
     fn selector_workspace() {
         let conn = MockXConn::new(test_screens(), vec![]);
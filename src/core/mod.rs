@@ -7,11 +7,18 @@ pub mod client;
 pub mod data_types;
 pub mod helpers;
 pub mod hooks;
+pub mod keysyms;
 pub mod layout;
 pub mod manager;
+pub mod recording;
 pub mod ring;
 pub mod screen;
+pub mod testing;
 pub mod workspace;
+#[cfg(feature = "x11rb")]
+pub mod x11rb_connection;
+#[cfg(feature = "xcb")]
+pub mod xcb_connection;
 pub mod xconnection;
 
 pub use bindings::{FireAndForget, MouseEventHandler};
@@ -23,4 +30,7 @@ pub use manager::WindowManager;
 pub use ring::Selector;
 pub use screen::Screen;
 pub use workspace::Workspace;
+#[cfg(feature = "x11rb")]
+pub use x11rb_connection::X11rbConnection;
+#[cfg(feature = "xcb")]
 pub use xconnection::XcbConnection;
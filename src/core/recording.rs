@@ -0,0 +1,1518 @@
+/*! Recording and replaying the interaction between penrose and the X server
+ *
+ *  [RecordingXConn] wraps another [XConn] and writes every [XEvent] that it receives, along with
+ *  every call made by the WindowManager and the result that was returned, to a plain text file.
+ *  [ReplayXConn] reads that file back and can be used in place of a real connection to drive
+ *  `WindowManager::grab_keys_and_run`: recorded events are replayed in order, recorded results
+ *  are returned for each call, and any call that differs from the recording is noted as a
+ *  mismatch.
+ *
+ *  A recording has one entry per line. Values are separated by whitespace, with strings quoted
+ *  and escaped as they would be in Rust source:
+ *
+ *  ```text
+ *  # penrose X session recording
+ *  call current_outputs -> ok 1 0 0 0 1920 1080
+ *  event map_request 4194305 false
+ *  call window_geometry 4194305 -> ok 0 0 640 480
+ *  call str_prop 4194305 "WM_CLASS" -> ok "xterm\0XTerm\0"
+ *  call map_window 4194305 -> ok
+ *  ```
+ *
+ *  `flush` and `wait_for_event` are not recorded as calls. A `timeout` line marks a call to
+ *  `wait_for_event_timeout` that returned without an event.
+ */
+use crate::{
+    bindings::{
+        KeyBindings, KeyCode, KeyboardMapping, MouseBindings, MouseEvent, MouseEventKind,
+        MouseState,
+    },
+    data_types::{FocusModel, Point, Region, SizeHints, Strut, WinId, WindowState},
+    screen::Screen,
+    xconnection::{XConn, XError, XErrorKind, XEvent},
+    Result,
+};
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+    time::Duration,
+    vec::IntoIter,
+};
+
+use anyhow::anyhow;
+
+const HEADER: &str = "# penrose X session recording";
+const CALL_SEP: &str = "->";
+
+// Split a line into tokens, keeping quoted strings (and their escapes) intact
+fn tokenize(line: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let mut tok = String::new();
+        if c == '"' {
+            tok.push(chars.next().unwrap());
+            loop {
+                match chars.next() {
+                    Some('\\') => {
+                        tok.push('\\');
+                        tok.push(chars.next().ok_or_else(|| anyhow!("unterminated escape"))?);
+                    }
+                    Some('"') => {
+                        tok.push('"');
+                        break;
+                    }
+                    Some(c) => tok.push(c),
+                    None => return Err(anyhow!("unterminated string in '{}'", line)),
+                }
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                tok.push(c);
+                chars.next();
+            }
+        }
+        tokens.push(tok);
+    }
+
+    Ok(tokens)
+}
+
+// Reverse the escaping applied by the Debug impl for str
+fn unescape(quoted: &str) -> Result<String> {
+    let inner = quoted
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .ok_or_else(|| anyhow!("expected a quoted string, got '{}'", quoted))?;
+
+    let mut s = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            s.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => s.push('\n'),
+            Some('r') => s.push('\r'),
+            Some('t') => s.push('\t'),
+            Some('0') => s.push('\0'),
+            Some('u') => {
+                let hex: String = chars.by_ref().skip(1).take_while(|&c| c != '}').collect();
+                let n = u32::from_str_radix(&hex, 16)?;
+                s.push(std::char::from_u32(n).ok_or_else(|| anyhow!("invalid char: {}", n))?);
+            }
+            Some(c) => s.push(c),
+            None => return Err(anyhow!("unterminated escape in '{}'", quoted)),
+        }
+    }
+
+    Ok(s)
+}
+
+// The remaining tokens of a recording entry
+struct Tokens(IntoIter<String>);
+
+impl Tokens {
+    fn new(tokens: Vec<String>) -> Self {
+        Self(tokens.into_iter())
+    }
+
+    fn next(&mut self) -> Result<String> {
+        self.0
+            .next()
+            .ok_or_else(|| anyhow!("unexpected end of recording entry"))
+    }
+
+    fn parse<T: std::str::FromStr>(&mut self) -> Result<T>
+    where
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        Ok(self.next()?.parse()?)
+    }
+
+    fn decode<T: Decode>(&mut self) -> Result<T> {
+        T::decode(self)
+    }
+
+    fn finish(mut self) -> Result<()> {
+        match self.0.next() {
+            Some(t) => Err(anyhow!("unexpected trailing token '{}'", t)),
+            None => Ok(()),
+        }
+    }
+}
+
+// A value that can be written to a recording
+trait Encode {
+    fn encode(&self, out: &mut Vec<String>);
+}
+
+// A value that can be read back from a recording
+trait Decode: Sized {
+    fn decode(tokens: &mut Tokens) -> Result<Self>;
+}
+
+// The result of a call that can be stubbed out if the call was not in the recording
+trait Reply: Encode + Decode {
+    fn missing(call: &str) -> Self;
+}
+
+macro_rules! plain_tokens(
+    ($($t:ty),+) => {
+        $(
+            impl Encode for $t {
+                fn encode(&self, out: &mut Vec<String>) {
+                    out.push(self.to_string());
+                }
+            }
+
+            impl Decode for $t {
+                fn decode(tokens: &mut Tokens) -> Result<Self> {
+                    tokens.parse()
+                }
+            }
+        )+
+    };
+);
+
+plain_tokens!(bool, u8, u16, u32, usize, f32);
+
+macro_rules! encode_all(
+    ($out:expr, $($val:expr),+) => {
+        $($val.encode($out);)+
+    };
+);
+
+impl<T: Encode + ?Sized> Encode for &T {
+    fn encode(&self, out: &mut Vec<String>) {
+        (**self).encode(out)
+    }
+}
+
+impl Encode for str {
+    fn encode(&self, out: &mut Vec<String>) {
+        out.push(format!("{:?}", self));
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, out: &mut Vec<String>) {
+        self.as_str().encode(out)
+    }
+}
+
+impl Decode for String {
+    fn decode(tokens: &mut Tokens) -> Result<Self> {
+        unescape(&tokens.next()?)
+    }
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encode(&self, out: &mut Vec<String>) {
+        self.len().encode(out);
+        self.iter().for_each(|t| t.encode(out));
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, out: &mut Vec<String>) {
+        self.as_slice().encode(out)
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(tokens: &mut Tokens) -> Result<Self> {
+        let n: usize = tokens.parse()?;
+        (0..n).map(|_| tokens.decode()).collect()
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, out: &mut Vec<String>) {
+        match self {
+            Some(t) => {
+                out.push("some".into());
+                t.encode(out);
+            }
+            None => out.push("none".into()),
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode(tokens: &mut Tokens) -> Result<Self> {
+        match tokens.next()?.as_ref() {
+            "some" => Ok(Some(tokens.decode()?)),
+            "none" => Ok(None),
+            t => Err(anyhow!("expected some or none, got '{}'", t)),
+        }
+    }
+}
+
+// Key codes are sorted to give a stable ordering
+impl Encode for KeyboardMapping {
+    fn encode(&self, out: &mut Vec<String>) {
+        let mut keys: Vec<(u8, &Vec<u32>)> = self.iter().map(|(&k, v)| (k, v)).collect();
+        keys.sort_by_key(|&(k, _)| k);
+        keys.encode(out);
+    }
+}
+
+impl Decode for KeyboardMapping {
+    fn decode(tokens: &mut Tokens) -> Result<Self> {
+        let keys: Vec<(u8, Vec<u32>)> = tokens.decode()?;
+        Ok(keys.into_iter().collect())
+    }
+}
+
+impl<A: Encode, B: Encode> Encode for (A, B) {
+    fn encode(&self, out: &mut Vec<String>) {
+        encode_all!(out, self.0, self.1);
+    }
+}
+
+impl<A: Decode, B: Decode> Decode for (A, B) {
+    fn decode(tokens: &mut Tokens) -> Result<Self> {
+        Ok((tokens.decode()?, tokens.decode()?))
+    }
+}
+
+impl Encode for Region {
+    fn encode(&self, out: &mut Vec<String>) {
+        let (x, y, w, h) = self.values();
+        encode_all!(out, x, y, w, h);
+    }
+}
+
+impl Decode for Region {
+    fn decode(tokens: &mut Tokens) -> Result<Self> {
+        Ok(Region::new(
+            tokens.parse()?,
+            tokens.parse()?,
+            tokens.parse()?,
+            tokens.parse()?,
+        ))
+    }
+}
+
+impl Encode for Point {
+    fn encode(&self, out: &mut Vec<String>) {
+        encode_all!(out, self.x, self.y);
+    }
+}
+
+impl Decode for Point {
+    fn decode(tokens: &mut Tokens) -> Result<Self> {
+        Ok(Point::new(tokens.parse()?, tokens.parse()?))
+    }
+}
+
+impl Encode for Screen {
+    fn encode(&self, out: &mut Vec<String>) {
+        encode_all!(out, self.wix, self.region(false));
+    }
+}
+
+impl Decode for Screen {
+    fn decode(tokens: &mut Tokens) -> Result<Self> {
+        let wix = tokens.parse()?;
+        Ok(Screen::new(tokens.decode()?, wix))
+    }
+}
+
+impl Encode for Strut {
+    fn encode(&self, out: &mut Vec<String>) {
+        encode_all!(
+            out,
+            self.left,
+            self.right,
+            self.top,
+            self.bottom,
+            self.left_y,
+            self.right_y,
+            self.top_x,
+            self.bottom_x
+        );
+    }
+}
+
+impl Decode for Strut {
+    fn decode(tokens: &mut Tokens) -> Result<Self> {
+        Ok(Strut {
+            left: tokens.parse()?,
+            right: tokens.parse()?,
+            top: tokens.parse()?,
+            bottom: tokens.parse()?,
+            left_y: tokens.decode()?,
+            right_y: tokens.decode()?,
+            top_x: tokens.decode()?,
+            bottom_x: tokens.decode()?,
+        })
+    }
+}
+
+impl Encode for SizeHints {
+    fn encode(&self, out: &mut Vec<String>) {
+        encode_all!(out, self.min, self.max, self.base, self.inc, self.aspect);
+    }
+}
+
+impl Decode for SizeHints {
+    fn decode(tokens: &mut Tokens) -> Result<Self> {
+        Ok(SizeHints {
+            min: tokens.decode()?,
+            max: tokens.decode()?,
+            base: tokens.decode()?,
+            inc: tokens.decode()?,
+            aspect: tokens.decode()?,
+        })
+    }
+}
+
+impl Encode for FocusModel {
+    fn encode(&self, out: &mut Vec<String>) {
+        let model = match self {
+            FocusModel::NoInput => "no_input",
+            FocusModel::Passive => "passive",
+            FocusModel::LocallyActive => "locally_active",
+            FocusModel::GloballyActive => "globally_active",
+        };
+        out.push(model.into());
+    }
+}
+
+impl Decode for FocusModel {
+    fn decode(tokens: &mut Tokens) -> Result<Self> {
+        match tokens.next()?.as_ref() {
+            "no_input" => Ok(FocusModel::NoInput),
+            "passive" => Ok(FocusModel::Passive),
+            "locally_active" => Ok(FocusModel::LocallyActive),
+            "globally_active" => Ok(FocusModel::GloballyActive),
+            t => Err(anyhow!("unknown focus model '{}'", t)),
+        }
+    }
+}
+
+impl Encode for WindowState {
+    fn encode(&self, out: &mut Vec<String>) {
+        let state = match self {
+            WindowState::Withdrawn => "withdrawn",
+            WindowState::Normal => "normal",
+            WindowState::Iconic => "iconic",
+        };
+        out.push(state.into());
+    }
+}
+
+impl Encode for KeyCode {
+    fn encode(&self, out: &mut Vec<String>) {
+        encode_all!(out, self.mask, self.code, self.release);
+    }
+}
+
+impl Decode for KeyCode {
+    fn decode(tokens: &mut Tokens) -> Result<Self> {
+        Ok(KeyCode {
+            mask: tokens.parse()?,
+            code: tokens.parse()?,
+            release: tokens.parse()?,
+        })
+    }
+}
+
+impl Encode for MouseState {
+    fn encode(&self, out: &mut Vec<String>) {
+        encode_all!(out, self.button(), self.mask());
+    }
+}
+
+impl Decode for MouseState {
+    fn decode(tokens: &mut Tokens) -> Result<Self> {
+        let button = tokens.parse()?;
+        MouseState::from_event(button, tokens.parse()?)
+    }
+}
+
+impl Encode for MouseEvent {
+    fn encode(&self, out: &mut Vec<String>) {
+        let kind = match self.kind {
+            MouseEventKind::Press => "press",
+            MouseEventKind::Release => "release",
+            MouseEventKind::Motion => "motion",
+        };
+        out.push(kind.into());
+        encode_all!(out, self.id, self.rpt, self.wpt, self.state);
+    }
+}
+
+impl Decode for MouseEvent {
+    fn decode(tokens: &mut Tokens) -> Result<Self> {
+        let kind = match tokens.next()?.as_ref() {
+            "press" => MouseEventKind::Press,
+            "release" => MouseEventKind::Release,
+            "motion" => MouseEventKind::Motion,
+            t => return Err(anyhow!("unknown mouse event kind '{}'", t)),
+        };
+
+        Ok(MouseEvent {
+            id: tokens.parse()?,
+            rpt: tokens.decode()?,
+            wpt: tokens.decode()?,
+            state: tokens.decode()?,
+            kind,
+        })
+    }
+}
+
+impl Encode for XError {
+    fn encode(&self, out: &mut Vec<String>) {
+        let kind = u8::from(self.kind);
+        encode_all!(
+            out,
+            kind,
+            self.resource_id,
+            self.major_code,
+            self.minor_code,
+            self.sequence
+        );
+    }
+}
+
+impl Decode for XError {
+    fn decode(tokens: &mut Tokens) -> Result<Self> {
+        Ok(XError {
+            kind: XErrorKind::from(tokens.parse::<u8>()?),
+            resource_id: tokens.parse()?,
+            major_code: tokens.parse()?,
+            minor_code: tokens.parse()?,
+            sequence: tokens.parse()?,
+        })
+    }
+}
+
+impl Encode for XEvent {
+    fn encode(&self, out: &mut Vec<String>) {
+        let mut tag = |t: &str| out.push(t.into());
+        match self {
+            XEvent::MouseEvent(e) => {
+                tag("mouse");
+                e.encode(out);
+            }
+            XEvent::KeyPress(k) => {
+                tag("key_press");
+                k.encode(out);
+            }
+            XEvent::MapRequest { id, ignore } => {
+                tag("map_request");
+                encode_all!(out, id, ignore);
+            }
+            XEvent::Enter { id, rpt, wpt } => {
+                tag("enter");
+                encode_all!(out, id, rpt, wpt);
+            }
+            XEvent::Leave { id, rpt, wpt } => {
+                tag("leave");
+                encode_all!(out, id, rpt, wpt);
+            }
+            XEvent::Destroy { id } => {
+                tag("destroy");
+                id.encode(out);
+            }
+            XEvent::UnmapNotify { id } => {
+                tag("unmap_notify");
+                id.encode(out);
+            }
+            XEvent::ScreenChange => tag("screen_change"),
+            XEvent::RandrNotify => tag("randr_notify"),
+            XEvent::ConfigureNotify { id, r, is_root } => {
+                tag("configure_notify");
+                encode_all!(out, id, r, is_root);
+            }
+            XEvent::ConfigureRequest { id, r, border } => {
+                tag("configure_request");
+                encode_all!(out, id, r, border);
+            }
+            XEvent::PropertyNotify { id, atom, is_root } => {
+                tag("property_notify");
+                encode_all!(out, id, atom, is_root);
+            }
+            XEvent::ClientMessage { id, dtype, data } => {
+                tag("client_message");
+                encode_all!(out, id, dtype, data);
+            }
+            XEvent::MappingNotify => tag("mapping_notify"),
+            XEvent::Error(e) => {
+                tag("error");
+                e.encode(out);
+            }
+        }
+    }
+}
+
+impl Decode for XEvent {
+    fn decode(tokens: &mut Tokens) -> Result<Self> {
+        let event = match tokens.next()?.as_ref() {
+            "mouse" => XEvent::MouseEvent(tokens.decode()?),
+            "key_press" => XEvent::KeyPress(tokens.decode()?),
+            "map_request" => XEvent::MapRequest {
+                id: tokens.parse()?,
+                ignore: tokens.parse()?,
+            },
+            "enter" => XEvent::Enter {
+                id: tokens.parse()?,
+                rpt: tokens.decode()?,
+                wpt: tokens.decode()?,
+            },
+            "leave" => XEvent::Leave {
+                id: tokens.parse()?,
+                rpt: tokens.decode()?,
+                wpt: tokens.decode()?,
+            },
+            "destroy" => XEvent::Destroy {
+                id: tokens.parse()?,
+            },
+            "unmap_notify" => XEvent::UnmapNotify {
+                id: tokens.parse()?,
+            },
+            "screen_change" => XEvent::ScreenChange,
+            "randr_notify" => XEvent::RandrNotify,
+            "configure_notify" => XEvent::ConfigureNotify {
+                id: tokens.parse()?,
+                r: tokens.decode()?,
+                is_root: tokens.parse()?,
+            },
+            "configure_request" => XEvent::ConfigureRequest {
+                id: tokens.parse()?,
+                r: tokens.decode()?,
+                border: tokens.parse()?,
+            },
+            "property_notify" => XEvent::PropertyNotify {
+                id: tokens.parse()?,
+                atom: tokens.parse()?,
+                is_root: tokens.parse()?,
+            },
+            "client_message" => XEvent::ClientMessage {
+                id: tokens.parse()?,
+                dtype: tokens.parse()?,
+                data: tokens.decode()?,
+            },
+            "mapping_notify" => XEvent::MappingNotify,
+            "error" => XEvent::Error(tokens.decode()?),
+            t => return Err(anyhow!("unknown event type '{}'", t)),
+        };
+
+        Ok(event)
+    }
+}
+
+impl<T: Encode> Encode for Result<T> {
+    fn encode(&self, out: &mut Vec<String>) {
+        match self {
+            Ok(t) => {
+                out.push("ok".into());
+                t.encode(out);
+            }
+            Err(e) => match e.downcast_ref::<XError>() {
+                Some(err) => {
+                    out.push("x_error".into());
+                    err.encode(out);
+                }
+                None => {
+                    out.push("err".into());
+                    e.to_string().encode(out);
+                }
+            },
+        }
+    }
+}
+
+impl<T: Decode> Decode for Result<T> {
+    fn decode(tokens: &mut Tokens) -> Result<Self> {
+        match tokens.next()?.as_ref() {
+            "ok" => Ok(Ok(tokens.decode()?)),
+            "x_error" => Ok(Err(anyhow!(tokens.decode::<XError>()?))),
+            "err" => Ok(Err(anyhow!(tokens.decode::<String>()?))),
+            t => Err(anyhow!("expected a result, got '{}'", t)),
+        }
+    }
+}
+
+impl Encode for () {
+    fn encode(&self, _: &mut Vec<String>) {}
+}
+
+impl Decode for () {
+    fn decode(_: &mut Tokens) -> Result<Self> {
+        Ok(())
+    }
+}
+
+impl<T: Encode + Decode> Reply for Result<T> {
+    fn missing(call: &str) -> Self {
+        Err(anyhow!("no recorded reply for '{}'", call))
+    }
+}
+
+impl Reply for bool {
+    fn missing(_: &str) -> Self {
+        false
+    }
+}
+
+impl Reply for FocusModel {
+    fn missing(_: &str) -> Self {
+        FocusModel::Passive
+    }
+}
+
+// Grabbed bindings are stored in HashMaps so they are sorted to give a stable ordering
+fn sorted_bindings(keys: &KeyBindings, mouse: &MouseBindings) -> (Vec<KeyCode>, Vec<MouseState>) {
+    let mut codes: Vec<KeyCode> = keys.keys().copied().collect();
+    codes.sort_by_key(|k| (k.mask, k.code, k.release));
+    let mut states: Vec<MouseState> = mouse.keys().map(|(_, s)| s.clone()).collect();
+    states.sort_by_key(|s| (s.button(), s.mask()));
+    states.dedup();
+
+    (codes, states)
+}
+
+macro_rules! args(
+    () => { Vec::<String>::new() };
+    ($($arg:expr),+) => {
+        {
+            let mut out = Vec::new();
+            encode_all!(&mut out, $($arg),+);
+            out
+        }
+    };
+);
+
+/**
+ * An XConn that records all of the interaction between penrose and another XConn.
+ *
+ * The recording can be replayed using a [ReplayXConn] in order to reproduce bugs without needing
+ * to share the original X session.
+ */
+pub struct RecordingXConn<C: XConn> {
+    inner: C,
+    out: RefCell<Box<dyn Write>>,
+}
+
+impl<C: XConn> RecordingXConn<C> {
+    /// Record all interaction with `inner` to a new file at `path`, replacing it if it exists.
+    pub fn new(inner: C, path: impl AsRef<Path>) -> Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        Self::with_writer(inner, Box::new(file))
+    }
+
+    /// Record all interaction with `inner` to an arbitrary writer.
+    pub fn with_writer(inner: C, out: Box<dyn Write>) -> Result<Self> {
+        let conn = Self {
+            inner,
+            out: RefCell::new(out),
+        };
+        writeln!(conn.out.borrow_mut(), "{}", HEADER)?;
+
+        Ok(conn)
+    }
+
+    /// Stop recording and return the wrapped XConn.
+    pub fn into_inner(self) -> C {
+        if let Err(e) = self.out.borrow_mut().flush() {
+            error!("unable to flush X session recording: {}", e);
+        }
+        self.inner
+    }
+
+    fn write_line(&self, kind: &str, tokens: &[String]) {
+        let mut out = self.out.borrow_mut();
+        let res = if tokens.is_empty() {
+            writeln!(out, "{}", kind)
+        } else {
+            writeln!(out, "{} {}", kind, tokens.join(" "))
+        };
+        if let Err(e) = res {
+            error!("unable to write X session recording: {}", e);
+        }
+    }
+
+    fn record<T: Encode>(&self, name: &str, mut args: Vec<String>, result: T) -> T {
+        args.insert(0, name.into());
+        args.push(CALL_SEP.into());
+        result.encode(&mut args);
+        self.write_line("call", &args);
+
+        result
+    }
+}
+
+impl<C: XConn> XConn for RecordingXConn<C> {
+    fn flush(&self) -> Result<()> {
+        self.out.borrow_mut().flush()?;
+        self.inner.flush()
+    }
+
+    fn wait_for_event(&self) -> Option<XEvent> {
+        let event = self.inner.wait_for_event();
+        if let Some(e) = &event {
+            let mut tokens = Vec::new();
+            e.encode(&mut tokens);
+            self.write_line("event", &tokens);
+        }
+        event
+    }
+
+    fn wait_for_event_timeout(&self, timeout: Duration) -> Option<XEvent> {
+        let event = self.inner.wait_for_event_timeout(timeout);
+        match &event {
+            Some(e) => {
+                let mut tokens = Vec::new();
+                e.encode(&mut tokens);
+                self.write_line("event", &tokens);
+            }
+            None => self.write_line("timeout", &[]),
+        }
+        event
+    }
+
+    fn current_outputs(&self) -> Result<Vec<Screen>> {
+        self.record("current_outputs", args!(), self.inner.current_outputs())
+    }
+
+    fn cursor_position(&self) -> Result<Point> {
+        self.record("cursor_position", args!(), self.inner.cursor_position())
+    }
+
+    fn position_window(&self, id: WinId, r: Region, border: u32, stack_above: bool) -> Result<()> {
+        let res = self.inner.position_window(id, r, border, stack_above);
+        self.record("position_window", args!(id, r, border, stack_above), res)
+    }
+
+    fn raise_window(&self, id: WinId) -> Result<()> {
+        self.record("raise_window", args!(id), self.inner.raise_window(id))
+    }
+
+    fn send_configure_notify(&self, id: WinId, r: Region, border: u32) -> Result<()> {
+        let res = self.inner.send_configure_notify(id, r, border);
+        self.record("send_configure_notify", args!(id, r, border), res)
+    }
+
+    fn mark_new_window(&self, id: WinId) -> Result<()> {
+        self.record("mark_new_window", args!(id), self.inner.mark_new_window(id))
+    }
+
+    fn map_window(&self, id: WinId) -> Result<()> {
+        self.record("map_window", args!(id), self.inner.map_window(id))
+    }
+
+    fn unmap_window(&self, id: WinId) -> Result<()> {
+        self.record("unmap_window", args!(id), self.inner.unmap_window(id))
+    }
+
+    fn send_client_event(&self, id: WinId, atom_name: &str) -> Result<()> {
+        let res = self.inner.send_client_event(id, atom_name);
+        self.record("send_client_event", args!(id, atom_name), res)
+    }
+
+    fn send_key(&self, id: WinId, key: KeyCode) -> Result<()> {
+        self.record("send_key", args!(id, key), self.inner.send_key(id, key))
+    }
+
+    fn window_supports_protocol(&self, id: WinId, protocol: &str) -> Result<bool> {
+        let res = self.inner.window_supports_protocol(id, protocol);
+        self.record("window_supports_protocol", args!(id, protocol), res)
+    }
+
+    fn kill_client(&self, id: WinId) -> Result<()> {
+        self.record("kill_client", args!(id), self.inner.kill_client(id))
+    }
+
+    fn focused_client(&self) -> Result<WinId> {
+        self.record("focused_client", args!(), self.inner.focused_client())
+    }
+
+    fn active_window(&self) -> Result<WinId> {
+        self.record("active_window", args!(), self.inner.active_window())
+    }
+
+    fn focus_client(&self, id: WinId) -> Result<()> {
+        self.record("focus_client", args!(id), self.inner.focus_client(id))
+    }
+
+    fn focus_model(&self, id: WinId) -> Result<FocusModel> {
+        self.record("focus_model", args!(id), self.inner.focus_model(id))
+    }
+
+    fn set_client_border_color(&self, id: WinId, color: u32) -> Result<()> {
+        let res = self.inner.set_client_border_color(id, color);
+        self.record("set_client_border_color", args!(id, color), res)
+    }
+
+    fn grab_keys(&self, key_bindings: &KeyBindings, mouse_bindings: &MouseBindings) -> Result<()> {
+        let res = self.inner.grab_keys(key_bindings, mouse_bindings);
+        let (codes, states) = sorted_bindings(key_bindings, mouse_bindings);
+        self.record("grab_keys", args!(codes, states), res)
+    }
+
+    fn keyboard_mapping(&self) -> Result<KeyboardMapping> {
+        self.record("keyboard_mapping", args!(), self.inner.keyboard_mapping())
+    }
+
+    fn grab_pointer(&self) -> Result<()> {
+        self.record("grab_pointer", args!(), self.inner.grab_pointer())
+    }
+
+    fn ungrab_pointer(&self) -> Result<()> {
+        self.record("ungrab_pointer", args!(), self.inner.ungrab_pointer())
+    }
+
+    fn grab_keyboard(&self) -> Result<()> {
+        self.record("grab_keyboard", args!(), self.inner.grab_keyboard())
+    }
+
+    fn ungrab_keyboard(&self) -> Result<()> {
+        self.record("ungrab_keyboard", args!(), self.inner.ungrab_keyboard())
+    }
+
+    fn set_wm_properties(&self, workspaces: &[&str]) -> Result<()> {
+        let res = self.inner.set_wm_properties(workspaces);
+        self.record("set_wm_properties", args!(workspaces), res)
+    }
+
+    fn update_desktops(&self, workspaces: &[&str]) -> Result<()> {
+        let res = self.inner.update_desktops(workspaces);
+        self.record("update_desktops", args!(workspaces), res)
+    }
+
+    fn set_current_workspace(&self, wix: usize) -> Result<()> {
+        let res = self.inner.set_current_workspace(wix);
+        self.record("set_current_workspace", args!(wix), res)
+    }
+
+    fn update_client_list(&self, clients: &[WinId], stacking: &[WinId]) -> Result<()> {
+        let res = self.inner.update_client_list(clients, stacking);
+        self.record("update_client_list", args!(clients, stacking), res)
+    }
+
+    fn set_root_window_name(&self, name: &str) -> Result<()> {
+        let res = self.inner.set_root_window_name(name);
+        self.record("set_root_window_name", args!(name), res)
+    }
+
+    fn set_client_workspace(&self, id: WinId, wix: usize) -> Result<()> {
+        let res = self.inner.set_client_workspace(id, wix);
+        self.record("set_client_workspace", args!(id, wix), res)
+    }
+
+    fn set_client_state(&self, id: WinId, state: WindowState) -> Result<()> {
+        let res = self.inner.set_client_state(id, state);
+        self.record("set_client_state", args!(id, state), res)
+    }
+
+    fn toggle_client_fullscreen(&self, id: WinId, client_is_fullscreen: bool) -> Result<()> {
+        let res = self
+            .inner
+            .toggle_client_fullscreen(id, client_is_fullscreen);
+        self.record(
+            "toggle_client_fullscreen",
+            args!(id, client_is_fullscreen),
+            res,
+        )
+    }
+
+    fn window_should_float(&self, id: WinId, floating_classes: &[&str]) -> Result<bool> {
+        let res = self.inner.window_should_float(id, floating_classes);
+        self.record("window_should_float", args!(id, floating_classes), res)
+    }
+
+    fn window_is_dock(&self, id: WinId) -> Result<bool> {
+        self.record("window_is_dock", args!(id), self.inner.window_is_dock(id))
+    }
+
+    fn window_strut(&self, id: WinId) -> Result<Strut> {
+        self.record("window_strut", args!(id), self.inner.window_strut(id))
+    }
+
+    fn window_is_urgent(&self, id: WinId) -> Result<bool> {
+        self.record(
+            "window_is_urgent",
+            args!(id),
+            self.inner.window_is_urgent(id),
+        )
+    }
+
+    fn window_geometry(&self, id: WinId) -> Result<Region> {
+        self.record("window_geometry", args!(id), self.inner.window_geometry(id))
+    }
+
+    fn size_hints(&self, id: WinId) -> Result<SizeHints> {
+        self.record("size_hints", args!(id), self.inner.size_hints(id))
+    }
+
+    fn warp_cursor(&self, win_id: Option<WinId>, screen: &Screen) -> Result<()> {
+        let res = self.inner.warp_cursor(win_id, screen);
+        self.record("warp_cursor", args!(win_id, screen), res)
+    }
+
+    fn query_for_active_windows(&self) -> Result<Vec<WinId>> {
+        let res = self.inner.query_for_active_windows();
+        self.record("query_for_active_windows", args!(), res)
+    }
+
+    fn str_prop(&self, id: u32, name: &str) -> Result<String> {
+        self.record("str_prop", args!(id, name), self.inner.str_prop(id, name))
+    }
+
+    fn atom_prop(&self, id: u32, name: &str) -> Result<u32> {
+        self.record("atom_prop", args!(id, name), self.inner.atom_prop(id, name))
+    }
+
+    fn intern_atom(&self, atom: &str) -> Result<u32> {
+        self.record("intern_atom", args!(atom), self.inner.intern_atom(atom))
+    }
+
+    fn atom_name(&self, atom: u32) -> Result<String> {
+        self.record("atom_name", args!(atom), self.inner.atom_name(atom))
+    }
+
+    fn cleanup(&self) -> Result<()> {
+        self.record("cleanup", args!(), self.inner.cleanup())
+    }
+}
+
+// A single line from a recording
+#[derive(Debug)]
+enum Entry {
+    Event(XEvent),
+    Timeout,
+    Call { call: String, reply: Vec<String> },
+}
+
+impl Entry {
+    fn parse(line: &str) -> Result<Option<Self>> {
+        let mut tokens = tokenize(line)?;
+        if tokens.is_empty() || tokens[0].starts_with('#') {
+            return Ok(None);
+        }
+
+        match tokens.remove(0).as_ref() {
+            "event" => {
+                let mut tokens = Tokens::new(tokens);
+                let event = tokens.decode()?;
+                tokens.finish()?;
+                Ok(Some(Entry::Event(event)))
+            }
+            "timeout" if tokens.is_empty() => Ok(Some(Entry::Timeout)),
+            "call" => {
+                let sep = tokens
+                    .iter()
+                    .position(|t| t == CALL_SEP)
+                    .ok_or_else(|| anyhow!("call is missing a result: '{}'", line))?;
+                let reply = tokens.split_off(sep + 1);
+                tokens.pop();
+                Ok(Some(Entry::Call {
+                    call: tokens.join(" "),
+                    reply,
+                }))
+            }
+            t => Err(anyhow!("unknown recording entry '{}'", t)),
+        }
+    }
+}
+
+/**
+ * An XConn that replays a recording made by a [RecordingXConn].
+ *
+ * Recorded events are returned from `wait_for_event` in order, and each call made by the
+ * WindowManager is checked against the calls that were recorded before the next event. Matching
+ * calls return the recorded result. Calls that are missing from the recording or that were
+ * recorded but never made are reported by [ReplayXConn::mismatches].
+ *
+ * # Panics
+ * The WindowManager has no way of knowing that the recording has ended so `wait_for_event` will
+ * panic if it is called once all recorded events have been replayed. Recordings of sessions that
+ * were exited normally end with the key press that triggered the exit and will not panic.
+ */
+pub struct ReplayXConn {
+    entries: RefCell<VecDeque<Entry>>,
+    mismatches: RefCell<Vec<String>>,
+}
+
+impl ReplayXConn {
+    /// Load a recording from a file on disk.
+    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Parse a recording that has already been read into memory.
+    pub fn parse(recording: &str) -> Result<Self> {
+        let mut entries = VecDeque::new();
+        for (n, line) in recording.lines().enumerate() {
+            match Entry::parse(line) {
+                Ok(Some(entry)) => entries.push_back(entry),
+                Ok(None) => (),
+                Err(e) => return Err(anyhow!("invalid recording at line {}: {}", n + 1, e)),
+            }
+        }
+
+        Ok(Self {
+            entries: RefCell::new(entries),
+            mismatches: RefCell::new(vec![]),
+        })
+    }
+
+    /// The differences between the calls made so far and the calls in the recording.
+    pub fn mismatches(&self) -> Vec<String> {
+        self.mismatches.borrow().clone()
+    }
+
+    /// Error if the calls made so far did not match the recording.
+    pub fn check(&self) -> Result<()> {
+        let mismatches = self.mismatches.borrow();
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "replay did not match the recording:\n{}",
+                mismatches.join("\n")
+            ))
+        }
+    }
+
+    // Calls that were recorded before the next event are the only ones that can match
+    fn replay<T: Reply>(&self, name: &str, mut args: Vec<String>) -> T {
+        args.insert(0, name.into());
+        let call = args.join(" ");
+        let mut entries = self.entries.borrow_mut();
+
+        let pending = entries
+            .iter()
+            .take_while(|e| matches!(e, Entry::Call { .. }))
+            .count();
+        let found = entries.iter().take(pending).position(|e| match e {
+            Entry::Call { call: c, .. } => c == &call,
+            _ => false,
+        });
+
+        let ix = match found {
+            Some(ix) => ix,
+            None => {
+                self.mismatches
+                    .borrow_mut()
+                    .push(format!("unexpected call: {}", call));
+                return T::missing(&call);
+            }
+        };
+
+        for skipped in entries.drain(..ix) {
+            self.missing_call(skipped);
+        }
+        match entries.pop_front() {
+            Some(Entry::Call { reply, .. }) => {
+                let mut tokens = Tokens::new(reply);
+                match tokens.decode().and_then(|r| tokens.finish().map(|_| r)) {
+                    Ok(r) => r,
+                    Err(e) => {
+                        self.mismatches
+                            .borrow_mut()
+                            .push(format!("invalid reply for {}: {}", call, e));
+                        T::missing(&call)
+                    }
+                }
+            }
+            _ => unreachable!("a matching call was found above"),
+        }
+    }
+
+    fn missing_call(&self, entry: Entry) {
+        if let Entry::Call { call, .. } = entry {
+            self.mismatches
+                .borrow_mut()
+                .push(format!("missing call: {}", call));
+        }
+    }
+}
+
+impl XConn for ReplayXConn {
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn wait_for_event(&self) -> Option<XEvent> {
+        let mut entries = self.entries.borrow_mut();
+        loop {
+            match entries.pop_front() {
+                Some(Entry::Event(e)) => return Some(e),
+                Some(Entry::Timeout) => self
+                    .mismatches
+                    .borrow_mut()
+                    .push("missing wait for event with a timeout".into()),
+                Some(call) => self.missing_call(call),
+                None => panic!("the recording ended before the window manager exited"),
+            }
+        }
+    }
+
+    fn wait_for_event_timeout(&self, _: Duration) -> Option<XEvent> {
+        let mut entries = self.entries.borrow_mut();
+        loop {
+            match entries.pop_front() {
+                Some(Entry::Event(e)) => return Some(e),
+                Some(Entry::Timeout) => return None,
+                Some(call) => self.missing_call(call),
+                None => panic!("the recording ended before the window manager exited"),
+            }
+        }
+    }
+
+    fn current_outputs(&self) -> Result<Vec<Screen>> {
+        self.replay("current_outputs", args!())
+    }
+
+    fn cursor_position(&self) -> Result<Point> {
+        self.replay("cursor_position", args!())
+    }
+
+    fn position_window(&self, id: WinId, r: Region, border: u32, stack_above: bool) -> Result<()> {
+        self.replay("position_window", args!(id, r, border, stack_above))
+    }
+
+    fn raise_window(&self, id: WinId) -> Result<()> {
+        self.replay("raise_window", args!(id))
+    }
+
+    fn send_configure_notify(&self, id: WinId, r: Region, border: u32) -> Result<()> {
+        self.replay("send_configure_notify", args!(id, r, border))
+    }
+
+    fn mark_new_window(&self, id: WinId) -> Result<()> {
+        self.replay("mark_new_window", args!(id))
+    }
+
+    fn map_window(&self, id: WinId) -> Result<()> {
+        self.replay("map_window", args!(id))
+    }
+
+    fn unmap_window(&self, id: WinId) -> Result<()> {
+        self.replay("unmap_window", args!(id))
+    }
+
+    fn send_client_event(&self, id: WinId, atom_name: &str) -> Result<()> {
+        self.replay("send_client_event", args!(id, atom_name))
+    }
+
+    fn send_key(&self, id: WinId, key: KeyCode) -> Result<()> {
+        self.replay("send_key", args!(id, key))
+    }
+
+    fn window_supports_protocol(&self, id: WinId, protocol: &str) -> Result<bool> {
+        self.replay("window_supports_protocol", args!(id, protocol))
+    }
+
+    fn kill_client(&self, id: WinId) -> Result<()> {
+        self.replay("kill_client", args!(id))
+    }
+
+    fn focused_client(&self) -> Result<WinId> {
+        self.replay("focused_client", args!())
+    }
+
+    fn active_window(&self) -> Result<WinId> {
+        self.replay("active_window", args!())
+    }
+
+    fn focus_client(&self, id: WinId) -> Result<()> {
+        self.replay("focus_client", args!(id))
+    }
+
+    fn focus_model(&self, id: WinId) -> Result<FocusModel> {
+        self.replay("focus_model", args!(id))
+    }
+
+    fn set_client_border_color(&self, id: WinId, color: u32) -> Result<()> {
+        self.replay("set_client_border_color", args!(id, color))
+    }
+
+    fn grab_keys(&self, key_bindings: &KeyBindings, mouse_bindings: &MouseBindings) -> Result<()> {
+        let (codes, states) = sorted_bindings(key_bindings, mouse_bindings);
+        self.replay("grab_keys", args!(codes, states))
+    }
+
+    fn keyboard_mapping(&self) -> Result<KeyboardMapping> {
+        self.replay("keyboard_mapping", args!())
+    }
+
+    fn grab_pointer(&self) -> Result<()> {
+        self.replay("grab_pointer", args!())
+    }
+
+    fn ungrab_pointer(&self) -> Result<()> {
+        self.replay("ungrab_pointer", args!())
+    }
+
+    fn grab_keyboard(&self) -> Result<()> {
+        self.replay("grab_keyboard", args!())
+    }
+
+    fn ungrab_keyboard(&self) -> Result<()> {
+        self.replay("ungrab_keyboard", args!())
+    }
+
+    fn set_wm_properties(&self, workspaces: &[&str]) -> Result<()> {
+        self.replay("set_wm_properties", args!(workspaces))
+    }
+
+    fn update_desktops(&self, workspaces: &[&str]) -> Result<()> {
+        self.replay("update_desktops", args!(workspaces))
+    }
+
+    fn set_current_workspace(&self, wix: usize) -> Result<()> {
+        self.replay("set_current_workspace", args!(wix))
+    }
+
+    fn update_client_list(&self, clients: &[WinId], stacking: &[WinId]) -> Result<()> {
+        self.replay("update_client_list", args!(clients, stacking))
+    }
+
+    fn set_root_window_name(&self, name: &str) -> Result<()> {
+        self.replay("set_root_window_name", args!(name))
+    }
+
+    fn set_client_workspace(&self, id: WinId, wix: usize) -> Result<()> {
+        self.replay("set_client_workspace", args!(id, wix))
+    }
+
+    fn set_client_state(&self, id: WinId, state: WindowState) -> Result<()> {
+        self.replay("set_client_state", args!(id, state))
+    }
+
+    fn toggle_client_fullscreen(&self, id: WinId, client_is_fullscreen: bool) -> Result<()> {
+        self.replay("toggle_client_fullscreen", args!(id, client_is_fullscreen))
+    }
+
+    fn window_should_float(&self, id: WinId, floating_classes: &[&str]) -> Result<bool> {
+        self.replay("window_should_float", args!(id, floating_classes))
+    }
+
+    fn window_is_dock(&self, id: WinId) -> Result<bool> {
+        self.replay("window_is_dock", args!(id))
+    }
+
+    fn window_strut(&self, id: WinId) -> Result<Strut> {
+        self.replay("window_strut", args!(id))
+    }
+
+    fn window_is_urgent(&self, id: WinId) -> Result<bool> {
+        self.replay("window_is_urgent", args!(id))
+    }
+
+    fn window_geometry(&self, id: WinId) -> Result<Region> {
+        self.replay("window_geometry", args!(id))
+    }
+
+    fn size_hints(&self, id: WinId) -> Result<SizeHints> {
+        self.replay("size_hints", args!(id))
+    }
+
+    fn warp_cursor(&self, win_id: Option<WinId>, screen: &Screen) -> Result<()> {
+        self.replay("warp_cursor", args!(win_id, screen))
+    }
+
+    fn query_for_active_windows(&self) -> Result<Vec<WinId>> {
+        self.replay("query_for_active_windows", args!())
+    }
+
+    fn str_prop(&self, id: u32, name: &str) -> Result<String> {
+        self.replay("str_prop", args!(id, name))
+    }
+
+    fn atom_prop(&self, id: u32, name: &str) -> Result<u32> {
+        self.replay("atom_prop", args!(id, name))
+    }
+
+    fn intern_atom(&self, atom: &str) -> Result<u32> {
+        self.replay("intern_atom", args!(atom))
+    }
+
+    fn atom_name(&self, atom: u32) -> Result<String> {
+        self.replay("atom_name", args!(atom))
+    }
+
+    fn cleanup(&self) -> Result<()> {
+        self.replay("cleanup", args!())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{data_types::Config, xconnection::MockXConn, WindowManager};
+
+    use std::{collections::HashMap, rc::Rc};
+
+    // A writer that can be read back once the RecordingXConn is done with it
+    #[derive(Clone, Default)]
+    struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    const EXIT: KeyCode = KeyCode {
+        mask: 0,
+        code: 0,
+        release: false,
+    };
+
+    fn bindings() -> KeyBindings {
+        let mut bindings = HashMap::new();
+        bindings.insert(EXIT, run_internal!(exit).into());
+        bindings
+    }
+
+    fn record(events: Vec<XEvent>) -> String {
+        let buf = SharedBuf::default();
+        let screens = vec![Screen::new(Region::new(0, 0, 1000, 800), 0)];
+        let conn = MockXConn::new(screens, events);
+        let conn = RecordingXConn::with_writer(conn, Box::new(buf.clone())).unwrap();
+
+        let mut wm = WindowManager::init(Config::default(), &conn);
+        wm.grab_keys_and_run(bindings(), HashMap::new());
+        drop(wm);
+        conn.into_inner();
+
+        let bytes = buf.0.borrow().clone();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    fn test_events() -> Vec<XEvent> {
+        vec![
+            XEvent::MapRequest {
+                id: 1,
+                ignore: false,
+            },
+            XEvent::MapRequest {
+                id: 2,
+                ignore: false,
+            },
+            XEvent::PropertyNotify {
+                id: 2,
+                atom: 1,
+                is_root: false,
+            },
+            XEvent::Destroy { id: 1 },
+            XEvent::KeyPress(EXIT),
+        ]
+    }
+
+    #[test]
+    fn events_round_trip() {
+        let events = vec![
+            XEvent::ClientMessage {
+                id: 3,
+                dtype: 7,
+                data: vec![1, 2, 3],
+            },
+            XEvent::ConfigureRequest {
+                id: 4,
+                r: Region::new(1, 2, 3, 4),
+                border: 2,
+            },
+            XEvent::Error(XError {
+                kind: XErrorKind::Window,
+                resource_id: 5,
+                major_code: 12,
+                minor_code: 0,
+                sequence: 42,
+            }),
+            XEvent::KeyPress(KeyCode {
+                mask: 4,
+                code: 38,
+                release: true,
+            }),
+        ];
+
+        for e in events {
+            let mut tokens = Vec::new();
+            e.encode(&mut tokens);
+            let decoded: XEvent = Tokens::new(tokens).decode().unwrap();
+            assert_eq!(format!("{:?}", decoded), format!("{:?}", e));
+        }
+    }
+
+    #[test]
+    fn strings_round_trip() {
+        let s = "class\0Class with \"quotes\" \\ and\nnewlines";
+        let mut tokens = Vec::new();
+        s.encode(&mut tokens);
+        let line = tokens.join(" ");
+
+        let decoded: String = Tokens::new(tokenize(&line).unwrap()).decode().unwrap();
+        assert_eq!(decoded, s);
+    }
+
+    #[test]
+    fn presses_and_releases_of_a_key_are_sorted() {
+        let release = KeyCode {
+            release: true,
+            ..EXIT
+        };
+
+        // each map has its own random iteration order
+        for _ in 0..10 {
+            let mut keys = bindings();
+            keys.insert(release, run_internal!(exit).into());
+            let (codes, _) = sorted_bindings(&keys, &HashMap::new());
+            assert_eq!(codes, vec![EXIT, release]);
+        }
+    }
+
+    #[test]
+    fn replaying_a_recording_matches() {
+        let recording = record(test_events());
+        let conn = ReplayXConn::parse(&recording).unwrap();
+
+        let mut wm = WindowManager::init(Config::default(), &conn);
+        wm.grab_keys_and_run(bindings(), HashMap::new());
+
+        assert!(conn.check().is_ok(), "{:?}", conn.mismatches());
+    }
+
+    #[test]
+    fn replay_reports_calls_that_differ() {
+        let recording = record(test_events());
+        let conn = ReplayXConn::parse(&recording.replace("map_window 2", "map_window 3")).unwrap();
+
+        let mut wm = WindowManager::init(Config::default(), &conn);
+        wm.grab_keys_and_run(bindings(), HashMap::new());
+
+        assert_eq!(
+            conn.mismatches(),
+            vec![
+                "unexpected call: map_window 2",
+                "missing call: map_window 3"
+            ]
+        );
+    }
+}
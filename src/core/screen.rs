@@ -1,8 +1,11 @@
 //! Information on connected displays
-use crate::data_types::{Point, Region};
+use crate::data_types::{Point, Region, Strut};
 
+use std::cmp;
+#[cfg(feature = "xcb")]
 use xcb::{base::Reply, ffi::randr::xcb_randr_get_crtc_info_reply_t};
 
+#[cfg(feature = "xcb")]
 type CRTCInfoReply = Reply<xcb_randr_get_crtc_info_reply_t>;
 
 /// Display information for a connected screen
@@ -25,6 +28,7 @@ impl Screen {
     }
 
     /// Create a new Screen from information obtained from the X server
+    #[cfg(feature = "xcb")]
     pub fn from_crtc_info_reply(r: CRTCInfoReply, wix: usize) -> Screen {
         let region = Region::new(
             r.x() as u32,
@@ -51,6 +55,39 @@ impl Screen {
         }
     }
 
+    /**
+     * Shrink the cached effective region of this screen to avoid the space reserved by dock
+     * windows. Struts are relative to the edges of the root window so `root` should be the
+     * (w, h) of the root window. Call after `update_effective_region`.
+     */
+    pub fn reserve_struts(&mut self, struts: &[Strut], root: (u32, u32)) {
+        let (sx, sy, sw, sh) = self.true_region.values();
+        let (x, y, w, h) = self.effective_region.values();
+        let (mut l, mut t, mut r, mut b) = (x, y, x + w, y + h);
+        let (rw, rh) = root;
+        let overlaps = |(start, end): (u32, u32), lo: u32, len: u32| start < lo + len && end >= lo;
+
+        for s in struts {
+            if s.left > sx && overlaps(s.left_y, sy, sh) {
+                l = cmp::max(l, s.left);
+            }
+            if s.right > 0 && rw.saturating_sub(s.right) < sx + sw && overlaps(s.right_y, sy, sh) {
+                r = cmp::min(r, rw.saturating_sub(s.right));
+            }
+            if s.top > sy && overlaps(s.top_x, sx, sw) {
+                t = cmp::max(t, s.top);
+            }
+            if s.bottom > 0 && rh.saturating_sub(s.bottom) < sy + sh && overlaps(s.bottom_x, sx, sw)
+            {
+                b = cmp::min(b, rh.saturating_sub(s.bottom));
+            }
+        }
+
+        if l < r && t < b {
+            self.effective_region = Region::new(l, t, r - l, b - t);
+        }
+    }
+
     /// The available space for displaying clients on this screen. If 'effective_only' then the
     /// returned Region will account for space taken up by a bar.
     pub fn region(&self, effective_only: bool) -> Region {
@@ -70,3 +107,34 @@ impl Screen {
         p.x >= x1 && p.x < x2 && p.y >= y1 && p.y < y2
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn struts_only_reserve_space_on_screens_they_overlap() {
+        let mut left = Screen::new(Region::new(0, 0, 1000, 800), 0);
+        let mut right = Screen::new(Region::new(1000, 0, 1000, 800), 1);
+        let struts = [
+            Strut {
+                top: 20,
+                top_x: (0, 999),
+                ..Strut::default()
+            },
+            Strut {
+                right: 50,
+                right_y: (0, 799),
+                ..Strut::default()
+            },
+        ];
+
+        for s in [&mut left, &mut right].iter_mut() {
+            s.update_effective_region(0, true);
+            s.reserve_struts(&struts, (2000, 800));
+        }
+
+        assert_eq!(left.region(true), Region::new(0, 20, 1000, 780));
+        assert_eq!(right.region(true), Region::new(1000, 0, 950, 800));
+    }
+}
@@ -0,0 +1,889 @@
+/*! Utilities for testing penrose configurations without a running X server
+ *
+ *  [SimulatedXConn] is an [XConn] that keeps an in memory model of the windows it knows about:
+ *  their geometry, whether or not they are mapped, their stacking order, their properties and
+ *  which window currently has input focus. Requests from the WindowManager update that model and
+ *  generate the events that a real X server would send back, so layouts, hooks and key binding
+ *  actions can be tested end to end and the results checked using the assertion helpers
+ *  ([SimulatedXConn::is_mapped], [SimulatedXConn::geometry], [SimulatedXConn::focused], ...).
+ *
+ *  ```
+ *  # #[macro_use] extern crate penrose;
+ *  # use penrose::{
+ *  #     bindings::KeyCode, data_types::Region, screen::Screen, testing::SimulatedXConn,
+ *  #     xconnection::XEvent, Config, WindowManager,
+ *  # };
+ *  # use std::collections::HashMap;
+ *  let exit = KeyCode { mask: 0, code: 9, release: false };
+ *  let screens = vec![Screen::new(Region::new(0, 0, 1920, 1080), 0)];
+ *  let events = vec![
+ *      XEvent::MapRequest { id: 1, ignore: false },
+ *      XEvent::KeyPress(exit),
+ *  ];
+ *
+ *  let conn = SimulatedXConn::new(screens, events);
+ *  conn.add_window(1, Region::new(0, 0, 200, 100));
+ *
+ *  let mut bindings = HashMap::new();
+ *  bindings.insert(exit, run_internal!(exit).into());
+ *  let mut wm = WindowManager::init(Config::default(), &conn);
+ *  wm.grab_keys_and_run(bindings, HashMap::new());
+ *
+ *  assert!(conn.is_mapped(1));
+ *  assert_eq!(conn.focused(), Some(1));
+ *  ```
+ */
+use crate::{
+    bindings::{KeyBindings, KeyCode, KeyboardMapping, MouseBindings},
+    data_types::{FocusModel, Point, Region, SizeHints, Strut, WinId, WindowState},
+    screen::Screen,
+    xconnection::{
+        Atom, AtomCache, XConn, XError, XErrorKind, XEvent, AUTO_FLOAT_WINDOW_TYPES,
+        UNMANAGED_WINDOW_TYPES, WM_NAME,
+    },
+    Result,
+};
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use strum::*;
+
+/// The ID of the root window of a [SimulatedXConn]
+pub const ROOT: WinId = 0x100;
+
+// Major opcodes of the core protocol requests that can fail with BadWindow
+const CHANGE_WINDOW_ATTRIBUTES: u8 = 2;
+const MAP_WINDOW: u8 = 8;
+const UNMAP_WINDOW: u8 = 10;
+const CONFIGURE_WINDOW: u8 = 12;
+const GET_GEOMETRY: u8 = 14;
+const CHANGE_PROPERTY: u8 = 18;
+const GET_PROPERTY: u8 = 20;
+const SEND_EVENT: u8 = 25;
+const WARP_POINTER: u8 = 41;
+const SET_INPUT_FOCUS: u8 = 42;
+const KILL_CLIENT: u8 = 113;
+
+fn bad_window(id: WinId, major_code: u8) -> anyhow::Error {
+    anyhow!(XError {
+        kind: XErrorKind::Window,
+        resource_id: id,
+        major_code,
+        minor_code: 0,
+        sequence: 0,
+    })
+}
+
+fn split_nul(s: &str) -> impl Iterator<Item = &str> {
+    s.split('\0').filter(|s| !s.is_empty())
+}
+
+#[derive(Debug, Clone)]
+struct SimWindow {
+    geometry: Region,
+    border: u32,
+    border_color: u32,
+    mapped: bool,
+    state: Option<WindowState>,
+    fullscreen: bool,
+    accepts_input: bool,
+    urgent: bool,
+    strut: Option<Strut>,
+    size_hints: SizeHints,
+    configure_notify: Option<Region>,
+    str_props: HashMap<String, String>,
+    u32_props: HashMap<String, Vec<u32>>,
+}
+
+impl SimWindow {
+    fn new(geometry: Region) -> Self {
+        Self {
+            geometry,
+            border: 0,
+            border_color: 0,
+            mapped: false,
+            state: None,
+            fullscreen: false,
+            accepts_input: true,
+            urgent: false,
+            strut: None,
+            size_hints: SizeHints::default(),
+            configure_notify: None,
+            str_props: HashMap::new(),
+            u32_props: HashMap::new(),
+        }
+    }
+
+    fn has_str_in(&self, prop: &str, values: &[&str]) -> bool {
+        match self.str_props.get(prop) {
+            Some(s) => split_nul(s).any(|v| values.contains(&v)),
+            None => false,
+        }
+    }
+}
+
+/**
+ * A simulated X server for testing.
+ *
+ * Windows need to be created using [SimulatedXConn::add_window] before any events referring to
+ * them are processed, as a client would create its window before asking for it to be mapped.
+ * Events are returned from `wait_for_event` in the order they were given, except that events
+ * generated in response to a request (such as the UnmapNotify that follows unmapping a window)
+ * are returned first.
+ *
+ * Requests that reference a window that does not exist fail with a BadWindow [XError] in the
+ * same way that they would for a real X server.
+ *
+ * As with [MockXConn][crate::xconnection::MockXConn], the WindowManager will block forever
+ * waiting for more events if the event stream runs out before it exits, so event streams should
+ * end with a key press that is bound to [exit][crate::WindowManager::exit].
+ */
+pub struct SimulatedXConn {
+    screens: Vec<Screen>,
+    events: RefCell<VecDeque<XEvent>>,
+    generated: RefCell<VecDeque<XEvent>>,
+    windows: RefCell<HashMap<WinId, SimWindow>>,
+    stack: RefCell<Vec<WinId>>,
+    focused: Cell<WinId>,
+    cursor: Cell<Point>,
+    keyboard_mapping: RefCell<KeyboardMapping>,
+    grabbed_keys: RefCell<Vec<KeyCode>>,
+    keyboard_grabbed: Cell<bool>,
+    sent_keys: RefCell<Vec<(WinId, KeyCode)>>,
+    atoms: AtomCache,
+}
+
+impl SimulatedXConn {
+    /**
+     * Set up a new SimulatedXConn with pre-defined Screens and an event stream to pull from.
+     *
+     * The only window that exists initially is the root window which covers all of the
+     * screens. Atoms are given the same IDs as they would be by a
+     * [MockXConn][crate::xconnection::MockXConn].
+     */
+    pub fn new(screens: Vec<Screen>, events: Vec<XEvent>) -> Self {
+        let atoms = AtomCache::default();
+        for (i, atom) in Atom::iter().enumerate() {
+            atoms.insert(atom.as_ref(), i as u32 + 1);
+        }
+
+        let (w, h) = screens.iter().fold((0, 0), |(w, h), s| {
+            let (x, y, sw, sh) = s.region(false).values();
+            (w.max(x + sw), h.max(y + sh))
+        });
+        let mut root = SimWindow::new(Region::new(0, 0, w, h));
+        root.mapped = true;
+
+        let mut windows = HashMap::new();
+        windows.insert(ROOT, root);
+
+        Self {
+            screens,
+            events: RefCell::new(events.into()),
+            generated: RefCell::new(VecDeque::new()),
+            windows: RefCell::new(windows),
+            stack: RefCell::new(vec![]),
+            focused: Cell::new(ROOT),
+            cursor: Cell::new(Point::new(0, 0)),
+            keyboard_mapping: RefCell::new(KeyboardMapping::new()),
+            grabbed_keys: RefCell::new(vec![]),
+            keyboard_grabbed: Cell::new(false),
+            sent_keys: RefCell::new(vec![]),
+            atoms,
+        }
+    }
+
+    /// Add an event to the end of the event stream.
+    pub fn push_event(&self, event: XEvent) {
+        self.events.borrow_mut().push_back(event);
+    }
+
+    /**
+     * Replace the keyboard mapping, as if the user had switched keyboard layout.
+     *
+     * A MappingNotify event is generated so that the WindowManager picks up the change. The
+     * initial mapping should be set before the WindowManager starts.
+     */
+    pub fn set_keyboard_mapping(&self, mapping: KeyboardMapping) {
+        self.keyboard_mapping.replace(mapping);
+        self.generate(XEvent::MappingNotify);
+    }
+
+    /**
+     * Create a new unmapped window at the top of the stacking order.
+     *
+     * Windows that should already be visible when the WindowManager starts can be mapped by
+     * calling `map_window` directly.
+     *
+     * # Panics
+     * Panics if a window with this ID already exists.
+     */
+    pub fn add_window(&self, id: WinId, r: Region) {
+        let mut windows = self.windows.borrow_mut();
+        if windows.contains_key(&id) {
+            panic!("window {} already exists", id);
+        }
+        windows.insert(id, SimWindow::new(r));
+        self.stack.borrow_mut().push(id);
+    }
+
+    /**
+     * Set a string property on a window. Properties holding lists (such as WM_CLASS,
+     * WM_PROTOCOLS and _NET_WM_WINDOW_TYPE) are separated with null bytes.
+     *
+     * # Panics
+     * Panics if the window does not exist.
+     */
+    pub fn set_prop(&self, id: WinId, name: &str, value: &str) {
+        self.setup(id, |w| {
+            w.str_props.insert(name.into(), value.into());
+        })
+    }
+
+    /**
+     * Set a property holding 32bit values (cardinals, atoms or window IDs) on a window.
+     *
+     * # Panics
+     * Panics if the window does not exist.
+     */
+    pub fn set_u32_prop(&self, id: WinId, name: &str, values: &[u32]) {
+        self.setup(id, |w| {
+            w.u32_props.insert(name.into(), values.to_vec());
+        })
+    }
+
+    /**
+     * Set the ICCCM input hint for a window: windows accept input unless told otherwise.
+     *
+     * # Panics
+     * Panics if the window does not exist.
+     */
+    pub fn set_accepts_input(&self, id: WinId, accepts_input: bool) {
+        self.setup(id, |w| w.accepts_input = accepts_input)
+    }
+
+    /**
+     * Set the urgency hint for a window.
+     *
+     * # Panics
+     * Panics if the window does not exist.
+     */
+    pub fn set_urgent(&self, id: WinId, urgent: bool) {
+        self.setup(id, |w| w.urgent = urgent)
+    }
+
+    /**
+     * Set the space reserved by a window that is acting as a dock.
+     *
+     * # Panics
+     * Panics if the window does not exist.
+     */
+    pub fn set_strut(&self, id: WinId, strut: Strut) {
+        self.setup(id, |w| w.strut = Some(strut))
+    }
+
+    /**
+     * Set the WM_NORMAL_HINTS of a window.
+     *
+     * # Panics
+     * Panics if the window does not exist.
+     */
+    pub fn set_size_hints(&self, id: WinId, hints: SizeHints) {
+        self.setup(id, |w| w.size_hints = hints)
+    }
+
+    /// Does a window with this ID currently exist?
+    pub fn exists(&self, id: WinId) -> bool {
+        self.windows.borrow().contains_key(&id)
+    }
+
+    /// Is the given window currently mapped? Windows that do not exist are never mapped.
+    pub fn is_mapped(&self, id: WinId) -> bool {
+        self.query(id, |w| w.mapped).unwrap_or(false)
+    }
+
+    /// The current position and size of a window, not including its border.
+    pub fn geometry(&self, id: WinId) -> Option<Region> {
+        self.query(id, |w| w.geometry)
+    }
+
+    /// The region from the last synthetic ConfigureNotify sent to a window.
+    pub fn configure_notify(&self, id: WinId) -> Option<Region> {
+        self.query(id, |w| w.configure_notify).flatten()
+    }
+
+    /// The current border width of a window.
+    pub fn border_width(&self, id: WinId) -> Option<u32> {
+        self.query(id, |w| w.border)
+    }
+
+    /// The current border color of a window.
+    pub fn border_color(&self, id: WinId) -> Option<u32> {
+        self.query(id, |w| w.border_color)
+    }
+
+    /// The ICCCM WM_STATE of a window, if it has been set.
+    pub fn window_state(&self, id: WinId) -> Option<WindowState> {
+        self.query(id, |w| w.state).flatten()
+    }
+
+    /// Has the window been made fullscreen?
+    pub fn is_fullscreen(&self, id: WinId) -> bool {
+        self.query(id, |w| w.fullscreen).unwrap_or(false)
+    }
+
+    /// The window that currently has input focus, or None if focus is on the root window.
+    pub fn focused(&self) -> Option<WinId> {
+        match self.focused.get() {
+            ROOT => None,
+            id => Some(id),
+        }
+    }
+
+    /// All windows other than the root window, ordered from bottom to top.
+    pub fn stacking_order(&self) -> Vec<WinId> {
+        self.stack.borrow().clone()
+    }
+
+    /// The current value of a string property on a window (the root window is [ROOT]).
+    pub fn prop(&self, id: WinId, name: &str) -> Option<String> {
+        self.query(id, |w| w.str_props.get(name).cloned()).flatten()
+    }
+
+    /// The current value of a 32bit property on a window (the root window is [ROOT]).
+    pub fn u32_prop(&self, id: WinId, name: &str) -> Option<Vec<u32>> {
+        self.query(id, |w| w.u32_props.get(name).cloned()).flatten()
+    }
+
+    /// The current position of the cursor.
+    pub fn cursor(&self) -> Point {
+        self.cursor.get()
+    }
+
+    /// Is this key (with these modifiers held) currently grabbed? Grabbing a key grabs both
+    /// its presses and its releases.
+    pub fn is_grabbed(&self, key: KeyCode) -> bool {
+        let key = KeyCode {
+            release: false,
+            ..key
+        };
+        self.grabbed_keys.borrow().contains(&key)
+    }
+
+    /// Is the whole keyboard currently grabbed?
+    pub fn is_keyboard_grabbed(&self) -> bool {
+        self.keyboard_grabbed.get()
+    }
+
+    /// The key presses and releases that have been passed through to this window.
+    pub fn sent_keys(&self, id: WinId) -> Vec<KeyCode> {
+        self.sent_keys
+            .borrow()
+            .iter()
+            .filter(|(w, _)| *w == id)
+            .map(|&(_, k)| k)
+            .collect()
+    }
+
+    fn setup(&self, id: WinId, f: impl FnOnce(&mut SimWindow)) {
+        match self.windows.borrow_mut().get_mut(&id) {
+            Some(w) => f(w),
+            None => panic!("unknown window {}", id),
+        }
+    }
+
+    fn query<T>(&self, id: WinId, f: impl FnOnce(&SimWindow) -> T) -> Option<T> {
+        self.windows.borrow().get(&id).map(f)
+    }
+
+    fn update<T>(&self, id: WinId, opcode: u8, f: impl FnOnce(&mut SimWindow) -> T) -> Result<T> {
+        self.windows
+            .borrow_mut()
+            .get_mut(&id)
+            .map(f)
+            .ok_or_else(|| bad_window(id, opcode))
+    }
+
+    fn set_root_str_prop(&self, name: &str, value: &str) {
+        self.setup(ROOT, |w| {
+            w.str_props.insert(name.into(), value.into());
+        })
+    }
+
+    fn set_root_u32_prop(&self, name: &str, values: &[u32]) {
+        self.setup(ROOT, |w| {
+            w.u32_props.insert(name.into(), values.to_vec());
+        })
+    }
+
+    fn generate(&self, event: XEvent) {
+        self.generated.borrow_mut().push_back(event);
+    }
+
+    // Focus reverts to the root window when the focused window is hidden or destroyed
+    fn revert_focus_from(&self, id: WinId) {
+        if self.focused.get() == id {
+            self.focused.set(ROOT);
+        }
+    }
+
+    fn set_input_focus(&self, id: WinId) -> Result<()> {
+        self.update(id, SET_INPUT_FOCUS, |_| ())?;
+        self.focused.set(id);
+        Ok(())
+    }
+
+    fn destroy_window(&self, id: WinId) {
+        let removed = self.windows.borrow_mut().remove(&id);
+        if let Some(w) = removed {
+            self.stack.borrow_mut().retain(|&s| s != id);
+            self.revert_focus_from(id);
+            if w.mapped {
+                self.generate(XEvent::UnmapNotify { id });
+            }
+            self.generate(XEvent::Destroy { id });
+        }
+    }
+
+    fn raise(&self, id: WinId) {
+        let mut stack = self.stack.borrow_mut();
+        stack.retain(|&s| s != id);
+        stack.push(id);
+    }
+}
+
+impl XConn for SimulatedXConn {
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn wait_for_event(&self) -> Option<XEvent> {
+        let generated = self.generated.borrow_mut().pop_front();
+        generated.or_else(|| self.events.borrow_mut().pop_front())
+    }
+
+    fn wait_for_event_timeout(&self, _: Duration) -> Option<XEvent> {
+        self.wait_for_event()
+    }
+
+    fn current_outputs(&self) -> Result<Vec<Screen>> {
+        Ok(self.screens.clone())
+    }
+
+    fn cursor_position(&self) -> Result<Point> {
+        Ok(self.cursor.get())
+    }
+
+    fn position_window(&self, id: WinId, r: Region, border: u32, stack_above: bool) -> Result<()> {
+        self.update(id, CONFIGURE_WINDOW, |w| {
+            w.geometry = r;
+            w.border = border;
+        })?;
+        if stack_above {
+            self.raise(id);
+        }
+        Ok(())
+    }
+
+    fn raise_window(&self, id: WinId) -> Result<()> {
+        self.update(id, CONFIGURE_WINDOW, |_| ())?;
+        self.raise(id);
+        Ok(())
+    }
+
+    fn send_configure_notify(&self, id: WinId, r: Region, _: u32) -> Result<()> {
+        self.update(id, SEND_EVENT, |w| w.configure_notify = Some(r))
+    }
+
+    fn mark_new_window(&self, id: WinId) -> Result<()> {
+        self.update(id, CHANGE_WINDOW_ATTRIBUTES, |_| ())
+    }
+
+    fn map_window(&self, id: WinId) -> Result<()> {
+        self.update(id, MAP_WINDOW, |w| w.mapped = true)
+    }
+
+    fn unmap_window(&self, id: WinId) -> Result<()> {
+        let was_mapped = self.update(id, UNMAP_WINDOW, |w| {
+            let was_mapped = w.mapped;
+            w.mapped = false;
+            was_mapped
+        })?;
+
+        if was_mapped {
+            self.revert_focus_from(id);
+            self.generate(XEvent::UnmapNotify { id });
+        }
+        Ok(())
+    }
+
+    // Simulated clients close their window when asked and take focus when offered it
+    fn send_client_event(&self, id: WinId, atom_name: &str) -> Result<()> {
+        self.update(id, SEND_EVENT, |_| ())?;
+        if !self.window_supports_protocol(id, atom_name)? {
+            return Ok(());
+        }
+
+        if atom_name == Atom::WmDeleteWindow.as_ref() {
+            self.destroy_window(id);
+        } else if atom_name == Atom::WmTakeFocus.as_ref() {
+            self.focused.set(id);
+        }
+        Ok(())
+    }
+
+    fn send_key(&self, id: WinId, key: KeyCode) -> Result<()> {
+        self.update(id, SEND_EVENT, |_| ())?;
+        self.sent_keys.borrow_mut().push((id, key));
+        Ok(())
+    }
+
+    fn window_supports_protocol(&self, id: WinId, protocol: &str) -> Result<bool> {
+        self.update(id, GET_PROPERTY, |w| {
+            w.has_str_in(Atom::WmProtocols.as_ref(), &[protocol])
+        })
+    }
+
+    fn kill_client(&self, id: WinId) -> Result<()> {
+        self.update(id, KILL_CLIENT, |_| ())?;
+        self.destroy_window(id);
+        Ok(())
+    }
+
+    fn focused_client(&self) -> Result<WinId> {
+        Ok(self.focused.get())
+    }
+
+    fn active_window(&self) -> Result<WinId> {
+        self.atom_prop(ROOT, Atom::NetActiveWindow.as_ref())
+    }
+
+    fn focus_client(&self, id: WinId) -> Result<()> {
+        let model = self.focus_model(id)?;
+        if matches!(model, FocusModel::Passive | FocusModel::LocallyActive) {
+            self.set_input_focus(id)?;
+        }
+        if matches!(
+            model,
+            FocusModel::LocallyActive | FocusModel::GloballyActive
+        ) {
+            self.send_client_event(id, Atom::WmTakeFocus.as_ref())?;
+        }
+
+        self.set_root_u32_prop(Atom::NetActiveWindow.as_ref(), &[id]);
+        Ok(())
+    }
+
+    fn focus_model(&self, id: WinId) -> Result<FocusModel> {
+        let accepts_input = self.update(id, GET_PROPERTY, |w| w.accepts_input)?;
+        let take_focus = self.window_supports_protocol(id, Atom::WmTakeFocus.as_ref())?;
+
+        Ok(match (accepts_input, take_focus) {
+            (false, false) => FocusModel::NoInput,
+            (true, false) => FocusModel::Passive,
+            (true, true) => FocusModel::LocallyActive,
+            (false, true) => FocusModel::GloballyActive,
+        })
+    }
+
+    fn set_client_border_color(&self, id: WinId, color: u32) -> Result<()> {
+        self.update(id, CHANGE_WINDOW_ATTRIBUTES, |w| w.border_color = color)
+    }
+
+    fn grab_keys(&self, key_bindings: &KeyBindings, _: &MouseBindings) -> Result<()> {
+        self.grabbed_keys.replace(
+            key_bindings
+                .keys()
+                .map(|&k| KeyCode {
+                    release: false,
+                    ..k
+                })
+                .collect(),
+        );
+        Ok(())
+    }
+
+    fn keyboard_mapping(&self) -> Result<KeyboardMapping> {
+        Ok(self.keyboard_mapping.borrow().clone())
+    }
+
+    fn grab_pointer(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn ungrab_pointer(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn grab_keyboard(&self) -> Result<()> {
+        self.keyboard_grabbed.set(true);
+        Ok(())
+    }
+
+    fn ungrab_keyboard(&self) -> Result<()> {
+        self.keyboard_grabbed.set(false);
+        Ok(())
+    }
+
+    fn set_wm_properties(&self, workspaces: &[&str]) -> Result<()> {
+        self.set_root_str_prop(Atom::NetWmName.as_ref(), WM_NAME);
+        self.update_desktops(workspaces)?;
+        self.update(ROOT, CHANGE_PROPERTY, |w| {
+            w.u32_props.remove(Atom::NetClientList.as_ref());
+            w.u32_props.remove(Atom::NetClientListStacking.as_ref());
+        })
+    }
+
+    fn update_desktops(&self, workspaces: &[&str]) -> Result<()> {
+        self.set_root_u32_prop(
+            Atom::NetNumberOfDesktops.as_ref(),
+            &[workspaces.len() as u32],
+        );
+        self.set_root_str_prop(Atom::NetDesktopNames.as_ref(), &workspaces.join("\0"));
+        Ok(())
+    }
+
+    fn set_current_workspace(&self, wix: usize) -> Result<()> {
+        self.set_root_u32_prop(Atom::NetCurrentDesktop.as_ref(), &[wix as u32]);
+        Ok(())
+    }
+
+    fn update_client_list(&self, clients: &[WinId], stacking: &[WinId]) -> Result<()> {
+        self.set_root_u32_prop(Atom::NetClientList.as_ref(), clients);
+        self.set_root_u32_prop(Atom::NetClientListStacking.as_ref(), stacking);
+        Ok(())
+    }
+
+    fn set_root_window_name(&self, name: &str) -> Result<()> {
+        self.set_root_str_prop(Atom::WmName.as_ref(), name);
+        Ok(())
+    }
+
+    fn set_client_workspace(&self, id: WinId, wix: usize) -> Result<()> {
+        self.update(id, CHANGE_PROPERTY, |w| {
+            w.u32_props
+                .insert(Atom::NetWmDesktop.as_ref().into(), vec![wix as u32]);
+        })
+    }
+
+    fn set_client_state(&self, id: WinId, state: WindowState) -> Result<()> {
+        self.update(id, CHANGE_PROPERTY, |w| w.state = Some(state))
+    }
+
+    fn toggle_client_fullscreen(&self, id: WinId, client_is_fullscreen: bool) -> Result<()> {
+        self.update(id, CHANGE_PROPERTY, |w| {
+            w.fullscreen = !client_is_fullscreen
+        })
+    }
+
+    fn window_should_float(&self, id: WinId, floating_classes: &[&str]) -> Result<bool> {
+        let auto_float_types: Vec<&str> =
+            AUTO_FLOAT_WINDOW_TYPES.iter().map(|t| t.as_ref()).collect();
+
+        self.update(id, GET_PROPERTY, |w| {
+            w.has_str_in(Atom::WmClass.as_ref(), floating_classes)
+                || w.has_str_in(Atom::NetWmWindowType.as_ref(), &auto_float_types)
+        })
+    }
+
+    fn window_is_dock(&self, id: WinId) -> Result<bool> {
+        let dock_types: Vec<&str> = UNMANAGED_WINDOW_TYPES.iter().map(|t| t.as_ref()).collect();
+
+        self.update(id, GET_PROPERTY, |w| {
+            w.has_str_in(Atom::NetWmWindowType.as_ref(), &dock_types)
+        })
+    }
+
+    fn window_strut(&self, id: WinId) -> Result<Strut> {
+        self.update(id, GET_PROPERTY, |w| w.strut)?
+            .ok_or_else(|| anyhow!("window {} has not set a strut", id))
+    }
+
+    fn window_is_urgent(&self, id: WinId) -> Result<bool> {
+        self.update(id, GET_PROPERTY, |w| w.urgent)
+    }
+
+    fn window_geometry(&self, id: WinId) -> Result<Region> {
+        self.update(id, GET_GEOMETRY, |w| w.geometry)
+    }
+
+    fn size_hints(&self, id: WinId) -> Result<SizeHints> {
+        self.update(id, GET_PROPERTY, |w| w.size_hints)
+    }
+
+    fn warp_cursor(&self, win_id: Option<WinId>, screen: &Screen) -> Result<()> {
+        let (x, y, w, h) = match win_id {
+            Some(id) => self.update(id, WARP_POINTER, |w| w.geometry)?.values(),
+            None => screen.region(true).values(),
+        };
+        self.cursor.set(Point::new(x + w / 2, y + h / 2));
+        Ok(())
+    }
+
+    fn query_for_active_windows(&self) -> Result<Vec<WinId>> {
+        Ok(self
+            .stack
+            .borrow()
+            .iter()
+            .filter(|&&id| self.is_mapped(id))
+            .copied()
+            .collect())
+    }
+
+    fn str_prop(&self, id: u32, name: &str) -> Result<String> {
+        self.update(id, GET_PROPERTY, |w| w.str_props.get(name).cloned())?
+            .ok_or_else(|| anyhow!("window {} has no property {}", id, name))
+    }
+
+    fn atom_prop(&self, id: u32, name: &str) -> Result<u32> {
+        self.update(id, GET_PROPERTY, |w| {
+            w.u32_props.get(name).and_then(|v| v.first().copied())
+        })?
+        .ok_or_else(|| anyhow!("window {} has no property {}", id, name))
+    }
+
+    fn intern_atom(&self, atom: &str) -> Result<u32> {
+        let next = self.atoms.len() as u32 + 1;
+        self.atoms.id_or_else(atom, || Ok(next))
+    }
+
+    fn atom_name(&self, atom: u32) -> Result<String> {
+        self.atoms
+            .name(atom)
+            .ok_or_else(|| anyhow!("unknown atom: {}", atom))
+    }
+
+    fn cleanup(&self) -> Result<()> {
+        self.update(ROOT, CHANGE_PROPERTY, |w| {
+            w.u32_props.remove(Atom::NetActiveWindow.as_ref());
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bindings::KeyCode, data_types::Config, Selector, WindowManager};
+
+    const EXIT: KeyCode = KeyCode {
+        mask: 0,
+        code: 0,
+        release: false,
+    };
+    const KILL: KeyCode = KeyCode {
+        mask: 0,
+        code: 1,
+        release: false,
+    };
+    const NEXT_WS: KeyCode = KeyCode {
+        mask: 0,
+        code: 2,
+        release: false,
+    };
+
+    fn run(conn: &SimulatedXConn) {
+        let mut bindings: KeyBindings = HashMap::new();
+        bindings.insert(EXIT, run_internal!(exit).into());
+        bindings.insert(KILL, run_internal!(kill_client).into());
+        bindings.insert(
+            NEXT_WS,
+            run_internal!(focus_workspace, &Selector::Index(1)).into(),
+        );
+
+        let mut wm = WindowManager::init(Config::default(), conn);
+        wm.grab_keys_and_run(bindings, HashMap::new());
+    }
+
+    fn conn_with_windows(n: WinId, events: Vec<XEvent>) -> SimulatedXConn {
+        let screens = vec![Screen::new(Region::new(0, 0, 1000, 800), 0)];
+        let mut all_events: Vec<XEvent> = (1..=n)
+            .map(|id| XEvent::MapRequest { id, ignore: false })
+            .collect();
+        all_events.extend(events);
+        all_events.push(XEvent::KeyPress(EXIT));
+
+        let conn = SimulatedXConn::new(screens, all_events);
+        (1..=n).for_each(|id| conn.add_window(id, Region::new(0, 0, 100, 100)));
+        conn
+    }
+
+    #[test]
+    fn new_clients_are_mapped_and_tiled() {
+        let conn = conn_with_windows(2, vec![]);
+        run(&conn);
+
+        assert!(conn.is_mapped(1));
+        assert!(conn.is_mapped(2));
+        assert_eq!(conn.window_state(2), Some(WindowState::Normal));
+        assert_eq!(conn.focused(), Some(2));
+        assert_ne!(conn.geometry(1), conn.geometry(2));
+        for id in 1..=2 {
+            let (x, y, w, h) = conn.geometry(id).unwrap().values();
+            assert!(x + w <= 1000 && y + h <= 800);
+        }
+        assert_eq!(conn.u32_prop(ROOT, "_NET_CLIENT_LIST"), Some(vec![1, 2]));
+    }
+
+    #[test]
+    fn switching_workspace_hides_clients() {
+        let conn = conn_with_windows(2, vec![XEvent::KeyPress(NEXT_WS)]);
+        run(&conn);
+
+        assert!(!conn.is_mapped(1));
+        assert!(!conn.is_mapped(2));
+        assert_eq!(conn.window_state(1), Some(WindowState::Iconic));
+        assert_eq!(conn.focused(), None);
+        assert_eq!(conn.u32_prop(ROOT, "_NET_CURRENT_DESKTOP"), Some(vec![1]));
+    }
+
+    #[test]
+    fn killed_clients_are_destroyed_and_removed() {
+        let conn = conn_with_windows(2, vec![XEvent::KeyPress(KILL)]);
+        run(&conn);
+
+        assert!(!conn.exists(2));
+        assert_eq!(conn.stacking_order(), vec![1]);
+        assert_eq!(conn.u32_prop(ROOT, "_NET_CLIENT_LIST"), Some(vec![1]));
+    }
+
+    #[test]
+    fn clients_supporting_delete_window_close_themselves() {
+        let conn = conn_with_windows(1, vec![XEvent::KeyPress(KILL)]);
+        conn.set_prop(1, "WM_PROTOCOLS", "WM_DELETE_WINDOW");
+        run(&conn);
+
+        assert!(!conn.exists(1));
+        assert_eq!(conn.u32_prop(ROOT, "_NET_CLIENT_LIST"), Some(vec![]));
+    }
+
+    #[test]
+    fn unmapping_generates_unmap_notify() {
+        let conn = SimulatedXConn::new(vec![], vec![]);
+        conn.add_window(1, Region::new(0, 0, 10, 10));
+        conn.map_window(1).unwrap();
+        conn.focus_client(1).unwrap();
+        conn.unmap_window(1).unwrap();
+
+        assert!(matches!(
+            conn.wait_for_event(),
+            Some(XEvent::UnmapNotify { id: 1 })
+        ));
+        assert_eq!(conn.focused(), None);
+    }
+
+    #[test]
+    fn requests_for_unknown_windows_are_bad_window_errors() {
+        let conn = SimulatedXConn::new(vec![], vec![]);
+        let err = conn.map_window(42).unwrap_err();
+        let x_err = err.downcast_ref::<XError>().unwrap();
+
+        assert_eq!(x_err.kind, XErrorKind::Window);
+        assert_eq!(x_err.resource_id, 42);
+        assert_eq!(x_err.major_code, MAP_WINDOW);
+    }
+}
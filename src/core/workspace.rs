@@ -106,26 +106,32 @@ impl Workspace {
     }
 
     /// Run the current layout function, generating a list of resize actions to be
-    /// applied byt the window manager.
+    /// applied byt the window manager. Floating clients are not passed to the layout
+    /// and a focused floating client is passed as no focus at all.
     pub(crate) fn arrange(
         &self,
         screen_region: Region,
         client_map: &HashMap<WinId, Client>,
     ) -> Vec<ResizeAction> {
-        if self.clients.len() > 0 {
+        let clients: Vec<&Client> = self
+            .clients
+            .iter()
+            .map(|id| client_map.get(id).unwrap())
+            .filter(|c| !c.floating)
+            .collect();
+
+        if !clients.is_empty() {
             let layout = self.layouts.focused().unwrap();
-            let clients: Vec<&Client> = self
-                .clients
-                .iter()
-                .map(|id| client_map.get(id).unwrap())
-                .collect();
             debug!(
                 "applying '{}' layout for {} clients on workspace '{}'",
                 layout.symbol,
-                self.clients.len(),
+                clients.len(),
                 self.name
             );
-            layout.arrange(&clients, self.focused_client(), &screen_region)
+            let focused = self
+                .focused_client()
+                .filter(|id| clients.iter().any(|c| c.id() == *id));
+            layout.arrange(&clients, focused, &screen_region)
         } else {
             vec![]
         }
@@ -283,6 +289,39 @@ mod tests {
         assert_eq!(actions.len(), 3, "actions are not 1-1 for clients")
     }
 
+    #[test]
+    fn applying_a_layout_skips_floating_clients() {
+        let mut ws = Workspace::new("test", test_layouts());
+        ws.clients = Ring::new(vec![1, 2, 3]);
+        let client_map = map! {
+            1 => Client::new(1, "".into(), "".into(), 1, false),
+            2 => Client::new(2, "".into(), "".into(), 1, true),
+            3 => Client::new(3, "".into(), "".into(), 1, false),
+        };
+        let actions = ws.arrange(Region::new(0, 0, 2000, 1000), &client_map);
+        let ids: Vec<WinId> = actions.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec![1, 3], "floating client was passed to the layout")
+    }
+
+    #[test]
+    fn a_focused_floating_client_is_not_passed_to_the_layout() {
+        let layouts = vec![Layout::new("m", LayoutConf::default(), monocle, 1, 0.6)];
+        let mut ws = Workspace::new("test", layouts);
+        ws.clients = Ring::new(vec![1, 2, 3]);
+        let client_map = map! {
+            1 => Client::new(1, "".into(), "".into(), 1, false),
+            2 => Client::new(2, "".into(), "".into(), 1, true),
+            3 => Client::new(3, "".into(), "".into(), 1, false),
+        };
+        let r = Region::new(0, 0, 2000, 1000);
+
+        ws.focus_client(2);
+        assert_eq!(ws.arrange(r, &client_map), vec![]);
+
+        ws.focus_client(3);
+        assert_eq!(ws.arrange(r, &client_map), vec![(1, None), (3, Some(r))]);
+    }
+
     #[test]
     fn dragging_a_client_forward() {
         let mut ws = Workspace::new("test", test_layouts());
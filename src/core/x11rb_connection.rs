@@ -0,0 +1,939 @@
+/*! API wrapper for talking to the X server using x11rb
+ *
+ *  [x11rb](https://github.com/psychon/x11rb) is a pure Rust implementation of the X11 protocol
+ *  that parses events into typed structs rather than requiring unsafe casts. The behaviour of
+ *  [X11rbConnection] matches that of [XcbConnection][crate::xconnection::XcbConnection] and
+ *  either can be used to drive the WindowManager.
+ *
+ *  This module is only available when the `x11rb` feature is enabled.
+ */
+use crate::{
+    bindings::{KeyBindings, KeyCode, KeyboardMapping, MouseBindings, MouseEvent, MouseEventKind},
+    data_types::{FocusModel, Point, Region, SizeHints, Strut, WinId, WindowState},
+    keysyms,
+    screen::Screen,
+    xconnection::{
+        wait_for_converted_event, Atom, AtomCache, XConn, XError, XErrorKind, XEvent,
+        AUTO_FLOAT_WINDOW_TYPES, UNMANAGED_WINDOW_TYPES, WM_NAME,
+    },
+    Result,
+};
+
+use std::{cmp, os::unix::io::AsRawFd, time::Duration};
+
+use anyhow::anyhow;
+use strum::*;
+use x11rb::{
+    connection::Connection,
+    cookie::VoidCookie,
+    errors::{ConnectionError, ReplyError},
+    protocol::{
+        randr::{self, ConnectionExt as _},
+        xproto::{
+            AtomEnum, ButtonIndex, ChangeWindowAttributesAux, ClientMessageEvent, ConfigWindow,
+            ConfigureNotifyEvent, ConfigureWindowAux, ConnectionExt as _, CreateWindowAux,
+            EventMask, GetPropertyReply, Grab, GrabMode, GrabStatus, InputFocus, KeyPressEvent,
+            MapState, Mapping, ModMask, PropMode, StackMode, WindowClass, CLIENT_MESSAGE_EVENT,
+            CONFIGURE_NOTIFY_EVENT, KEY_PRESS_EVENT, KEY_RELEASE_EVENT,
+        },
+        Event,
+    },
+    rust_connection::RustConnection,
+    wrapper::ConnectionExt as _,
+    x11_utils::X11Error,
+    COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME, NONE,
+};
+
+const NUMLOCK_MASK: ModMask = ModMask::M2;
+
+fn new_window_mask() -> EventMask {
+    EventMask::ENTER_WINDOW
+        | EventMask::LEAVE_WINDOW
+        | EventMask::PROPERTY_CHANGE
+        | EventMask::STRUCTURE_NOTIFY
+}
+
+fn mouse_mask() -> EventMask {
+    EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE | EventMask::BUTTON_MOTION
+}
+
+fn root_event_mask() -> EventMask {
+    EventMask::PROPERTY_CHANGE
+        | EventMask::SUBSTRUCTURE_REDIRECT
+        | EventMask::SUBSTRUCTURE_NOTIFY
+        | EventMask::BUTTON_MOTION
+}
+
+fn x_error(e: &X11Error) -> XError {
+    XError {
+        kind: XErrorKind::from(e.error_code),
+        resource_id: e.bad_value,
+        major_code: e.major_opcode,
+        minor_code: e.minor_opcode,
+        sequence: e.sequence,
+    }
+}
+
+// Errors reported by the X server itself are converted to XErrors
+fn reply_error(e: ReplyError) -> anyhow::Error {
+    match e {
+        ReplyError::X11Error(e) => anyhow!(x_error(&e)),
+        ReplyError::ConnectionError(e) => anyhow!(e),
+    }
+}
+
+fn prop_values(reply: &GetPropertyReply) -> Vec<u32> {
+    reply
+        .value32()
+        .map(|vals| vals.collect())
+        .unwrap_or_default()
+}
+
+/**
+ * Handles communication with an X server via the x11rb crate.
+ *
+ * X11rbConnection is a minimal implementation that does not make use of the async capabilities
+ * of x11rb: as with [XcbConnection][crate::xconnection::XcbConnection], replies are waited on
+ * as soon as each request is made.
+ **/
+pub struct X11rbConnection {
+    conn: RustConnection,
+    root: WinId,
+    check_win: WinId,
+    atoms: AtomCache,
+    auto_float_types: Vec<&'static str>,
+    checked: bool,
+}
+
+impl X11rbConnection {
+    /// Establish a new connection to the running X server. Fails if unable to connect
+    pub fn new() -> Result<X11rbConnection> {
+        let (conn, screen_num) = RustConnection::connect(None)?;
+        let root = conn
+            .setup()
+            .roots
+            .get(screen_num)
+            .ok_or_else(|| anyhow!("unable to get handle for screen"))?
+            .root;
+
+        // Send all of the intern requests before waiting on any of the replies
+        let cookies = Atom::iter()
+            .map(|atom| Ok((atom, conn.intern_atom(false, atom.as_ref().as_bytes())?)))
+            .collect::<Result<Vec<_>>>()?;
+        let atoms = AtomCache::default();
+        for (atom, cookie) in cookies {
+            atoms.insert(atom.as_ref(), cookie.reply()?.atom);
+        }
+
+        let auto_float_types: Vec<&'static str> = AUTO_FLOAT_WINDOW_TYPES
+            .iter()
+            .map(|atom| atom.as_ref())
+            .collect();
+
+        let check_win = conn.generate_id()?;
+        conn.create_window(
+            COPY_DEPTH_FROM_PARENT,
+            check_win,
+            root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            COPY_FROM_PARENT,
+            &CreateWindowAux::new(),
+        )?;
+
+        let notify_mask = randr::NotifyMask::OUTPUT_CHANGE
+            | randr::NotifyMask::CRTC_CHANGE
+            | randr::NotifyMask::SCREEN_CHANGE;
+        conn.randr_select_input(root, notify_mask)?.check()?;
+
+        Ok(X11rbConnection {
+            conn,
+            root,
+            check_win,
+            atoms,
+            auto_float_types,
+            checked: false,
+        })
+    }
+
+    /**
+     * Set whether or not requests that have no reply should be checked. When checked, each
+     * request blocks until the X server has processed it so that any resulting XError can be
+     * returned directly. When unchecked (the default) these errors arrive as XEvent::Error.
+     */
+    pub fn set_checked(&mut self, checked: bool) {
+        self.checked = checked;
+    }
+
+    // Requests that do not have a reply only fail immediately if the connection to the X server
+    // has been lost. Unless we are in checked mode, errors reported by the server itself arrive
+    // through the event loop.
+    fn void_request(
+        &self,
+        cookie: std::result::Result<VoidCookie<'_, RustConnection>, ConnectionError>,
+    ) -> Result<()> {
+        let cookie = cookie?;
+        if self.checked {
+            cookie.check().map_err(reply_error)
+        } else {
+            Ok(())
+        }
+    }
+
+    // Return the cached atom if we have seen it before, falling back to interning the atom
+    fn atom(&self, name: &str) -> Result<u32> {
+        self.atoms.id_or_else(name, || {
+            Ok(self.conn.intern_atom(false, name.as_bytes())?.reply()?.atom)
+        })
+    }
+
+    fn known_atom(&self, atom: Atom) -> u32 {
+        self.atoms.known(atom)
+    }
+
+    fn get_prop(&self, id: WinId, prop: u32, prop_type: u32, len: u32) -> Result<GetPropertyReply> {
+        self.conn
+            .get_property(false, id, prop, prop_type, 0, len)?
+            .reply()
+            .map_err(reply_error)
+    }
+
+    fn window_has_type_in(&self, id: WinId, win_types: &[u32]) -> Result<bool> {
+        let atom = self.known_atom(Atom::NetWmWindowType);
+        let r = self.get_prop(id, atom, AtomEnum::ANY.into(), 2048)?;
+        Ok(prop_values(&r).iter().any(|t| win_types.contains(t)))
+    }
+
+    fn window_has_state(&self, id: WinId, state: Atom) -> Result<bool> {
+        let prop = self.known_atom(Atom::NetWmState);
+        let r = self.get_prop(id, prop, AtomEnum::ATOM.into(), 1024)?;
+        Ok(prop_values(&r).contains(&self.known_atom(state)))
+    }
+
+    fn replace_prop32(&self, id: WinId, prop: Atom, prop_type: u32, data: &[u32]) -> Result<()> {
+        self.void_request(self.conn.change_property32(
+            PropMode::REPLACE,
+            id,
+            self.known_atom(prop),
+            prop_type,
+            data,
+        ))
+    }
+
+    fn replace_prop8(&self, id: WinId, prop: Atom, prop_type: u32, data: &[u8]) -> Result<()> {
+        self.void_request(self.conn.change_property8(
+            PropMode::REPLACE,
+            id,
+            self.known_atom(prop),
+            prop_type,
+            data,
+        ))
+    }
+
+    fn convert_event(&self, event: Event) -> Option<XEvent> {
+        match event {
+            Event::ButtonPress(e) => Some(XEvent::MouseEvent(
+                MouseEvent::from_x11rb_button(&e, MouseEventKind::Press).ok()?,
+            )),
+
+            Event::ButtonRelease(e) => Some(XEvent::MouseEvent(
+                MouseEvent::from_x11rb_button(&e, MouseEventKind::Release).ok()?,
+            )),
+
+            Event::MotionNotify(e) => {
+                Some(XEvent::MouseEvent(MouseEvent::from_x11rb_motion(&e).ok()?))
+            }
+
+            Event::KeyPress(e) | Event::KeyRelease(e) => Some(XEvent::KeyPress(
+                KeyCode::from_x11rb_key_press(&e).ignoring_modifier(NUMLOCK_MASK.into()),
+            )),
+
+            Event::MapRequest(e) => {
+                let attrs = self
+                    .conn
+                    .get_window_attributes(e.window)
+                    .ok()?
+                    .reply()
+                    .ok()?;
+                Some(XEvent::MapRequest {
+                    id: e.window,
+                    ignore: attrs.override_redirect,
+                })
+            }
+
+            Event::EnterNotify(e) => Some(XEvent::Enter {
+                id: e.event,
+                rpt: Point::new(e.root_x as u32, e.root_y as u32),
+                wpt: Point::new(e.event_x as u32, e.event_y as u32),
+            }),
+
+            Event::LeaveNotify(e) => Some(XEvent::Leave {
+                id: e.event,
+                rpt: Point::new(e.root_x as u32, e.root_y as u32),
+                wpt: Point::new(e.event_x as u32, e.event_y as u32),
+            }),
+
+            Event::DestroyNotify(e) => Some(XEvent::Destroy { id: e.window }),
+
+            // Unmaps are reported both to the window itself and to the root window: only
+            // the one for the root window is passed on
+            Event::UnmapNotify(e) if e.event == self.root => {
+                Some(XEvent::UnmapNotify { id: e.window })
+            }
+
+            Event::RandrScreenChangeNotify(_) => Some(XEvent::ScreenChange),
+
+            Event::RandrNotify(_) => Some(XEvent::RandrNotify),
+
+            Event::ConfigureNotify(e) => Some(XEvent::ConfigureNotify {
+                id: e.window,
+                r: Region::new(e.x as u32, e.y as u32, e.width as u32, e.height as u32),
+                is_root: e.window == self.root,
+            }),
+
+            Event::ConfigureRequest(e) => {
+                let (x, y, w, h) = self.window_geometry(e.window).ok()?.values();
+                let requested = |flag: ConfigWindow, val: u32, current: u32| {
+                    if e.value_mask & u16::from(flag) > 0 {
+                        val
+                    } else {
+                        current
+                    }
+                };
+
+                Some(XEvent::ConfigureRequest {
+                    id: e.window,
+                    r: Region::new(
+                        requested(ConfigWindow::X, cmp::max(e.x, 0) as u32, x),
+                        requested(ConfigWindow::Y, cmp::max(e.y, 0) as u32, y),
+                        requested(ConfigWindow::WIDTH, e.width as u32, w),
+                        requested(ConfigWindow::HEIGHT, e.height as u32, h),
+                    ),
+                    // unset values in the event are filled in by the server
+                    border: e.border_width as u32,
+                })
+            }
+
+            Event::ClientMessage(e) => Some(XEvent::ClientMessage {
+                id: e.window,
+                dtype: e.type_,
+                data: match e.format {
+                    8 => e.data.as_data8().iter().map(|&d| d as usize).collect(),
+                    16 => e.data.as_data16().iter().map(|&d| d as usize).collect(),
+                    32 => e.data.as_data32().iter().map(|&d| d as usize).collect(),
+                    _ => unreachable!("ClientMessageEvent.format should really be an enum..."),
+                },
+            }),
+
+            Event::PropertyNotify(e) => {
+                let is_root = e.window == self.root;
+                let is_name = [Atom::WmName, Atom::NetWmName]
+                    .iter()
+                    .any(|&a| self.known_atom(a) == e.atom);
+                if is_root && !is_name {
+                    None
+                } else {
+                    Some(XEvent::PropertyNotify {
+                        id: e.window,
+                        atom: e.atom,
+                        is_root,
+                    })
+                }
+            }
+
+            Event::MappingNotify(e) if e.request != Mapping::POINTER => Some(XEvent::MappingNotify),
+
+            Event::Error(e) => Some(XEvent::Error(x_error(&e))),
+
+            // NOTE: ignoring other event types
+            _ => None,
+        }
+    }
+}
+
+impl XConn for X11rbConnection {
+    fn flush(&self) -> Result<()> {
+        Ok(self.conn.flush()?)
+    }
+
+    fn wait_for_event(&self) -> Option<XEvent> {
+        match self.conn.wait_for_event() {
+            Ok(event) => self.convert_event(event),
+            Err(e) => {
+                error!("error reading X event: {}", e);
+                None
+            }
+        }
+    }
+
+    fn wait_for_event_timeout(&self, timeout: Duration) -> Option<XEvent> {
+        wait_for_converted_event(
+            self.conn.stream().as_raw_fd(),
+            timeout,
+            || Ok(self.conn.poll_for_event()?),
+            |event| self.convert_event(event),
+        )
+    }
+
+    fn current_outputs(&self) -> Result<Vec<Screen>> {
+        let resources = self
+            .conn
+            .randr_get_screen_resources(self.check_win)?
+            .reply()
+            .map_err(reply_error)?;
+
+        Ok(resources
+            .crtcs
+            .iter()
+            .flat_map(|&c| self.conn.randr_get_crtc_info(c, 0).ok()?.reply().ok())
+            .enumerate()
+            .map(|(i, r)| {
+                let region = Region::new(r.x as u32, r.y as u32, r.width as u32, r.height as u32);
+                Screen::new(region, i)
+            })
+            .filter(|s| {
+                let (_, _, w, _) = s.region(false).values();
+                w > 0
+            })
+            .collect())
+    }
+
+    fn cursor_position(&self) -> Result<Point> {
+        let reply = self
+            .conn
+            .query_pointer(self.root)?
+            .reply()
+            .map_err(reply_error)?;
+
+        Ok(Point::new(reply.root_x as u32, reply.root_y as u32))
+    }
+
+    fn position_window(
+        &self,
+        id: WinId,
+        reg: Region,
+        border: u32,
+        stack_above: bool,
+    ) -> Result<()> {
+        let (x, y, w, h) = reg.values();
+        let mut aux = ConfigureWindowAux::new()
+            .x(x as i32)
+            .y(y as i32)
+            .width(w)
+            .height(h)
+            .border_width(border);
+        if stack_above {
+            aux = aux.stack_mode(StackMode::ABOVE);
+        }
+
+        self.void_request(self.conn.configure_window(id, &aux))
+    }
+
+    fn raise_window(&self, id: WinId) -> Result<()> {
+        let aux = ConfigureWindowAux::new().stack_mode(StackMode::ABOVE);
+        self.void_request(self.conn.configure_window(id, &aux))
+    }
+
+    fn send_configure_notify(&self, id: WinId, reg: Region, border: u32) -> Result<()> {
+        let (x, y, w, h) = reg.values();
+        let event = ConfigureNotifyEvent {
+            response_type: CONFIGURE_NOTIFY_EVENT,
+            sequence: 0,
+            event: id,
+            window: id,
+            above_sibling: NONE,
+            x: x as i16,
+            y: y as i16,
+            width: w as u16,
+            height: h as u16,
+            border_width: border as u16,
+            override_redirect: false,
+        };
+
+        self.void_request(
+            self.conn
+                .send_event(false, id, EventMask::STRUCTURE_NOTIFY, event),
+        )
+    }
+
+    fn mark_new_window(&self, id: WinId) -> Result<()> {
+        let aux = ChangeWindowAttributesAux::new().event_mask(new_window_mask());
+        self.void_request(self.conn.change_window_attributes(id, &aux))
+    }
+
+    fn map_window(&self, id: WinId) -> Result<()> {
+        self.void_request(self.conn.map_window(id))
+    }
+
+    fn unmap_window(&self, id: WinId) -> Result<()> {
+        self.void_request(self.conn.unmap_window(id))
+    }
+
+    fn send_client_event(&self, id: WinId, atom_name: &str) -> Result<()> {
+        let atom = self.atom(atom_name)?;
+        let event = ClientMessageEvent {
+            response_type: CLIENT_MESSAGE_EVENT,
+            format: 32,
+            sequence: 0,
+            window: id,
+            type_: self.known_atom(Atom::WmProtocols),
+            data: [atom, CURRENT_TIME, 0, 0, 0].into(),
+        };
+        self.void_request(self.conn.send_event(false, id, EventMask::NO_EVENT, event))
+    }
+
+    fn send_key(&self, id: WinId, key: KeyCode) -> Result<()> {
+        let (response_type, mask) = if key.release {
+            (KEY_RELEASE_EVENT, EventMask::KEY_RELEASE)
+        } else {
+            (KEY_PRESS_EVENT, EventMask::KEY_PRESS)
+        };
+        let event = KeyPressEvent {
+            response_type,
+            detail: key.code,
+            sequence: 0,
+            time: CURRENT_TIME,
+            root: self.root,
+            event: id,
+            child: NONE,
+            root_x: 0,
+            root_y: 0,
+            event_x: 0,
+            event_y: 0,
+            state: key.mask,
+            same_screen: true,
+        };
+        self.void_request(self.conn.send_event(false, id, mask, event))
+    }
+
+    fn window_supports_protocol(&self, id: WinId, protocol: &str) -> Result<bool> {
+        let protocol = self.atom(protocol)?;
+        let prop = self.known_atom(Atom::WmProtocols);
+        let r = self.get_prop(id, prop, AtomEnum::ATOM.into(), 1024)?;
+        Ok(prop_values(&r).contains(&protocol))
+    }
+
+    fn kill_client(&self, id: WinId) -> Result<()> {
+        self.void_request(self.conn.kill_client(id))
+    }
+
+    fn focused_client(&self) -> Result<WinId> {
+        let reply = self.conn.get_input_focus()?.reply().map_err(reply_error)?;
+        Ok(reply.focus)
+    }
+
+    fn active_window(&self) -> Result<WinId> {
+        self.atom_prop(self.root, Atom::NetActiveWindow.as_ref())
+    }
+
+    fn focus_client(&self, id: WinId) -> Result<()> {
+        let model = self.focus_model(id)?;
+
+        if matches!(model, FocusModel::Passive | FocusModel::LocallyActive) {
+            self.void_request(
+                self.conn
+                    .set_input_focus(InputFocus::PARENT, id, CURRENT_TIME),
+            )?;
+        }
+
+        let wants_take_focus = matches!(
+            model,
+            FocusModel::LocallyActive | FocusModel::GloballyActive
+        );
+        if wants_take_focus {
+            if let Err(e) = self.send_client_event(id, Atom::WmTakeFocus.as_ref()) {
+                warn!("unable to send WM_TAKE_FOCUS to {}: {}", id, e);
+            }
+        }
+
+        self.replace_prop32(
+            self.root,
+            Atom::NetActiveWindow,
+            AtomEnum::WINDOW.into(),
+            &[id],
+        )
+    }
+
+    fn focus_model(&self, id: WinId) -> Result<FocusModel> {
+        // InputHint: see section 4.1.2.4 of the ICCCM
+        const INPUT_HINT: u32 = 1;
+
+        let prop = self.known_atom(Atom::WmHints);
+        let hints = self.get_prop(id, prop, AtomEnum::WM_HINTS.into(), 2)?;
+        // Clients that do not set the input hint are assumed to want keyboard input
+        let accepts_input = match prop_values(&hints)[..] {
+            [flags, input, ..] if flags & INPUT_HINT > 0 => input != 0,
+            _ => true,
+        };
+        let take_focus = self.window_supports_protocol(id, Atom::WmTakeFocus.as_ref())?;
+
+        Ok(match (accepts_input, take_focus) {
+            (false, false) => FocusModel::NoInput,
+            (true, false) => FocusModel::Passive,
+            (true, true) => FocusModel::LocallyActive,
+            (false, true) => FocusModel::GloballyActive,
+        })
+    }
+
+    fn set_client_border_color(&self, id: WinId, color: u32) -> Result<()> {
+        let aux = ChangeWindowAttributesAux::new().border_pixel(color);
+        self.void_request(self.conn.change_window_attributes(id, &aux))
+    }
+
+    fn toggle_client_fullscreen(&self, id: WinId, client_is_fullscreen: bool) -> Result<()> {
+        let data = if client_is_fullscreen {
+            0
+        } else {
+            self.known_atom(Atom::NetWmStateFullscreen)
+        };
+
+        self.replace_prop32(id, Atom::NetWmState, AtomEnum::ATOM.into(), &[data])
+    }
+
+    fn grab_keys(&self, key_bindings: &KeyBindings, mouse_bindings: &MouseBindings) -> Result<()> {
+        // We need to explicitly grab NumLock as an additional modifier and then drop it later on
+        // when we are passing events through to the WindowManager as NumLock alters the modifier
+        // mask when it is active.
+        let modifiers = &[0, u16::from(NUMLOCK_MASK)];
+
+        self.void_request(self.conn.ungrab_key(Grab::ANY, self.root, ModMask::ANY))?;
+        self.void_request(
+            self.conn
+                .ungrab_button(ButtonIndex::ANY, self.root, ModMask::ANY),
+        )?;
+
+        for m in modifiers.iter() {
+            for k in key_bindings.keys() {
+                self.void_request(self.conn.grab_key(
+                    false,           // don't pass grabbed events through to the client
+                    self.root,       // the window to grab: in this case the root window
+                    k.mask | m,      // modifiers to grab
+                    k.code,          // keycode to grab
+                    GrabMode::ASYNC, // don't lock pointer input while grabbing
+                    GrabMode::ASYNC, // don't lock keyboard input while grabbing
+                ))?;
+            }
+
+            for (_, state) in mouse_bindings.keys() {
+                self.void_request(self.conn.grab_button(
+                    false,                          // don't pass grabbed events through to the client
+                    self.root, // the window to grab: in this case the root window
+                    u32::from(mouse_mask()) as u16, // which events are reported to the client
+                    GrabMode::ASYNC, // don't lock pointer input while grabbing
+                    GrabMode::ASYNC, // don't lock keyboard input while grabbing
+                    NONE,      // don't confine the cursor to a specific window
+                    NONE,      // don't change the cursor type
+                    state.button().into(), // the button to grab
+                    state.mask() | m, // modifiers to grab
+                ))?;
+            }
+        }
+
+        let aux = ChangeWindowAttributesAux::new().event_mask(root_event_mask());
+        self.void_request(self.conn.change_window_attributes(self.root, &aux))?;
+        self.flush()
+    }
+
+    fn keyboard_mapping(&self) -> Result<KeyboardMapping> {
+        let setup = self.conn.setup();
+        let (min, max) = (setup.min_keycode, setup.max_keycode);
+        let reply = self
+            .conn
+            .get_keyboard_mapping(min, max - min + 1)?
+            .reply()
+            .map_err(reply_error)?;
+
+        Ok(keysyms::mapping_from_reply(
+            min,
+            reply.keysyms_per_keycode,
+            &reply.keysyms,
+        ))
+    }
+
+    fn grab_pointer(&self) -> Result<()> {
+        let cookie = self.conn.grab_pointer(
+            false,                          // don't pass grabbed events through to the client
+            self.root,                      // the window to grab: in this case the root window
+            u32::from(mouse_mask()) as u16, // which events are reported to the client
+            GrabMode::ASYNC,                // don't lock pointer input while grabbing
+            GrabMode::ASYNC,                // don't lock keyboard input while grabbing
+            NONE,                           // don't confine the cursor to a specific window
+            NONE,                           // don't change the cursor type
+            CURRENT_TIME,                   // time the grab was requested
+        );
+
+        cookie?.reply().map_err(reply_error)?;
+        Ok(())
+    }
+
+    fn ungrab_pointer(&self) -> Result<()> {
+        self.void_request(self.conn.ungrab_pointer(CURRENT_TIME))
+    }
+
+    fn grab_keyboard(&self) -> Result<()> {
+        let cookie = self.conn.grab_keyboard(
+            false,           // don't pass grabbed events through to the client
+            self.root,       // the window to grab: in this case the root window
+            CURRENT_TIME,    // time the grab was requested
+            GrabMode::ASYNC, // don't lock pointer input while grabbing
+            GrabMode::ASYNC, // don't lock keyboard input while grabbing
+        );
+
+        let reply = cookie?.reply().map_err(reply_error)?;
+        if reply.status == GrabStatus::SUCCESS {
+            Ok(())
+        } else {
+            Err(anyhow!("unable to grab the keyboard: {:?}", reply.status))
+        }
+    }
+
+    fn ungrab_keyboard(&self) -> Result<()> {
+        self.void_request(self.conn.ungrab_keyboard(CURRENT_TIME))
+    }
+
+    fn set_wm_properties(&self, workspaces: &[&str]) -> Result<()> {
+        let utf8 = self.known_atom(Atom::UTF8String);
+        for &win in &[self.check_win, self.root] {
+            self.replace_prop32(
+                win,
+                Atom::NetSupportingWmCheck,
+                AtomEnum::WINDOW.into(),
+                &[self.check_win],
+            )?;
+            self.replace_prop8(win, Atom::NetWmName, utf8, WM_NAME.as_bytes())?;
+        }
+
+        // EWMH support
+        let supported: Vec<u32> = Atom::iter().map(|a| self.known_atom(a)).collect();
+        self.replace_prop32(
+            self.root,
+            Atom::NetSupported,
+            AtomEnum::ATOM.into(),
+            &supported,
+        )?;
+        self.update_desktops(workspaces)?;
+
+        for &atom in &[Atom::NetClientList, Atom::NetClientListStacking] {
+            self.void_request(self.conn.delete_property(self.root, self.known_atom(atom)))?;
+        }
+
+        Ok(())
+    }
+
+    fn update_desktops(&self, workspaces: &[&str]) -> Result<()> {
+        self.replace_prop32(
+            self.root,
+            Atom::NetNumberOfDesktops,
+            AtomEnum::CARDINAL.into(),
+            &[workspaces.len() as u32],
+        )?;
+        self.replace_prop8(
+            self.root,
+            Atom::NetDesktopNames,
+            self.known_atom(Atom::UTF8String),
+            workspaces.join("\0").as_bytes(),
+        )
+    }
+
+    fn update_client_list(&self, clients: &[WinId], stacking: &[WinId]) -> Result<()> {
+        let window = AtomEnum::WINDOW.into();
+        self.replace_prop32(self.root, Atom::NetClientList, window, clients)?;
+        self.replace_prop32(self.root, Atom::NetClientListStacking, window, stacking)
+    }
+
+    fn set_current_workspace(&self, wix: usize) -> Result<()> {
+        self.replace_prop32(
+            self.root,
+            Atom::NetCurrentDesktop,
+            AtomEnum::CARDINAL.into(),
+            &[wix as u32],
+        )
+    }
+
+    fn set_root_window_name(&self, name: &str) -> Result<()> {
+        let utf8 = self.known_atom(Atom::UTF8String);
+        self.replace_prop8(self.root, Atom::WmName, utf8, name.as_bytes())
+    }
+
+    fn set_client_workspace(&self, id: WinId, wix: usize) -> Result<()> {
+        self.replace_prop32(
+            id,
+            Atom::NetWmDesktop,
+            AtomEnum::CARDINAL.into(),
+            &[wix as u32],
+        )
+    }
+
+    fn set_client_state(&self, id: WinId, state: WindowState) -> Result<()> {
+        let state = match state {
+            WindowState::Withdrawn => 0,
+            WindowState::Normal => 1,
+            WindowState::Iconic => 3,
+        };
+
+        let wm_state = self.known_atom(Atom::WmState);
+        self.replace_prop32(id, Atom::WmState, wm_state, &[state, NONE])
+    }
+
+    fn window_should_float(&self, id: WinId, floating_classes: &[&str]) -> Result<bool> {
+        let class = self.str_prop(id, Atom::WmClass.as_ref())?;
+        if class.split('\0').any(|c| floating_classes.contains(&c)) {
+            return Ok(true);
+        }
+
+        let types = self.str_prop(id, Atom::NetWmWindowType.as_ref())?;
+        Ok(types
+            .split('\0')
+            .any(|t| self.auto_float_types.contains(&t)))
+    }
+
+    fn window_is_dock(&self, id: WinId) -> Result<bool> {
+        let dock_types: Vec<u32> = UNMANAGED_WINDOW_TYPES
+            .iter()
+            .map(|&t| self.known_atom(t))
+            .collect();
+
+        self.window_has_type_in(id, &dock_types)
+    }
+
+    fn window_strut(&self, id: WinId) -> Result<Strut> {
+        for &atom in &[Atom::NetWmStrutPartial, Atom::NetWmStrut] {
+            let reply = self.get_prop(id, self.known_atom(atom), AtomEnum::CARDINAL.into(), 12)?;
+            let v = prop_values(&reply);
+            if v.len() == 12 {
+                return Ok(Strut {
+                    left: v[0],
+                    right: v[1],
+                    top: v[2],
+                    bottom: v[3],
+                    left_y: (v[4], v[5]),
+                    right_y: (v[6], v[7]),
+                    top_x: (v[8], v[9]),
+                    bottom_x: (v[10], v[11]),
+                });
+            } else if v.len() == 4 {
+                // _NET_WM_STRUT covers the full length of each edge
+                let full = (0, u32::MAX);
+                return Ok(Strut {
+                    left: v[0],
+                    right: v[1],
+                    top: v[2],
+                    bottom: v[3],
+                    left_y: full,
+                    right_y: full,
+                    top_x: full,
+                    bottom_x: full,
+                });
+            }
+        }
+
+        Err(anyhow!("no strut set for id: {}", id))
+    }
+
+    fn window_is_urgent(&self, id: WinId) -> Result<bool> {
+        // XUrgencyHint: see section 4.1.2.4 of the ICCCM
+        const URGENCY_HINT: u32 = 1 << 8;
+
+        let prop = self.known_atom(Atom::WmHints);
+        let hints = self.get_prop(id, prop, AtomEnum::WM_HINTS.into(), 1)?;
+        let hint_set =
+            matches!(prop_values(&hints).first(), Some(flags) if flags & URGENCY_HINT > 0);
+
+        Ok(hint_set || self.window_has_state(id, Atom::NetWmStateDemandsAttention)?)
+    }
+
+    fn window_geometry(&self, id: WinId) -> Result<Region> {
+        let res = self.conn.get_geometry(id)?.reply().map_err(reply_error)?;
+        Ok(Region::new(
+            res.x as u32,
+            res.y as u32,
+            res.width as u32,
+            res.height as u32,
+        ))
+    }
+
+    fn size_hints(&self, id: WinId) -> Result<SizeHints> {
+        let prop = self.known_atom(Atom::WmNormalHints);
+        let reply = self.get_prop(id, prop, AtomEnum::WM_SIZE_HINTS.into(), 18)?;
+        let v = prop_values(&reply);
+        SizeHints::from_wm_normal_hints(&v)
+            .ok_or_else(|| anyhow!("WM_NORMAL_HINTS was not set for id: {}", id))
+    }
+
+    fn warp_cursor(&self, win_id: Option<WinId>, screen: &Screen) -> Result<()> {
+        let (x, y, id) = match win_id {
+            Some(id) => {
+                let (_, _, w, h) = self.window_geometry(id)?.values();
+                ((w / 2) as i16, (h / 2) as i16, id)
+            }
+            None => {
+                let (x, y, w, h) = screen.region(true).values();
+                ((x + w / 2) as i16, (y + h / 2) as i16, self.root)
+            }
+        };
+
+        self.void_request(self.conn.warp_pointer(NONE, id, 0, 0, 0, 0, x, y))
+    }
+
+    fn query_for_active_windows(&self) -> Result<Vec<WinId>> {
+        let all_ids = self
+            .conn
+            .query_tree(self.root)?
+            .reply()
+            .map_err(reply_error)?
+            .children;
+
+        Ok(all_ids
+            .into_iter()
+            .filter(
+                |&id| match self.conn.get_window_attributes(id).map(|c| c.reply()) {
+                    Ok(Ok(attrs)) => {
+                        !attrs.override_redirect && attrs.map_state == MapState::VIEWABLE
+                    }
+                    _ => false,
+                },
+            )
+            .collect())
+    }
+
+    fn str_prop(&self, id: u32, name: &str) -> Result<String> {
+        let reply = self.get_prop(id, self.atom(name)?, AtomEnum::ANY.into(), 1024)?;
+        Ok(String::from_utf8(reply.value)?)
+    }
+
+    fn atom_prop(&self, id: u32, name: &str) -> Result<u32> {
+        let reply = self.get_prop(id, self.atom(name)?, AtomEnum::ANY.into(), 1024)?;
+        match prop_values(&reply).first() {
+            Some(&a) => Ok(a),
+            None => Err(anyhow!("property '{}' was empty for id: {}", name, id)),
+        }
+    }
+
+    fn intern_atom(&self, atom: &str) -> Result<u32> {
+        self.atom(atom)
+    }
+
+    fn atom_name(&self, atom: u32) -> Result<String> {
+        self.atoms.name_or_else(atom, || {
+            let reply = self.conn.get_atom_name(atom)?.reply()?;
+            Ok(String::from_utf8(reply.name)?)
+        })
+    }
+
+    // - Release all of the keybindings we are holding on to
+    // - destroy the check window
+    // - mark ourselves as no longer being the active root window
+    fn cleanup(&self) -> Result<()> {
+        self.void_request(self.conn.ungrab_key(Grab::ANY, self.root, ModMask::ANY))?;
+        self.void_request(self.conn.destroy_window(self.check_win))?;
+        self.void_request(
+            self.conn
+                .delete_property(self.root, self.known_atom(Atom::NetActiveWindow)),
+        )?;
+        self.flush()
+    }
+}
@@ -0,0 +1,1309 @@
+/*! API wrapper for talking to the X server using XCB
+ *
+ *  The crate used by penrose for talking to the X server is rust-xcb, which
+ *  is a set of bindings for the C level XCB library that are autogenerated
+ *  from an XML spec. The XML files can be found
+ *  [here](https://github.com/rtbo/rust-xcb/tree/master/xml) and are useful
+ *  as reference for how the API works. Sections have been converted and added
+ *  to the documentation of the method calls and enums present in this module.
+ *
+ *  This module is only available when the `xcb` feature (enabled by default) is enabled.
+ */
+use crate::{
+    bindings::{KeyBindings, KeyCode, KeyboardMapping, MouseBindings, MouseEvent},
+    data_types::{FocusModel, Point, Region, SizeHints, Strut, WinId, WindowState},
+    keysyms,
+    screen::Screen,
+    xconnection::{
+        wait_for_converted_event, Atom, AtomCache, XConn, XError, XErrorKind, XEvent,
+        AUTO_FLOAT_WINDOW_TYPES, UNMANAGED_WINDOW_TYPES, WM_NAME,
+    },
+    Result,
+};
+
+use std::{cmp, os::unix::io::AsRawFd, time::Duration};
+
+use anyhow::anyhow;
+use strum::*;
+
+// xcb argument enums
+const WINDOW_CLASS_INPUT_ONLY: u16 = xcb::xproto::WINDOW_CLASS_INPUT_ONLY as u16;
+const GRAB_MODE_ASYNC: u8 = xcb::GRAB_MODE_ASYNC as u8;
+const INPUT_FOCUS_PARENT: u8 = xcb::INPUT_FOCUS_PARENT as u8;
+const GRAB_ANY: u8 = xcb::GRAB_ANY as u8;
+const BUTTON_INDEX_ANY: u8 = xcb::BUTTON_INDEX_ANY as u8;
+const MOD_MASK_ANY: u16 = xcb::MOD_MASK_ANY as u16;
+const PROP_MODE_REPLACE: u8 = xcb::PROP_MODE_REPLACE as u8;
+
+// atoms
+const ATOM_WINDOW: u32 = xcb::xproto::ATOM_WINDOW;
+const ATOM_ATOM: u32 = xcb::xproto::ATOM_ATOM;
+const ATOM_CARDINAL: u32 = xcb::xproto::ATOM_CARDINAL;
+
+// window props
+const STACK_MODE: u16 = xcb::CONFIG_WINDOW_STACK_MODE as u16;
+const STACK_ABOVE: u32 = xcb::STACK_MODE_ABOVE as u32;
+const WIN_BORDER: u16 = xcb::CONFIG_WINDOW_BORDER_WIDTH as u16;
+const WIN_HEIGHT: u16 = xcb::CONFIG_WINDOW_HEIGHT as u16;
+const WIN_WIDTH: u16 = xcb::CONFIG_WINDOW_WIDTH as u16;
+const WIN_X: u16 = xcb::CONFIG_WINDOW_X as u16;
+const WIN_Y: u16 = xcb::CONFIG_WINDOW_Y as u16;
+
+// masks
+const XCB_RESPONSE_TYPE_MASK: u8 = 0x7F;
+const NUMLOCK_MASK: u16 = xcb::MOD_MASK_2 as u16;
+const NOTIFY_MASK: u16 = (xcb::randr::NOTIFY_MASK_OUTPUT_CHANGE
+    | xcb::randr::NOTIFY_MASK_CRTC_CHANGE
+    | xcb::randr::NOTIFY_MASK_SCREEN_CHANGE) as u16;
+const NEW_WINDOW_MASK: u32 = xcb::EVENT_MASK_ENTER_WINDOW
+    | xcb::EVENT_MASK_LEAVE_WINDOW
+    | xcb::EVENT_MASK_PROPERTY_CHANGE
+    | xcb::EVENT_MASK_STRUCTURE_NOTIFY;
+const MOUSE_MASK: u16 = (xcb::EVENT_MASK_BUTTON_PRESS
+    | xcb::EVENT_MASK_BUTTON_RELEASE
+    | xcb::EVENT_MASK_BUTTON_MOTION) as u16;
+const EVENT_MASK: u32 = xcb::EVENT_MASK_PROPERTY_CHANGE
+    | xcb::EVENT_MASK_SUBSTRUCTURE_REDIRECT
+    | xcb::EVENT_MASK_SUBSTRUCTURE_NOTIFY
+    | xcb::EVENT_MASK_BUTTON_MOTION;
+
+// xcb docs: https://www.mankier.com/3/xcb_generic_error_t
+fn x_error(raw: &xcb::ffi::xcb_generic_error_t) -> XError {
+    XError {
+        kind: XErrorKind::from(raw.error_code),
+        resource_id: raw.resource_id,
+        major_code: raw.major_code,
+        minor_code: raw.minor_code,
+        sequence: raw.sequence,
+    }
+}
+
+// Convert an error returned from an xcb request into an XError
+fn xcb_error(e: xcb::GenericError) -> anyhow::Error {
+    anyhow!(x_error(unsafe { &*e.ptr }))
+}
+
+// xcb docs: https://www.mankier.com/3/xcb_get_keyboard_mapping
+pub(crate) fn xcb_keyboard_mapping(conn: &xcb::Connection) -> Result<KeyboardMapping> {
+    let setup = conn.get_setup();
+    let (min, max) = (setup.min_keycode(), setup.max_keycode());
+    let reply = xcb::get_keyboard_mapping(conn, min, max - min + 1)
+        .get_reply()
+        .map_err(xcb_error)?;
+
+    Ok(keysyms::mapping_from_reply(
+        min,
+        reply.keysyms_per_keycode(),
+        reply.keysyms(),
+    ))
+}
+
+// Issue a request that has no reply. In checked mode we wait for the X server to process the
+// request and return any error that it reports, otherwise errors are reported through the
+// event loop.
+macro_rules! void_request {
+    ($self:ident, $unchecked:path, $checked:path, $($arg:expr),+ $(,)?) => {
+        if $self.checked {
+            $checked(&$self.conn, $($arg),+).request_check().map_err(xcb_error)
+        } else {
+            $unchecked(&$self.conn, $($arg),+);
+            Ok(())
+        }
+    };
+}
+
+/**
+ * Handles communication with an X server via the XCB library.
+ *
+ * XcbConnection is a minimal implementation that does not make use of the full asyc capabilities
+ * of the underlying C XCB library.
+ **/
+pub struct XcbConnection {
+    conn: xcb::Connection,
+    root: WinId,
+    check_win: WinId,
+    atoms: AtomCache,
+    auto_float_types: Vec<&'static str>,
+    randr_base: u8,
+    checked: bool,
+}
+
+impl XcbConnection {
+    /// Establish a new connection to the running X server. Fails if unable to connect
+    pub fn new() -> Result<XcbConnection> {
+        let (conn, _) = xcb::Connection::connect(None)?;
+        let root = conn
+            .get_setup()
+            .roots()
+            .next()
+            .ok_or_else(|| anyhow!("unable to get handle for screen"))?
+            .root();
+
+        // https://www.mankier.com/3/xcb_intern_atom
+        let atoms = AtomCache::default();
+        for atom in Atom::iter() {
+            // false == always return the atom, even if exists already
+            let val = xcb::intern_atom(&conn, false, atom.as_ref())
+                .get_reply()?
+                .atom();
+            atoms.insert(atom.as_ref(), val);
+        }
+
+        let auto_float_types: Vec<&'static str> = AUTO_FLOAT_WINDOW_TYPES
+            .iter()
+            .map(|atom| atom.as_ref())
+            .collect();
+
+        let check_win = conn.generate_id();
+
+        // xcb docs: https://www.mankier.com/3/xcb_create_window
+        xcb::create_window(
+            &conn,                   // xcb connection to X11
+            0,                       // new window's depth
+            check_win,               // ID to be used for referring to the window
+            root,                    // parent window
+            0,                       // x-coordinate
+            0,                       // y-coordinate
+            1,                       // width
+            1,                       // height
+            0,                       // border width
+            WINDOW_CLASS_INPUT_ONLY, // class (i _think_ 0 == COPY_FROM_PARENT?)
+            0,                       // visual (i _think_ 0 == COPY_FROM_PARENT?)
+            &[],                     // value list? (value mask? not documented either way...)
+        );
+
+        let randr_base = conn
+            .get_extension_data(&mut xcb::randr::id())
+            .ok_or_else(|| anyhow!("unable to fetch extension data"))?
+            .first_event();
+
+        // xcb docs: https://www.mankier.com/3/xcb_randr_select_input
+        xcb::randr::select_input(&conn, root, NOTIFY_MASK).request_check()?;
+
+        Ok(XcbConnection {
+            conn,
+            root,
+            check_win,
+            atoms,
+            auto_float_types,
+            randr_base,
+            checked: false,
+        })
+    }
+
+    /**
+     * Set whether or not requests that have no reply should be checked. When checked, each
+     * request blocks until the X server has processed it so that any resulting XError can be
+     * returned directly. When unchecked (the default) these errors arrive as XEvent::Error.
+     */
+    pub fn set_checked(&mut self, checked: bool) {
+        self.checked = checked;
+    }
+
+    fn convert_event(&self, event: xcb::GenericEvent) -> Option<XEvent> {
+        let etype = event.response_type() & XCB_RESPONSE_TYPE_MASK;
+        // Errors for unchecked requests share the event queue with a response type of 0
+        if etype == 0 {
+            let raw = unsafe { &*(event.ptr as *const xcb::ffi::xcb_generic_error_t) };
+            return Some(XEvent::Error(x_error(raw)));
+        }
+        // Need to apply the randr_base mask as well which doesn't seem to work in 'match'
+        if etype == self.randr_base + xcb::randr::NOTIFY {
+            return Some(XEvent::RandrNotify);
+        }
+
+        match etype {
+            xcb::BUTTON_PRESS => {
+                let e: &xcb::ButtonPressEvent = unsafe { xcb::cast_event(&event) };
+                Some(XEvent::MouseEvent(MouseEvent::from_press(e).ok()?))
+            }
+
+            xcb::BUTTON_RELEASE => {
+                let e: &xcb::ButtonReleaseEvent = unsafe { xcb::cast_event(&event) };
+                Some(XEvent::MouseEvent(MouseEvent::from_release(e).ok()?))
+            }
+
+            xcb::MOTION_NOTIFY => {
+                let e: &xcb::MotionNotifyEvent = unsafe { xcb::cast_event(&event) };
+                Some(XEvent::MouseEvent(MouseEvent::from_motion(e).ok()?))
+            }
+
+            xcb::KEY_PRESS => {
+                let e: &xcb::KeyPressEvent = unsafe { xcb::cast_event(&event) };
+                Some(XEvent::KeyPress(
+                    KeyCode::from_key_press(e).ignoring_modifier(NUMLOCK_MASK),
+                ))
+            }
+
+            xcb::KEY_RELEASE => {
+                let e: &xcb::KeyReleaseEvent = unsafe { xcb::cast_event(&event) };
+                Some(XEvent::KeyPress(
+                    KeyCode::from_key_release(e).ignoring_modifier(NUMLOCK_MASK),
+                ))
+            }
+
+            xcb::MAP_REQUEST => {
+                let e: &xcb::MapRequestEvent = unsafe { xcb::cast_event(&event) };
+                let id = e.window();
+                xcb::xproto::get_window_attributes(&self.conn, id)
+                    .get_reply()
+                    .ok()
+                    .map(|r| XEvent::MapRequest {
+                        id,
+                        ignore: r.override_redirect(),
+                    })
+            }
+
+            xcb::ENTER_NOTIFY => {
+                let e: &xcb::EnterNotifyEvent = unsafe { xcb::cast_event(&event) };
+                Some(XEvent::Enter {
+                    id: e.event(),
+                    rpt: Point::new(e.root_x() as u32, e.root_y() as u32),
+                    wpt: Point::new(e.event_x() as u32, e.event_y() as u32),
+                })
+            }
+
+            xcb::LEAVE_NOTIFY => {
+                let e: &xcb::LeaveNotifyEvent = unsafe { xcb::cast_event(&event) };
+                Some(XEvent::Leave {
+                    id: e.event(),
+                    rpt: Point::new(e.root_x() as u32, e.root_y() as u32),
+                    wpt: Point::new(e.event_x() as u32, e.event_y() as u32),
+                })
+            }
+
+            xcb::DESTROY_NOTIFY => {
+                let e: &xcb::MapNotifyEvent = unsafe { xcb::cast_event(&event) };
+                Some(XEvent::Destroy { id: e.window() })
+            }
+
+            // Unmaps are reported both to the window itself and to the root window: only
+            // the one for the root window is passed on
+            xcb::UNMAP_NOTIFY => {
+                let e: &xcb::UnmapNotifyEvent = unsafe { xcb::cast_event(&event) };
+                if e.event() == self.root {
+                    Some(XEvent::UnmapNotify { id: e.window() })
+                } else {
+                    None
+                }
+            }
+
+            xcb::randr::SCREEN_CHANGE_NOTIFY => Some(XEvent::ScreenChange),
+
+            xcb::CONFIGURE_NOTIFY => {
+                let e: &xcb::ConfigureNotifyEvent = unsafe { xcb::cast_event(&event) };
+                Some(XEvent::ConfigureNotify {
+                    id: e.window(),
+                    r: Region::new(
+                        e.x() as u32,
+                        e.y() as u32,
+                        e.width() as u32,
+                        e.height() as u32,
+                    ),
+                    is_root: e.window() == self.root,
+                })
+            }
+
+            xcb::CONFIGURE_REQUEST => {
+                let e: &xcb::ConfigureRequestEvent = unsafe { xcb::cast_event(&event) };
+                let id = e.window();
+                let mask = e.value_mask();
+                let (x, y, w, h) = self.window_geometry(id).ok()?.values();
+                let requested = |flag: u16, val: u32, current: u32| {
+                    if mask & flag > 0 {
+                        val
+                    } else {
+                        current
+                    }
+                };
+
+                Some(XEvent::ConfigureRequest {
+                    id,
+                    r: Region::new(
+                        requested(WIN_X, cmp::max(e.x(), 0) as u32, x),
+                        requested(WIN_Y, cmp::max(e.y(), 0) as u32, y),
+                        requested(WIN_WIDTH, e.width() as u32, w),
+                        requested(WIN_HEIGHT, e.height() as u32, h),
+                    ),
+                    // unset values in the event are filled in by the server
+                    border: e.border_width() as u32,
+                })
+            }
+
+            xcb::CLIENT_MESSAGE => {
+                let e: &xcb::ClientMessageEvent = unsafe { xcb::cast_event(&event) };
+                Some(XEvent::ClientMessage {
+                    id: e.window(),
+                    dtype: e.type_(),
+                    data: match e.format() {
+                        8 => e.data().data8().iter().map(|&d| d as usize).collect(),
+                        16 => e.data().data16().iter().map(|&d| d as usize).collect(),
+                        32 => e.data().data32().iter().map(|&d| d as usize).collect(),
+                        _ => unreachable!("ClientMessageEvent.format should really be an enum..."),
+                    },
+                })
+            }
+
+            xcb::PROPERTY_NOTIFY => {
+                let e: &xcb::PropertyNotifyEvent = unsafe { xcb::cast_event(&event) };
+                let atom = e.atom();
+                let is_root = e.window() == self.root;
+                let is_name = [Atom::WmName, Atom::NetWmName]
+                    .iter()
+                    .any(|&a| self.known_atom(a) == atom);
+                if is_root && !is_name {
+                    None
+                } else {
+                    Some(XEvent::PropertyNotify {
+                        id: e.window(),
+                        atom,
+                        is_root,
+                    })
+                }
+            }
+
+            xcb::MAPPING_NOTIFY => {
+                let e: &xcb::MappingNotifyEvent = unsafe { xcb::cast_event(&event) };
+                if e.request() == xcb::MAPPING_POINTER as u8 {
+                    None
+                } else {
+                    Some(XEvent::MappingNotify)
+                }
+            }
+
+            // NOTE: ignoring other event types
+            _ => None,
+        }
+    }
+
+    // Return the cached atom if we have seen it before, falling back to interning the atom
+    fn atom(&self, name: &str) -> Result<u32> {
+        self.atoms.id_or_else(name, || {
+            Ok(xcb::intern_atom(&self.conn, false, name)
+                .get_reply()?
+                .atom())
+        })
+    }
+
+    fn known_atom(&self, atom: Atom) -> u32 {
+        self.atoms.known(atom)
+    }
+
+    fn window_has_type_in(&self, id: WinId, win_types: &[u32]) -> Result<bool> {
+        // xcb docs: https://www.mankier.com/3/xcb_get_property
+        let atom = self.known_atom(Atom::NetWmWindowType);
+        let cookie = xcb::get_property(
+            &self.conn,    // xcb connection to X11
+            false,         // should the property be deleted
+            id,            // target window to query
+            atom,          // the property we want
+            xcb::ATOM_ANY, // the type of the property
+            0,             // offset in the property to retrieve data from
+            2048,          // how many 32bit multiples of data to retrieve
+        );
+
+        let types = cookie.get_reply().map_err(xcb_error)?;
+        Ok(types.value().iter().any(|t| win_types.contains(t)))
+    }
+
+    fn window_has_state(&self, id: WinId, state: Atom) -> Result<bool> {
+        // xcb docs: https://www.mankier.com/3/xcb_get_property
+        let cookie = xcb::get_property(
+            &self.conn,                        // xcb connection to X11
+            false,                             // should the property be deleted
+            id,                                // target window to query
+            self.known_atom(Atom::NetWmState), // the property we want
+            ATOM_ATOM,                         // the type of the property
+            0,                                 // offset in the property to retrieve data from
+            1024,                              // how many 32bit multiples of data to retrieve
+        );
+
+        let state = self.known_atom(state);
+        let states = cookie.get_reply().map_err(xcb_error)?;
+        Ok(states.value::<u32>().contains(&state))
+    }
+}
+
+impl XConn for XcbConnection {
+    fn flush(&self) -> Result<()> {
+        if self.conn.flush() {
+            Ok(())
+        } else {
+            Err(anyhow!("unable to flush the xcb connection"))
+        }
+    }
+
+    fn wait_for_event(&self) -> Option<XEvent> {
+        self.conn
+            .wait_for_event()
+            .and_then(|event| self.convert_event(event))
+    }
+
+    fn wait_for_event_timeout(&self, timeout: Duration) -> Option<XEvent> {
+        wait_for_converted_event(
+            self.conn.as_raw_fd(),
+            timeout,
+            || Ok(self.conn.poll_for_event()),
+            |event| self.convert_event(event),
+        )
+    }
+
+    fn current_outputs(&self) -> Result<Vec<Screen>> {
+        // xcb docs: https://www.mankier.com/3/xcb_randr_get_screen_resources
+        let resources = xcb::randr::get_screen_resources(&self.conn, self.check_win)
+            .get_reply()
+            .map_err(xcb_error)?;
+
+        // xcb docs: https://www.mankier.com/3/xcb_randr_get_crtc_info
+        Ok(resources
+            .crtcs()
+            .iter()
+            .flat_map(|c| xcb::randr::get_crtc_info(&self.conn, *c, 0).get_reply())
+            .enumerate()
+            .map(|(i, r)| Screen::from_crtc_info_reply(r, i))
+            .filter(|s| {
+                let (_, _, w, _) = s.region(false).values();
+                w > 0
+            })
+            .collect())
+    }
+
+    fn cursor_position(&self) -> Result<Point> {
+        // xcb docs: https://www.mankier.com/3/xcb_query_pointer
+        let reply = xcb::query_pointer(&self.conn, self.root)
+            .get_reply()
+            .map_err(xcb_error)?;
+
+        Ok(Point::new(reply.root_x() as u32, reply.root_y() as u32))
+    }
+
+    fn position_window(
+        &self,
+        id: WinId,
+        reg: Region,
+        border: u32,
+        stack_above: bool,
+    ) -> Result<()> {
+        let (x, y, w, h) = reg.values();
+        let mut args = vec![
+            (WIN_X, x),
+            (WIN_Y, y),
+            (WIN_WIDTH, w),
+            (WIN_HEIGHT, h),
+            (WIN_BORDER, border),
+        ];
+        if stack_above {
+            args.push((STACK_MODE, STACK_ABOVE));
+        }
+
+        // xcb docs: https://www.mankier.com/3/xcb_configure_window
+        void_request!(
+            self,
+            xcb::configure_window,
+            xcb::configure_window_checked,
+            id,
+            &args
+        )
+    }
+
+    fn raise_window(&self, id: WinId) -> Result<()> {
+        // xcb docs: https://www.mankier.com/3/xcb_configure_window
+        void_request!(
+            self,
+            xcb::configure_window,
+            xcb::configure_window_checked,
+            id,
+            &[(STACK_MODE, STACK_ABOVE)]
+        )
+    }
+
+    fn send_configure_notify(&self, id: WinId, reg: Region, border: u32) -> Result<()> {
+        let (x, y, w, h) = reg.values();
+        // xcb docs: https://www.mankier.com/3/xcb_configure_notify_event_t
+        let event = xcb::ConfigureNotifyEvent::new(
+            id,            // the window that was reconfigured
+            id,            // the window being notified
+            xcb::NONE,     // sibling that the window is stacked above (none)
+            x as i16,      // x-coordinate
+            y as i16,      // y-coordinate
+            w as u16,      // width
+            h as u16,      // height
+            border as u16, // border width
+            false,         // override redirect
+        );
+        void_request!(
+            self,
+            xcb::send_event,
+            xcb::send_event_checked,
+            false,
+            id,
+            xcb::EVENT_MASK_STRUCTURE_NOTIFY,
+            &event,
+        )
+    }
+
+    fn mark_new_window(&self, id: WinId) -> Result<()> {
+        // xcb docs: https://www.mankier.com/3/xcb_change_window_attributes
+        void_request!(
+            self,
+            xcb::change_window_attributes,
+            xcb::change_window_attributes_checked,
+            id,
+            &[(xcb::CW_EVENT_MASK, NEW_WINDOW_MASK)]
+        )
+    }
+
+    fn map_window(&self, id: WinId) -> Result<()> {
+        void_request!(self, xcb::map_window, xcb::map_window_checked, id)
+    }
+
+    fn unmap_window(&self, id: WinId) -> Result<()> {
+        void_request!(self, xcb::unmap_window, xcb::unmap_window_checked, id)
+    }
+
+    fn send_client_event(&self, id: WinId, atom_name: &str) -> Result<()> {
+        let atom = self.atom(atom_name)?;
+        let wm_protocols = self.known_atom(Atom::WmProtocols);
+        let data = xcb::ClientMessageData::from_data32([atom, xcb::CURRENT_TIME, 0, 0, 0]);
+        let event = xcb::ClientMessageEvent::new(32, id, wm_protocols, data);
+        void_request!(
+            self,
+            xcb::send_event,
+            xcb::send_event_checked,
+            false,
+            id,
+            xcb::EVENT_MASK_NO_EVENT,
+            &event
+        )
+    }
+
+    fn send_key(&self, id: WinId, key: KeyCode) -> Result<()> {
+        let (response_type, mask) = if key.release {
+            (xcb::KEY_RELEASE, xcb::EVENT_MASK_KEY_RELEASE)
+        } else {
+            (xcb::KEY_PRESS, xcb::EVENT_MASK_KEY_PRESS)
+        };
+        // xcb docs: https://www.mankier.com/3/xcb_key_press_event_t
+        let event = xcb::KeyPressEvent::new(
+            response_type,     // press or release
+            key.code,          // the key code
+            xcb::CURRENT_TIME, // time the key was pressed
+            self.root,         // root window of the target
+            id,                // the window the event is reported to
+            xcb::NONE,         // no child window
+            0,                 // root x-coordinate
+            0,                 // root y-coordinate
+            0,                 // event x-coordinate
+            0,                 // event y-coordinate
+            key.mask,          // held modifiers
+            true,              // on the same screen as the root window
+        );
+        void_request!(
+            self,
+            xcb::send_event,
+            xcb::send_event_checked,
+            false,
+            id,
+            mask,
+            &event
+        )
+    }
+
+    fn window_supports_protocol(&self, id: WinId, protocol: &str) -> Result<bool> {
+        let protocol = self.atom(protocol)?;
+
+        // xcb docs: https://www.mankier.com/3/xcb_get_property
+        let cookie = xcb::get_property(
+            &self.conn,                         // xcb connection to X11
+            false,                              // should the property be deleted
+            id,                                 // target window to query
+            self.known_atom(Atom::WmProtocols), // the property we want
+            ATOM_ATOM,                          // the type of the property
+            0,                                  // offset in the property to retrieve data from
+            1024,                               // how many 32bit multiples of data to retrieve
+        );
+
+        let protocols = cookie.get_reply().map_err(xcb_error)?;
+        Ok(protocols.value::<u32>().contains(&protocol))
+    }
+
+    fn kill_client(&self, id: WinId) -> Result<()> {
+        void_request!(self, xcb::kill_client, xcb::kill_client_checked, id)
+    }
+
+    fn focused_client(&self) -> Result<WinId> {
+        // xcb docs: https://www.mankier.com/3/xcb_get_input_focus
+        Ok(xcb::get_input_focus(&self.conn)
+            .get_reply()
+            .map_err(xcb_error)?
+            .focus())
+    }
+
+    fn active_window(&self) -> Result<WinId> {
+        self.atom_prop(self.root, Atom::NetActiveWindow.as_ref())
+    }
+
+    fn focus_client(&self, id: WinId) -> Result<()> {
+        let prop = self.known_atom(Atom::NetActiveWindow);
+        let model = self.focus_model(id)?;
+
+        if matches!(model, FocusModel::Passive | FocusModel::LocallyActive) {
+            // xcb docs: https://www.mankier.com/3/xcb_set_input_focus
+            void_request!(
+                self,
+                xcb::set_input_focus,
+                xcb::set_input_focus_checked,
+                INPUT_FOCUS_PARENT, // focus the parent when focus is lost
+                id,                 // window to focus
+                0,                  // current time to avoid network race conditions
+            )?;
+        }
+
+        let wants_take_focus = matches!(
+            model,
+            FocusModel::LocallyActive | FocusModel::GloballyActive
+        );
+        if wants_take_focus {
+            if let Err(e) = self.send_client_event(id, Atom::WmTakeFocus.as_ref()) {
+                warn!("unable to send WM_TAKE_FOCUS to {}: {}", id, e);
+            }
+        }
+
+        // xcb docs: https://www.mankier.com/3/xcb_change_property
+        void_request!(
+            self,
+            xcb::change_property,
+            xcb::change_property_checked,
+            PROP_MODE_REPLACE, // discard current prop and replace
+            self.root,         // window to change prop on
+            prop,              // prop to change
+            ATOM_WINDOW,       // type of prop
+            32,                // data format (8/16/32-bit)
+            &[id],             // data
+        )
+    }
+
+    fn focus_model(&self, id: WinId) -> Result<FocusModel> {
+        // InputHint: see section 4.1.2.4 of the ICCCM
+        const INPUT_HINT: u32 = 1;
+
+        let cookie = xcb::get_property(
+            &self.conn,                     // xcb connection to X11
+            false,                          // should the property be deleted
+            id,                             // target window to query
+            self.known_atom(Atom::WmHints), // the property we want
+            xcb::xproto::ATOM_WM_HINTS,     // the type of the property
+            0,                              // offset in the property to retrieve data from
+            2,                              // how many 32bit multiples of data to retrieve
+        );
+
+        // Clients that do not set the input hint are assumed to want keyboard input
+        let accepts_input = match cookie.get_reply().map_err(xcb_error)?.value::<u32>() {
+            [flags, input, ..] if flags & INPUT_HINT > 0 => *input != 0,
+            _ => true,
+        };
+        let take_focus = self.window_supports_protocol(id, Atom::WmTakeFocus.as_ref())?;
+
+        Ok(match (accepts_input, take_focus) {
+            (false, false) => FocusModel::NoInput,
+            (true, false) => FocusModel::Passive,
+            (true, true) => FocusModel::LocallyActive,
+            (false, true) => FocusModel::GloballyActive,
+        })
+    }
+
+    fn set_client_border_color(&self, id: WinId, color: u32) -> Result<()> {
+        void_request!(
+            self,
+            xcb::change_window_attributes,
+            xcb::change_window_attributes_checked,
+            id,
+            &[(xcb::CW_BORDER_PIXEL, color)]
+        )
+    }
+
+    fn toggle_client_fullscreen(&self, id: WinId, client_is_fullscreen: bool) -> Result<()> {
+        let state_prop = self.known_atom(Atom::NetWmState);
+        let data = if client_is_fullscreen {
+            0
+        } else {
+            self.known_atom(Atom::NetWmStateFullscreen)
+        };
+
+        void_request!(
+            self,
+            xcb::change_property,
+            xcb::change_property_checked,
+            PROP_MODE_REPLACE, // discard current prop and replace
+            id,                // window to change prop on
+            state_prop,        // prop to change
+            ATOM_ATOM,         // type of prop
+            32,                // data format (8/16/32-bit)
+            &[data],           // data
+        )
+    }
+
+    fn grab_keys(&self, key_bindings: &KeyBindings, mouse_bindings: &MouseBindings) -> Result<()> {
+        // We need to explicitly grab NumLock as an additional modifier and then drop it later on
+        // when we are passing events through to the WindowManager as NumLock alters the modifier
+        // mask when it is active.
+        let modifiers = &[0, NUMLOCK_MASK];
+
+        // xcb docs: https://www.mankier.com/3/xcb_ungrab_key
+        void_request!(
+            self,
+            xcb::ungrab_key,
+            xcb::ungrab_key_checked,
+            GRAB_ANY,     // release all keys
+            self.root,    // the window the keys were grabbed on
+            MOD_MASK_ANY, // with any modifiers
+        )?;
+        // xcb docs: https://www.mankier.com/3/xcb_ungrab_button
+        void_request!(
+            self,
+            xcb::ungrab_button,
+            xcb::ungrab_button_checked,
+            BUTTON_INDEX_ANY, // release all buttons
+            self.root,        // the window the buttons were grabbed on
+            MOD_MASK_ANY,     // with any modifiers
+        )?;
+
+        for m in modifiers.iter() {
+            for k in key_bindings.keys() {
+                // xcb docs: https://www.mankier.com/3/xcb_grab_key
+                void_request!(
+                    self,
+                    xcb::grab_key,
+                    xcb::grab_key_checked,
+                    false,           // don't pass grabbed events through to the client
+                    self.root,       // the window to grab: in this case the root window
+                    k.mask | m,      // modifiers to grab
+                    k.code,          // keycode to grab
+                    GRAB_MODE_ASYNC, // don't lock pointer input while grabbing
+                    GRAB_MODE_ASYNC, // don't lock keyboard input while grabbing
+                )?;
+            }
+
+            for (_, state) in mouse_bindings.keys() {
+                // xcb docs: https://www.mankier.com/3/xcb_grab_button
+                void_request!(
+                    self,
+                    xcb::grab_button,
+                    xcb::grab_button_checked,
+                    false,            // don't pass grabbed events through to the client
+                    self.root,        // the window to grab: in this case the root window
+                    MOUSE_MASK,       // which events are reported to the client
+                    GRAB_MODE_ASYNC,  // don't lock pointer input while grabbing
+                    GRAB_MODE_ASYNC,  // don't lock keyboard input while grabbing
+                    xcb::NONE,        // don't confine the cursor to a specific window
+                    xcb::NONE,        // don't change the cursor type
+                    state.button(),   // the button to grab
+                    state.mask() | m, // modifiers to grab
+                )?;
+            }
+        }
+
+        // xcb docs: https://www.mankier.com/3/xcb_change_window_attributes
+        void_request!(
+            self,
+            xcb::change_window_attributes,
+            xcb::change_window_attributes_checked,
+            self.root,
+            &[(xcb::CW_EVENT_MASK, EVENT_MASK)]
+        )?;
+        self.flush()
+    }
+
+    fn keyboard_mapping(&self) -> Result<KeyboardMapping> {
+        xcb_keyboard_mapping(&self.conn)
+    }
+
+    fn grab_pointer(&self) -> Result<()> {
+        // xcb docs: https://www.mankier.com/3/xcb_grab_pointer
+        let cookie = xcb::grab_pointer(
+            &self.conn,        // xcb connection to X11
+            false,             // don't pass grabbed events through to the client
+            self.root,         // the window to grab: in this case the root window
+            MOUSE_MASK,        // which events are reported to the client
+            GRAB_MODE_ASYNC,   // don't lock pointer input while grabbing
+            GRAB_MODE_ASYNC,   // don't lock keyboard input while grabbing
+            xcb::NONE,         // don't confine the cursor to a specific window
+            xcb::NONE,         // don't change the cursor type
+            xcb::CURRENT_TIME, // time the grab was requested
+        );
+        cookie.get_reply().map_err(xcb_error)?;
+        Ok(())
+    }
+
+    fn ungrab_pointer(&self) -> Result<()> {
+        // xcb docs: https://www.mankier.com/3/xcb_ungrab_pointer
+        void_request!(
+            self,
+            xcb::ungrab_pointer,
+            xcb::ungrab_pointer_checked,
+            xcb::CURRENT_TIME
+        )
+    }
+
+    fn grab_keyboard(&self) -> Result<()> {
+        // xcb docs: https://www.mankier.com/3/xcb_grab_keyboard
+        let reply = xcb::grab_keyboard(
+            &self.conn,        // xcb connection to X11
+            false,             // don't pass grabbed events through to the client
+            self.root,         // the window to grab: in this case the root window
+            xcb::CURRENT_TIME, // time the grab was requested
+            GRAB_MODE_ASYNC,   // don't lock pointer input while grabbing
+            GRAB_MODE_ASYNC,   // don't lock keyboard input while grabbing
+        )
+        .get_reply()
+        .map_err(xcb_error)?;
+
+        if reply.status() == xcb::GRAB_STATUS_SUCCESS as u8 {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "unable to grab the keyboard: status {}",
+                reply.status()
+            ))
+        }
+    }
+
+    fn ungrab_keyboard(&self) -> Result<()> {
+        // xcb docs: https://www.mankier.com/3/xcb_ungrab_keyboard
+        void_request!(
+            self,
+            xcb::ungrab_keyboard,
+            xcb::ungrab_keyboard_checked,
+            xcb::CURRENT_TIME
+        )
+    }
+
+    fn set_wm_properties(&self, workspaces: &[&str]) -> Result<()> {
+        // xcb docs: https://www.mankier.com/3/xcb_change_property
+        void_request!(
+            self,
+            xcb::change_property,
+            xcb::change_property_checked,
+            PROP_MODE_REPLACE, // discard current prop and replace
+            self.check_win,    // window to change prop on
+            self.known_atom(Atom::NetSupportingWmCheck), // prop to change
+            ATOM_WINDOW,       // type of prop
+            32,                // data format (8/16/32-bit)
+            &[self.check_win], // data
+        )?;
+        void_request!(
+            self,
+            xcb::change_property,
+            xcb::change_property_checked,
+            PROP_MODE_REPLACE,                 // discard current prop and replace
+            self.check_win,                    // window to change prop on
+            self.known_atom(Atom::NetWmName),  // prop to change
+            self.known_atom(Atom::UTF8String), // type of prop
+            8,                                 // data format (8/16/32-bit)
+            WM_NAME.as_bytes(),                // data
+        )?;
+        void_request!(
+            self,
+            xcb::change_property,
+            xcb::change_property_checked,
+            PROP_MODE_REPLACE, // discard current prop and replace
+            self.root,         // window to change prop on
+            self.known_atom(Atom::NetSupportingWmCheck), // prop to change
+            ATOM_WINDOW,       // type of prop
+            32,                // data format (8/16/32-bit)
+            &[self.check_win], // data
+        )?;
+        void_request!(
+            self,
+            xcb::change_property,
+            xcb::change_property_checked,
+            PROP_MODE_REPLACE,                 // discard current prop and replace
+            self.root,                         // window to change prop on
+            self.known_atom(Atom::NetWmName),  // prop to change
+            self.known_atom(Atom::UTF8String), // type of prop
+            8,                                 // data format (8/16/32-bit)
+            WM_NAME.as_bytes(),                // data
+        )?;
+
+        // EWMH support
+        let supported: Vec<u32> = Atom::iter().map(|a| self.known_atom(a)).collect();
+        void_request!(
+            self,
+            xcb::change_property,
+            xcb::change_property_checked,
+            PROP_MODE_REPLACE,                   // discard current prop and replace
+            self.root,                           // window to change prop on
+            self.known_atom(Atom::NetSupported), // prop to change
+            ATOM_ATOM,                           // type of prop
+            32,                                  // data format (8/16/32-bit)
+            &supported,                          // data
+        )?;
+        self.update_desktops(workspaces)?;
+        void_request!(
+            self,
+            xcb::delete_property,
+            xcb::delete_property_checked,
+            self.root,
+            self.known_atom(Atom::NetClientList)
+        )?;
+        void_request!(
+            self,
+            xcb::delete_property,
+            xcb::delete_property_checked,
+            self.root,
+            self.known_atom(Atom::NetClientListStacking),
+        )
+    }
+
+    fn update_desktops(&self, workspaces: &[&str]) -> Result<()> {
+        void_request!(
+            self,
+            xcb::change_property,
+            xcb::change_property_checked,
+            PROP_MODE_REPLACE, // discard current prop and replace
+            self.root,         // window to change prop on
+            self.known_atom(Atom::NetNumberOfDesktops), // prop to change
+            ATOM_CARDINAL,     // type of prop
+            32,                // data format (8/16/32-bit)
+            &[workspaces.len() as u32], // data
+        )?;
+        void_request!(
+            self,
+            xcb::change_property,
+            xcb::change_property_checked,
+            PROP_MODE_REPLACE, // discard current prop and replace
+            self.root,         // window to change prop on
+            self.known_atom(Atom::NetDesktopNames), // prop to change
+            self.known_atom(Atom::UTF8String), // type of prop
+            8,                 // data format (8/16/32-bit)
+            workspaces.join("\0").as_bytes(), // data
+        )
+    }
+
+    fn update_client_list(&self, clients: &[WinId], stacking: &[WinId]) -> Result<()> {
+        void_request!(
+            self,
+            xcb::change_property,
+            xcb::change_property_checked,
+            PROP_MODE_REPLACE, // discard current prop and replace
+            self.root,         // window to change prop on
+            self.known_atom(Atom::NetClientList), // prop to change
+            ATOM_WINDOW,       // type of prop
+            32,                // data format (8/16/32-bit)
+            clients,           // data
+        )?;
+        void_request!(
+            self,
+            xcb::change_property,
+            xcb::change_property_checked,
+            PROP_MODE_REPLACE, // discard current prop and replace
+            self.root,         // window to change prop on
+            self.known_atom(Atom::NetClientListStacking), // prop to change
+            ATOM_WINDOW,       // type of prop
+            32,                // data format (8/16/32-bit)
+            stacking,          // data
+        )
+    }
+
+    fn set_current_workspace(&self, wix: usize) -> Result<()> {
+        void_request!(
+            self,
+            xcb::change_property,
+            xcb::change_property_checked,
+            PROP_MODE_REPLACE, // discard current prop and replace
+            self.root,         // window to change prop on
+            self.known_atom(Atom::NetCurrentDesktop), // prop to change
+            xcb::xproto::ATOM_CARDINAL, // type of prop
+            32,                // data format (8/16/32-bit)
+            &[wix as u32],     // data
+        )
+    }
+
+    fn set_root_window_name(&self, name: &str) -> Result<()> {
+        void_request!(
+            self,
+            xcb::change_property,
+            xcb::change_property_checked,
+            PROP_MODE_REPLACE,                 // discard current prop and replace
+            self.root,                         // window to change prop on
+            self.known_atom(Atom::WmName),     // prop to change
+            self.known_atom(Atom::UTF8String), // type of prop
+            8,                                 // data format (8/16/32-bit)
+            name.as_bytes(),                   // data
+        )
+    }
+
+    fn set_client_workspace(&self, id: WinId, wix: usize) -> Result<()> {
+        void_request!(
+            self,
+            xcb::change_property,
+            xcb::change_property_checked,
+            PROP_MODE_REPLACE,                   // discard current prop and replace
+            id,                                  // window to change prop on
+            self.known_atom(Atom::NetWmDesktop), // prop to change
+            xcb::xproto::ATOM_CARDINAL,          // type of prop
+            32,                                  // data format (8/16/32-bit)
+            &[wix as u32],                       // data
+        )
+    }
+
+    fn set_client_state(&self, id: WinId, state: WindowState) -> Result<()> {
+        let state = match state {
+            WindowState::Withdrawn => 0,
+            WindowState::Normal => 1,
+            WindowState::Iconic => 3,
+        };
+
+        void_request!(
+            self,
+            xcb::change_property,
+            xcb::change_property_checked,
+            PROP_MODE_REPLACE,              // discard current prop and replace
+            id,                             // window to change prop on
+            self.known_atom(Atom::WmState), // prop to change
+            self.known_atom(Atom::WmState), // type of prop
+            32,                             // data format (8/16/32-bit)
+            &[state, xcb::NONE],            // data
+        )
+    }
+
+    fn window_should_float(&self, id: WinId, floating_classes: &[&str]) -> Result<bool> {
+        let class = self.str_prop(id, Atom::WmClass.as_ref())?;
+        if class.split('\0').any(|c| floating_classes.contains(&c)) {
+            return Ok(true);
+        }
+
+        let types = self.str_prop(id, Atom::NetWmWindowType.as_ref())?;
+        Ok(types
+            .split('\0')
+            .any(|t| self.auto_float_types.contains(&t)))
+    }
+
+    fn window_is_dock(&self, id: WinId) -> Result<bool> {
+        let dock_types: Vec<u32> = UNMANAGED_WINDOW_TYPES
+            .iter()
+            .map(|&t| self.known_atom(t))
+            .collect();
+
+        self.window_has_type_in(id, &dock_types)
+    }
+
+    fn window_strut(&self, id: WinId) -> Result<Strut> {
+        for &atom in &[Atom::NetWmStrutPartial, Atom::NetWmStrut] {
+            let cookie = xcb::get_property(
+                &self.conn,            // xcb connection to X11
+                false,                 // should the property be deleted
+                id,                    // target window to query
+                self.known_atom(atom), // the property we want
+                ATOM_CARDINAL,         // the type of the property
+                0,                     // offset in the property to retrieve data from
+                12,                    // how many 32bit multiples of data to retrieve
+            );
+
+            let reply = cookie.get_reply()?;
+            let v: &[u32] = reply.value();
+            if v.len() == 12 {
+                return Ok(Strut {
+                    left: v[0],
+                    right: v[1],
+                    top: v[2],
+                    bottom: v[3],
+                    left_y: (v[4], v[5]),
+                    right_y: (v[6], v[7]),
+                    top_x: (v[8], v[9]),
+                    bottom_x: (v[10], v[11]),
+                });
+            } else if v.len() == 4 {
+                // _NET_WM_STRUT covers the full length of each edge
+                let full = (0, u32::MAX);
+                return Ok(Strut {
+                    left: v[0],
+                    right: v[1],
+                    top: v[2],
+                    bottom: v[3],
+                    left_y: full,
+                    right_y: full,
+                    top_x: full,
+                    bottom_x: full,
+                });
+            }
+        }
+
+        Err(anyhow!("no strut set for id: {}", id))
+    }
+
+    fn window_is_urgent(&self, id: WinId) -> Result<bool> {
+        // XUrgencyHint: see section 4.1.2.4 of the ICCCM
+        const URGENCY_HINT: u32 = 1 << 8;
+
+        let cookie = xcb::get_property(
+            &self.conn,                     // xcb connection to X11
+            false,                          // should the property be deleted
+            id,                             // target window to query
+            self.known_atom(Atom::WmHints), // the property we want
+            xcb::xproto::ATOM_WM_HINTS,     // the type of the property
+            0,                              // offset in the property to retrieve data from
+            1,                              // how many 32bit multiples of data to retrieve
+        );
+        let hints = cookie.get_reply().map_err(xcb_error)?;
+        let hint_set =
+            matches!(hints.value::<u32>().first(), Some(flags) if flags & URGENCY_HINT > 0);
+
+        Ok(hint_set || self.window_has_state(id, Atom::NetWmStateDemandsAttention)?)
+    }
+
+    fn window_geometry(&self, id: WinId) -> Result<Region> {
+        let res = xcb::get_geometry(&self.conn, id)
+            .get_reply()
+            .map_err(xcb_error)?;
+        Ok(Region::new(
+            res.x() as u32,
+            res.y() as u32,
+            res.width() as u32,
+            res.height() as u32,
+        ))
+    }
+
+    fn size_hints(&self, id: WinId) -> Result<SizeHints> {
+        let cookie = xcb::get_property(
+            &self.conn,                           // xcb connection to X11
+            false,                                // should the property be deleted
+            id,                                   // target window to query
+            self.known_atom(Atom::WmNormalHints), // the property we want
+            xcb::xproto::ATOM_WM_SIZE_HINTS,      // the type of the property
+            0,                                    // offset in the property to retrieve data from
+            18,                                   // how many 32bit multiples of data to retrieve
+        );
+
+        let reply = cookie.get_reply()?;
+        let v: &[u32] = reply.value();
+        SizeHints::from_wm_normal_hints(v)
+            .ok_or_else(|| anyhow!("WM_NORMAL_HINTS was not set for id: {}", id))
+    }
+
+    fn warp_cursor(&self, win_id: Option<WinId>, screen: &Screen) -> Result<()> {
+        let (x, y, id) = match win_id {
+            Some(id) => {
+                let (_, _, w, h) = self.window_geometry(id)?.values();
+                ((w / 2) as i16, (h / 2) as i16, id)
+            }
+            None => {
+                let (x, y, w, h) = screen.region(true).values();
+                ((x + w / 2) as i16, (y + h / 2) as i16, self.root)
+            }
+        };
+
+        void_request!(
+            self,
+            xcb::warp_pointer,
+            xcb::warp_pointer_checked,
+            0,  // source window
+            id, // destination window
+            0,  // source x
+            0,  // source y
+            0,  // source width
+            0,  // source height
+            x,  // destination x
+            y,  // destination y
+        )
+    }
+
+    fn query_for_active_windows(&self) -> Result<Vec<WinId>> {
+        // xcb docs: https://www.mankier.com/3/xcb_query_tree
+        let all_ids: Vec<WinId> = xcb::query_tree(&self.conn, self.root)
+            .get_reply()
+            .map_err(xcb_error)?
+            .children()
+            .into();
+
+        Ok(all_ids
+            .into_iter()
+            .filter(
+                |&id| match xcb::get_window_attributes(&self.conn, id).get_reply() {
+                    Ok(attrs) => {
+                        !attrs.override_redirect()
+                            && attrs.map_state() == xcb::MAP_STATE_VIEWABLE as u8
+                    }
+                    Err(_) => false,
+                },
+            )
+            .collect())
+    }
+
+    fn str_prop(&self, id: u32, name: &str) -> Result<String> {
+        // xcb docs: https://www.mankier.com/3/xcb_get_property
+        let cookie = xcb::get_property(
+            &self.conn,       // xcb connection to X11
+            false,            // should the property be deleted
+            id,               // target window to query
+            self.atom(name)?, // the property we want
+            xcb::ATOM_ANY,    // the type of the property
+            0,                // offset in the property to retrieve data from
+            1024,             // how many 32bit multiples of data to retrieve
+        );
+
+        Ok(String::from_utf8(
+            cookie.get_reply().map_err(xcb_error)?.value().to_vec(),
+        )?)
+    }
+
+    fn atom_prop(&self, id: u32, name: &str) -> Result<u32> {
+        // xcb docs: https://www.mankier.com/3/xcb_get_property
+        let cookie = xcb::get_property(
+            &self.conn,       // xcb connection to X11
+            false,            // should the property be deleted
+            id,               // target window to query
+            self.atom(name)?, // the property we want
+            xcb::ATOM_ANY,    // the type of the property
+            0,                // offset in the property to retrieve data from
+            1024,             // how many 32bit multiples of data to retrieve
+        );
+
+        let reply = cookie.get_reply()?;
+        if reply.value_len() == 0 {
+            Err(anyhow!("property '{}' was empty for id: {}", name, id))
+        } else {
+            Ok(reply.value()[0])
+        }
+    }
+
+    fn intern_atom(&self, atom: &str) -> Result<u32> {
+        self.atom(atom)
+    }
+
+    fn atom_name(&self, atom: u32) -> Result<String> {
+        self.atoms.name_or_else(atom, || {
+            // xcb docs: https://www.mankier.com/3/xcb_get_atom_name
+            let reply = xcb::xproto::get_atom_name(&self.conn, atom).get_reply()?;
+            Ok(reply.name().to_string())
+        })
+    }
+
+    // - Release all of the keybindings we are holding on to
+    // - destroy the check window
+    // - mark ourselves as no longer being the active root window
+    fn cleanup(&self) -> Result<()> {
+        // xcb docs: https://www.mankier.com/3/xcb_ungrab_key
+        void_request!(
+            self,
+            xcb::ungrab_key,
+            xcb::ungrab_key_checked,
+            xcb::GRAB_ANY as u8,
+            self.root, // the window to ungrab keys for
+            xcb::MOD_MASK_ANY as u16,
+        )?;
+        void_request!(
+            self,
+            xcb::destroy_window,
+            xcb::destroy_window_checked,
+            self.check_win
+        )?;
+        void_request!(
+            self,
+            xcb::delete_property,
+            xcb::delete_property_checked,
+            self.root,
+            self.known_atom(Atom::NetActiveWindow),
+        )
+    }
+}
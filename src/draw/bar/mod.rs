@@ -3,9 +3,12 @@ pub mod statusbar;
 pub mod widgets;
 
 pub use statusbar::{Position, StatusBar};
-pub use widgets::{ActiveWindowName, CurrentLayout, RootWindowName, Text, Workspaces};
+pub use widgets::{
+    ActiveWindowName, CurrentLayout, CurrentMode, RootWindowName, Systray, Text, Workspaces,
+};
 
 use crate::{
+    data_types::WinId,
     draw::{Color, Draw, DrawContext, TextStyle},
     hooks::Hook,
     Result,
@@ -45,6 +48,13 @@ pub trait Widget: Hook {
      * space will be split evenly between all widgets.
      */
     fn is_greedy(&self) -> bool;
+
+    /**
+     * Called by the status bar before each call to `draw` with the window id of the bar being
+     * rendered, the index of the screen it is shown on and the x offset of this widget within
+     * it. Only needed by widgets that manage X windows of their own.
+     */
+    fn set_position(&mut self, _bar: WinId, _screen: usize, _x: f64) {}
 }
 
 /// Create a default dwm style status bar that displays content pulled from the
@@ -4,9 +4,12 @@ use crate::{
     data_types::{Region, WinId},
     draw::{Color, Draw, DrawContext, Widget, WindowType},
     hooks::Hook,
+    xconnection::XError,
     Result, WindowManager,
 };
 
+use std::time::Duration;
+
 /// The position of a status bar
 pub enum Position {
     /// Top of the screen
@@ -90,6 +93,7 @@ impl<Ctx: DrawContext> StatusBar<Ctx> {
             let extents = self.layout(&mut ctx, w)?;
             let mut x = 0.0;
             for (wd, (w, _)) in self.widgets.iter_mut().zip(extents) {
+                wd.set_position(id, i, x);
                 wd.draw(&mut ctx, self.active_screen, screen_has_focus, w, self.h)?;
                 x += w;
                 ctx.flush();
@@ -180,6 +184,12 @@ impl<Ctx: DrawContext> Hook for StatusBar<Ctx> {
     }
 
     fn screens_updated(&mut self, wm: &mut WindowManager, dimensions: &[Region]) {
+        // widgets need to be notified before the old windows are destroyed so that they can
+        // release anything that has been reparented into them
+        self.widgets
+            .iter_mut()
+            .for_each(|w| w.screens_updated(wm, dimensions));
+
         self.screens
             .iter()
             .for_each(|(id, _)| self.drw.destroy_window(*id));
@@ -187,10 +197,6 @@ impl<Ctx: DrawContext> Hook for StatusBar<Ctx> {
             error!("error removing old status bar windows: {}", e)
         }
 
-        self.widgets
-            .iter_mut()
-            .for_each(|w| w.screens_updated(wm, dimensions));
-
         // always need to redraw when screen sizes change
         match self.redraw() {
             Ok(_) => (),
@@ -209,11 +215,43 @@ impl<Ctx: DrawContext> Hook for StatusBar<Ctx> {
         self.widgets.iter_mut().for_each(|w| w.focus_change(wm, id));
     }
 
+    fn floating_change(&mut self, wm: &mut WindowManager, id: WinId, floating: bool) {
+        self.widgets
+            .iter_mut()
+            .for_each(|w| w.floating_change(wm, id, floating));
+    }
+
+    fn client_urgent(&mut self, wm: &mut WindowManager, id: WinId) {
+        self.widgets
+            .iter_mut()
+            .for_each(|w| w.client_urgent(wm, id));
+    }
+
+    fn x_error(&mut self, wm: &mut WindowManager, err: &XError) {
+        self.widgets.iter_mut().for_each(|w| w.x_error(wm, err));
+    }
+
+    fn key_chord_pending(&mut self, wm: &mut WindowManager, pending: Option<&str>) {
+        self.widgets
+            .iter_mut()
+            .for_each(|w| w.key_chord_pending(wm, pending));
+    }
+
+    fn mode_changed(&mut self, wm: &mut WindowManager, mode: &str) {
+        self.widgets
+            .iter_mut()
+            .for_each(|w| w.mode_changed(wm, mode));
+    }
+
     fn event_handled(&mut self, wm: &mut WindowManager) {
         self.widgets.iter_mut().for_each(|w| w.event_handled(wm));
         self.redraw_if_needed();
     }
 
+    fn poll_interval(&self) -> Option<Duration> {
+        self.widgets.iter().filter_map(|w| w.poll_interval()).min()
+    }
+
     fn startup(&mut self, wm: &mut WindowManager) {
         self.widgets.iter_mut().for_each(|w| w.startup(wm));
         match self.redraw() {
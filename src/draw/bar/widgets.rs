@@ -1,12 +1,16 @@
 //! Built in status bar widgets
 use crate::{
+    bindings::DEFAULT_MODE,
     client::Client,
+    core::helpers::xcb_util,
     data_types::{Region, WinId},
     draw::{Color, DrawContext, TextStyle, Widget},
     hooks::Hook,
     Result, Selector, WindowManager,
 };
 
+use std::time::Duration;
+
 const PADDING: f64 = 3.0;
 
 /// A simple piece of static text with an optional background color.
@@ -508,3 +512,727 @@ impl Widget for CurrentLayout {
         false
     }
 }
+
+/// A simple widget that displays the active key binding mode when it is not the default mode
+pub struct CurrentMode {
+    txt: Text,
+}
+
+impl CurrentMode {
+    /// Create a new CurrentMode widget
+    pub fn new(style: &TextStyle) -> Self {
+        Self {
+            txt: Text::new("", style, false, false),
+        }
+    }
+}
+
+impl Hook for CurrentMode {
+    fn mode_changed(&mut self, _: &mut WindowManager, mode: &str) {
+        if mode == DEFAULT_MODE {
+            self.txt.set_text("");
+        } else {
+            self.txt.set_text(mode);
+        }
+    }
+}
+
+impl Widget for CurrentMode {
+    fn draw(&mut self, ctx: &mut dyn DrawContext, s: usize, f: bool, w: f64, h: f64) -> Result<()> {
+        self.txt.draw(ctx, s, f, w, h)
+    }
+
+    fn current_extent(&mut self, ctx: &mut dyn DrawContext, h: f64) -> Result<(f64, f64)> {
+        self.txt.current_extent(ctx, h)
+    }
+
+    fn require_draw(&self) -> bool {
+        self.txt.require_draw()
+    }
+
+    fn is_greedy(&self) -> bool {
+        false
+    }
+}
+
+// XEmbed and system tray protocol constants
+const SYSTEM_TRAY_REQUEST_DOCK: u32 = 0;
+const XEMBED_EMBEDDED_NOTIFY: u32 = 0;
+const XEMBED_VERSION: u32 = 0;
+const XEMBED_MAPPED: u32 = 1 << 0;
+
+// How often the tray connection is checked for dock requests when the WindowManager is idle
+const TRAY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// The events from the tray connection that the Systray needs to act on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TrayEvent {
+    DockRequest(WinId),
+    Destroyed(WinId),
+    Reparented { id: WinId, parent: WinId },
+    XEmbedInfoChanged(WinId),
+    SelectionLost,
+}
+
+// The X requests made by the Systray, kept separate from the layout of icons in the bar so that
+// the tray logic can be run against a mock connection.
+trait TrayConn {
+    fn root(&self) -> WinId;
+    fn poll_for_event(&self) -> Option<TrayEvent>;
+    // The flags from an icon's _XEMBED_INFO property, or None if it has not been set
+    fn xembed_flags(&self, id: WinId) -> Option<u32>;
+    fn embed(&self, id: WinId, parent: WinId, bg: u32);
+    fn reparent(&self, id: WinId, parent: WinId);
+    fn release(&self, id: WinId);
+    fn position(&self, id: WinId, x: u32, y: u32, size: u32);
+    fn map(&self, id: WinId);
+    fn unmap(&self, id: WinId);
+    fn release_selection(&self);
+    fn flush(&self);
+}
+
+struct XcbTrayConn {
+    conn: xcb::Connection,
+    root: WinId,
+    win: WinId,
+    selection: u32,
+    opcode: u32,
+    xembed: u32,
+    xembed_info: u32,
+}
+
+impl XcbTrayConn {
+    fn try_new() -> Result<Self> {
+        let (conn, screen_num) = xcb::Connection::connect(None)?;
+        let root = conn
+            .get_setup()
+            .roots()
+            .nth(screen_num as usize)
+            .ok_or_else(|| anyhow!("unable to get handle for screen"))?
+            .root();
+
+        let selection = xcb_util::intern_atom(&conn, &format!("_NET_SYSTEM_TRAY_S{}", screen_num))?;
+        let opcode = xcb_util::intern_atom(&conn, "_NET_SYSTEM_TRAY_OPCODE")?;
+        let orientation = xcb_util::intern_atom(&conn, "_NET_SYSTEM_TRAY_ORIENTATION")?;
+        let xembed = xcb_util::intern_atom(&conn, "_XEMBED")?;
+        let xembed_info = xcb_util::intern_atom(&conn, "_XEMBED_INFO")?;
+        let manager = xcb_util::intern_atom(&conn, "MANAGER")?;
+
+        // The selection owner window is never shown
+        let win = conn.generate_id();
+        xcb::create_window(
+            &conn,
+            xcb::COPY_FROM_PARENT as u8,
+            win,
+            root,
+            -1,
+            -1,
+            1,
+            1,
+            0,
+            xcb::WINDOW_CLASS_INPUT_ONLY as u16,
+            xcb::COPY_FROM_PARENT,
+            &[(xcb::CW_EVENT_MASK, xcb::EVENT_MASK_STRUCTURE_NOTIFY)],
+        );
+        xcb::change_property(
+            &conn,                        // xcb connection to X11
+            xcb::PROP_MODE_REPLACE as u8, // discard current prop and replace
+            win,                          // window to change prop on
+            orientation,                  // prop to change
+            xcb::ATOM_CARDINAL,           // type of prop
+            32,                           // data format (8/16/32-bit)
+            &[0u32],                      // data: _NET_SYSTEM_TRAY_ORIENTATION_HORZ
+        );
+
+        xcb::set_selection_owner(&conn, win, selection, xcb::CURRENT_TIME);
+        if xcb::get_selection_owner(&conn, selection)
+            .get_reply()?
+            .owner()
+            != win
+        {
+            xcb::destroy_window(&conn, win);
+            return Err(anyhow!(
+                "unable to take ownership of the system tray selection"
+            ));
+        }
+
+        // Let any running tray icons know that there is a new tray to dock with
+        let data = xcb::ClientMessageData::from_data32([xcb::CURRENT_TIME, selection, win, 0, 0]);
+        let event = xcb::ClientMessageEvent::new(32, root, manager, data);
+        xcb::send_event(&conn, false, root, xcb::EVENT_MASK_STRUCTURE_NOTIFY, &event);
+        conn.flush();
+
+        Ok(Self {
+            conn,
+            root,
+            win,
+            selection,
+            opcode,
+            xembed,
+            xembed_info,
+        })
+    }
+}
+
+impl TrayConn for XcbTrayConn {
+    fn root(&self) -> WinId {
+        self.root
+    }
+
+    fn poll_for_event(&self) -> Option<TrayEvent> {
+        while let Some(event) = self.conn.poll_for_event() {
+            let tray_event = match event.response_type() & !0x80 {
+                xcb::CLIENT_MESSAGE => {
+                    let e: &xcb::ClientMessageEvent = unsafe { xcb::cast_event(&event) };
+                    let data = e.data().data32();
+                    if e.type_() == self.opcode && data[1] == SYSTEM_TRAY_REQUEST_DOCK {
+                        Some(TrayEvent::DockRequest(data[2]))
+                    } else {
+                        None
+                    }
+                }
+
+                xcb::DESTROY_NOTIFY => {
+                    let e: &xcb::DestroyNotifyEvent = unsafe { xcb::cast_event(&event) };
+                    Some(TrayEvent::Destroyed(e.window()))
+                }
+
+                xcb::REPARENT_NOTIFY => {
+                    let e: &xcb::ReparentNotifyEvent = unsafe { xcb::cast_event(&event) };
+                    Some(TrayEvent::Reparented {
+                        id: e.window(),
+                        parent: e.parent(),
+                    })
+                }
+
+                xcb::PROPERTY_NOTIFY => {
+                    let e: &xcb::PropertyNotifyEvent = unsafe { xcb::cast_event(&event) };
+                    if e.atom() == self.xembed_info {
+                        Some(TrayEvent::XEmbedInfoChanged(e.window()))
+                    } else {
+                        None
+                    }
+                }
+
+                xcb::SELECTION_CLEAR => {
+                    let e: &xcb::SelectionClearEvent = unsafe { xcb::cast_event(&event) };
+                    if e.selection() == self.selection {
+                        Some(TrayEvent::SelectionLost)
+                    } else {
+                        None
+                    }
+                }
+
+                _ => None,
+            };
+
+            if tray_event.is_some() {
+                return tray_event;
+            }
+        }
+
+        None
+    }
+
+    fn xembed_flags(&self, id: WinId) -> Option<u32> {
+        // _XEMBED_INFO is two CARD32 values: the protocol version followed by the flags
+        let cookie = xcb::get_property(
+            &self.conn,       // xcb connection to X11
+            false,            // should the property be deleted
+            id,               // target window to query
+            self.xembed_info, // the property we want
+            xcb::ATOM_ANY,    // the type of the property
+            0,                // offset in the property to retrieve data from
+            2,                // how many 32bit multiples of data to retrieve
+        );
+
+        match cookie.get_reply() {
+            Ok(r) if r.format() == 32 && r.value_len() >= 2 => Some(r.value::<u32>()[1]),
+            _ => None,
+        }
+    }
+
+    fn embed(&self, id: WinId, parent: WinId, bg: u32) {
+        xcb::change_window_attributes(
+            &self.conn,
+            id,
+            &[
+                (xcb::CW_BACK_PIXEL, bg),
+                (
+                    xcb::CW_EVENT_MASK,
+                    xcb::EVENT_MASK_STRUCTURE_NOTIFY | xcb::EVENT_MASK_PROPERTY_CHANGE,
+                ),
+            ],
+        );
+
+        // Make sure that icons survive us exiting unexpectedly
+        xcb::change_save_set(&self.conn, xcb::SET_MODE_INSERT as u8, id);
+        if parent != self.root {
+            xcb::reparent_window(&self.conn, id, parent, 0, 0);
+        }
+
+        let data = xcb::ClientMessageData::from_data32([
+            xcb::CURRENT_TIME,
+            XEMBED_EMBEDDED_NOTIFY,
+            0,
+            parent,
+            XEMBED_VERSION,
+        ]);
+        let event = xcb::ClientMessageEvent::new(32, id, self.xembed, data);
+        xcb::send_event(&self.conn, false, id, xcb::EVENT_MASK_NO_EVENT, &event);
+        self.conn.flush();
+    }
+
+    fn reparent(&self, id: WinId, parent: WinId) {
+        xcb::reparent_window(&self.conn, id, parent, 0, 0);
+    }
+
+    fn release(&self, id: WinId) {
+        xcb::change_save_set(&self.conn, xcb::SET_MODE_DELETE as u8, id);
+    }
+
+    fn position(&self, id: WinId, x: u32, y: u32, size: u32) {
+        xcb::configure_window(
+            &self.conn,
+            id,
+            &[
+                (xcb::CONFIG_WINDOW_X as u16, x),
+                (xcb::CONFIG_WINDOW_Y as u16, y),
+                (xcb::CONFIG_WINDOW_WIDTH as u16, size),
+                (xcb::CONFIG_WINDOW_HEIGHT as u16, size),
+            ],
+        );
+    }
+
+    fn map(&self, id: WinId) {
+        xcb::map_window(&self.conn, id);
+    }
+
+    fn unmap(&self, id: WinId) {
+        xcb::unmap_window(&self.conn, id);
+    }
+
+    fn release_selection(&self) {
+        xcb::set_selection_owner(&self.conn, xcb::NONE, self.selection, xcb::CURRENT_TIME);
+        xcb::destroy_window(&self.conn, self.win);
+        self.conn.flush();
+    }
+
+    fn flush(&self) {
+        self.conn.flush();
+    }
+}
+
+// A docked icon along with whether it has asked to be shown through _XEMBED_INFO
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TrayIcon {
+    id: WinId,
+    mapped: bool,
+}
+
+/**
+ * An XEmbed system tray following the freedesktop.org system tray specification.
+ *
+ * The Systray takes ownership of the _NET_SYSTEM_TRAY_S{n} selection for the X screen it is
+ * created on and embeds any icons that request to be docked into the status bar window shown
+ * on `screen`. Icons are placed right to left in the order that they were docked and are only
+ * shown while they have the XEMBED_MAPPED flag set in their _XEMBED_INFO property (icons that
+ * do not set _XEMBED_INFO at all are always shown).
+ *
+ * NOTE: The Systray uses its own connection to the X server which is checked for new dock
+ * requests each time the WindowManager handles an event, and at least every 100ms when there
+ * are no other events.
+ */
+pub struct Systray {
+    conn: Box<dyn TrayConn>,
+    icons: Vec<TrayIcon>,
+    screen: usize,
+    bar: Option<(WinId, f64)>,
+    icon_size: u32,
+    spacing: u32,
+    bg: Color,
+    require_draw: bool,
+}
+
+impl Systray {
+    /**
+     * Create a new Systray showing icons of `icon_size` pixels in the status bar on `screen`.
+     * Fails if we are unable to connect to the X server or if another system tray is already
+     * running.
+     */
+    pub fn try_new(
+        screen: usize,
+        icon_size: u32,
+        spacing: u32,
+        bg: impl Into<Color>,
+    ) -> Result<Self> {
+        let conn = XcbTrayConn::try_new()?;
+        Ok(Self::new(
+            Box::new(conn),
+            screen,
+            icon_size,
+            spacing,
+            bg.into(),
+        ))
+    }
+
+    fn new(
+        conn: Box<dyn TrayConn>,
+        screen: usize,
+        icon_size: u32,
+        spacing: u32,
+        bg: Color,
+    ) -> Self {
+        Self {
+            conn,
+            icons: vec![],
+            screen,
+            bar: None,
+            icon_size,
+            spacing,
+            bg,
+            require_draw: true,
+        }
+    }
+
+    fn process_events(&mut self) {
+        while let Some(event) = self.conn.poll_for_event() {
+            match event {
+                TrayEvent::DockRequest(id) => self.dock(id),
+                TrayEvent::Destroyed(id) => self.remove_icon(id),
+
+                // Icons that reparent themselves elsewhere are no longer part of the tray. We
+                // hand icons back to the root window ourselves when the bar is recreated.
+                TrayEvent::Reparented { id, parent } => {
+                    if parent != self.conn.root() && self.bar.map(|(bar, _)| bar) != Some(parent) {
+                        self.remove_icon(id);
+                    }
+                }
+
+                TrayEvent::XEmbedInfoChanged(id) => self.update_mapped_state(id),
+
+                // Another tray has taken over
+                TrayEvent::SelectionLost => self.release_icons(),
+            }
+        }
+    }
+
+    fn wants_mapping(&self, id: WinId) -> bool {
+        match self.conn.xembed_flags(id) {
+            Some(flags) => flags & XEMBED_MAPPED != 0,
+            None => true,
+        }
+    }
+
+    fn dock(&mut self, id: WinId) {
+        if self.icons.iter().any(|i| i.id == id) {
+            return;
+        }
+
+        let (r, g, b) = self.bg.rgb();
+        let pixel = (((r * 255.0) as u32) << 16) | (((g * 255.0) as u32) << 8) | (b * 255.0) as u32;
+        let parent = self.bar.map_or(self.conn.root(), |(bar, _)| bar);
+        self.conn.embed(id, parent, pixel);
+
+        // Checked after embedding so that later changes are seen as property notify events
+        let mapped = self.wants_mapping(id);
+        self.icons.push(TrayIcon { id, mapped });
+        self.require_draw = true;
+    }
+
+    fn update_mapped_state(&mut self, id: WinId) {
+        if !self.icons.iter().any(|i| i.id == id) {
+            return;
+        }
+
+        let mapped = self.wants_mapping(id);
+        if let Some(icon) = self.icons.iter_mut().find(|i| i.id == id) {
+            if icon.mapped != mapped {
+                icon.mapped = mapped;
+                if !mapped {
+                    self.conn.unmap(id);
+                    self.conn.flush();
+                }
+                self.require_draw = true;
+            }
+        }
+    }
+
+    fn remove_icon(&mut self, id: WinId) {
+        if self.icons.iter().any(|i| i.id == id) {
+            self.icons.retain(|i| i.id != id);
+            self.require_draw = true;
+        }
+    }
+
+    // Hand icons back to the root window so they are not destroyed along with the bar window
+    fn reparent_to_root(&mut self) {
+        let root = self.conn.root();
+        for icon in self.icons.iter() {
+            self.conn.unmap(icon.id);
+            self.conn.reparent(icon.id, root);
+        }
+        self.bar = None;
+        self.conn.flush();
+    }
+
+    fn release_icons(&mut self) {
+        self.reparent_to_root();
+        for icon in self.icons.iter() {
+            self.conn.release(icon.id);
+        }
+        self.icons.clear();
+        self.conn.flush();
+    }
+
+    fn visible_icons(&self) -> impl Iterator<Item = &TrayIcon> {
+        self.icons.iter().rev().filter(|i| i.mapped)
+    }
+
+    fn width(&self) -> u32 {
+        match self.visible_icons().count() as u32 {
+            0 => 0,
+            n => n * (self.icon_size + self.spacing) + self.spacing,
+        }
+    }
+
+    fn position_icons(&mut self, h: f64) {
+        let (_, x) = match self.bar {
+            Some(bar) => bar,
+            None => return,
+        };
+
+        let y = (h as u32).saturating_sub(self.icon_size) / 2;
+        let step = self.icon_size + self.spacing;
+        for (i, icon) in self.visible_icons().enumerate() {
+            let ix = x as u32 + self.spacing + i as u32 * step;
+            self.conn.position(icon.id, ix, y, self.icon_size);
+            self.conn.map(icon.id);
+        }
+        self.conn.flush();
+    }
+}
+
+impl Hook for Systray {
+    fn startup(&mut self, _: &mut WindowManager) {
+        self.process_events();
+    }
+
+    fn event_handled(&mut self, _: &mut WindowManager) {
+        self.process_events();
+    }
+
+    fn poll_interval(&self) -> Option<Duration> {
+        Some(TRAY_POLL_INTERVAL)
+    }
+
+    // The status bar windows are about to be replaced
+    fn screens_updated(&mut self, _: &mut WindowManager, _: &[Region]) {
+        self.process_events();
+        self.reparent_to_root();
+        self.require_draw = true;
+    }
+}
+
+impl Widget for Systray {
+    fn draw(&mut self, ctx: &mut dyn DrawContext, _: usize, _: bool, w: f64, h: f64) -> Result<()> {
+        ctx.color(&self.bg);
+        ctx.rectangle(0.0, 0.0, w, h);
+        self.position_icons(h);
+        self.require_draw = false;
+
+        Ok(())
+    }
+
+    fn set_position(&mut self, bar: WinId, screen: usize, x: f64) {
+        if screen != self.screen {
+            return;
+        }
+
+        if self.bar.map(|(id, _)| id) != Some(bar) {
+            for icon in self.icons.iter() {
+                self.conn.reparent(icon.id, bar);
+            }
+        }
+        self.bar = Some((bar, x));
+    }
+
+    fn current_extent(&mut self, _: &mut dyn DrawContext, h: f64) -> Result<(f64, f64)> {
+        Ok((self.width() as f64, h))
+    }
+
+    fn require_draw(&self) -> bool {
+        self.require_draw
+    }
+
+    fn is_greedy(&self) -> bool {
+        false
+    }
+}
+
+impl Drop for Systray {
+    fn drop(&mut self) {
+        self.release_icons();
+        self.conn.release_selection();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        cell::RefCell,
+        collections::{HashMap, VecDeque},
+        rc::Rc,
+    };
+
+    const ROOT: WinId = 1;
+    const BAR: WinId = 2;
+
+    #[derive(Debug, Default)]
+    struct MockTrayState {
+        events: VecDeque<TrayEvent>,
+        flags: HashMap<WinId, u32>,
+        parents: HashMap<WinId, WinId>,
+        positions: HashMap<WinId, (u32, u32)>,
+        mapped: Vec<WinId>,
+    }
+
+    struct MockTrayConn(Rc<RefCell<MockTrayState>>);
+
+    impl TrayConn for MockTrayConn {
+        fn root(&self) -> WinId {
+            ROOT
+        }
+
+        fn poll_for_event(&self) -> Option<TrayEvent> {
+            self.0.borrow_mut().events.pop_front()
+        }
+
+        fn xembed_flags(&self, id: WinId) -> Option<u32> {
+            self.0.borrow().flags.get(&id).copied()
+        }
+
+        fn embed(&self, id: WinId, parent: WinId, _: u32) {
+            self.0.borrow_mut().parents.insert(id, parent);
+        }
+
+        fn reparent(&self, id: WinId, parent: WinId) {
+            self.0.borrow_mut().parents.insert(id, parent);
+        }
+
+        fn release(&self, _: WinId) {}
+
+        fn position(&self, id: WinId, x: u32, y: u32, _: u32) {
+            self.0.borrow_mut().positions.insert(id, (x, y));
+        }
+
+        fn map(&self, id: WinId) {
+            let mut state = self.0.borrow_mut();
+            if !state.mapped.contains(&id) {
+                state.mapped.push(id);
+            }
+        }
+
+        fn unmap(&self, id: WinId) {
+            self.0.borrow_mut().mapped.retain(|&i| i != id);
+        }
+
+        fn release_selection(&self) {}
+
+        fn flush(&self) {}
+    }
+
+    // 16px icons with 2px spacing, starting at x=100 in an 20px high bar
+    fn tray_in_bar(events: Vec<TrayEvent>) -> (Systray, Rc<RefCell<MockTrayState>>) {
+        let state = Rc::new(RefCell::new(MockTrayState {
+            events: events.into(),
+            ..Default::default()
+        }));
+        let conn = MockTrayConn(Rc::clone(&state));
+        let mut tray = Systray::new(Box::new(conn), 0, 16, 2, Color::from(0x000000ff));
+        tray.set_position(BAR, 0, 100.0);
+
+        (tray, state)
+    }
+
+    fn send(tray: &mut Systray, state: &Rc<RefCell<MockTrayState>>, event: TrayEvent) {
+        state.borrow_mut().events.push_back(event);
+        tray.require_draw = false;
+        tray.process_events();
+    }
+
+    #[test]
+    fn dock_requests_embed_icons_in_the_bar() {
+        let (mut tray, state) = tray_in_bar(vec![
+            TrayEvent::DockRequest(10),
+            TrayEvent::DockRequest(11),
+            TrayEvent::DockRequest(10),
+        ]);
+        tray.process_events();
+        tray.position_icons(20.0);
+
+        let ids: Vec<WinId> = tray.icons.iter().map(|i| i.id).collect();
+        assert_eq!(ids, vec![10, 11]);
+        assert_eq!(tray.width(), 38);
+
+        let state = state.borrow();
+        assert_eq!(state.parents.get(&10), Some(&BAR));
+        assert_eq!(state.parents.get(&11), Some(&BAR));
+        assert_eq!(state.positions.get(&11), Some(&(102, 2)));
+        assert_eq!(state.positions.get(&10), Some(&(120, 2)));
+        assert!(state.mapped.contains(&10) && state.mapped.contains(&11));
+    }
+
+    #[test]
+    fn icons_are_only_shown_while_xembed_mapped_is_set() {
+        let (mut tray, state) =
+            tray_in_bar(vec![TrayEvent::DockRequest(10), TrayEvent::DockRequest(11)]);
+        state.borrow_mut().flags.insert(10, 0);
+        state.borrow_mut().flags.insert(11, XEMBED_MAPPED);
+        tray.process_events();
+        tray.position_icons(20.0);
+
+        assert_eq!(state.borrow().mapped, vec![11]);
+        assert_eq!(state.borrow().positions.get(&11), Some(&(102, 2)));
+        assert_eq!(tray.width(), 20);
+
+        state.borrow_mut().flags.insert(10, XEMBED_MAPPED);
+        send(&mut tray, &state, TrayEvent::XEmbedInfoChanged(10));
+        assert!(tray.require_draw());
+        tray.position_icons(20.0);
+
+        assert!(state.borrow().mapped.contains(&10));
+        assert_eq!(state.borrow().positions.get(&10), Some(&(120, 2)));
+
+        state.borrow_mut().flags.insert(11, 0);
+        send(&mut tray, &state, TrayEvent::XEmbedInfoChanged(11));
+        assert_eq!(state.borrow().mapped, vec![10]);
+        tray.position_icons(20.0);
+
+        assert_eq!(state.borrow().positions.get(&10), Some(&(102, 2)));
+        assert_eq!(tray.width(), 20);
+    }
+
+    #[test]
+    fn destroyed_icons_are_removed_and_the_rest_are_repositioned() {
+        let (mut tray, state) = tray_in_bar(vec![
+            TrayEvent::DockRequest(10),
+            TrayEvent::DockRequest(11),
+            TrayEvent::DockRequest(12),
+        ]);
+        tray.process_events();
+        tray.position_icons(20.0);
+        assert_eq!(state.borrow().positions.get(&10), Some(&(138, 2)));
+
+        send(&mut tray, &state, TrayEvent::Destroyed(11));
+        assert!(tray.require_draw());
+        tray.position_icons(20.0);
+
+        let ids: Vec<WinId> = tray.icons.iter().map(|i| i.id).collect();
+        assert_eq!(ids, vec![10, 12]);
+        assert_eq!(tray.width(), 38);
+        assert_eq!(state.borrow().positions.get(&12), Some(&(102, 2)));
+        assert_eq!(state.borrow().positions.get(&10), Some(&(120, 2)));
+    }
+}
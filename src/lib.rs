@@ -19,15 +19,23 @@ pub use crate::core::client;
 pub use crate::core::data_types;
 pub use crate::core::helpers;
 pub use crate::core::hooks;
+pub use crate::core::keysyms;
 pub use crate::core::layout;
 pub use crate::core::manager;
+pub use crate::core::recording;
 pub use crate::core::screen;
+pub use crate::core::testing;
 pub use crate::core::workspace;
+#[cfg(feature = "x11rb")]
+pub use crate::core::x11rb_connection;
 pub use crate::core::xconnection;
 
 pub use crate::core::ring::{Direction::*, InsertPoint, Selector};
 pub use data_types::{Change::*, Config};
 pub use manager::WindowManager;
+#[cfg(feature = "x11rb")]
+pub use x11rb_connection::X11rbConnection;
+#[cfg(feature = "xcb")]
 pub use xconnection::XcbConnection;
 
 /// A default 'anyhow' based result type
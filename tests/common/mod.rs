@@ -4,6 +4,7 @@ use penrose::{
     layout::*,
     screen::Screen,
     workspace::Workspace,
+    xconnection::{MockXConn, XConn},
     Forward, Selector, WindowManager,
 };
 
@@ -11,13 +12,46 @@ use std::collections::HashMap;
 
 const SCREEN_WIDTH: u32 = 1000;
 const SCREEN_HEIGHT: u32 = 600;
-pub const EXIT_CODE: KeyCode = KeyCode { mask: 0, code: 0 };
-pub const LAYOUT_CHANGE_CODE: KeyCode = KeyCode { mask: 0, code: 1 };
-pub const WORKSPACE_CHANGE_CODE: KeyCode = KeyCode { mask: 0, code: 2 };
-pub const SCREEN_CHANGE_CODE: KeyCode = KeyCode { mask: 0, code: 3 };
-pub const FOCUS_CHANGE_CODE: KeyCode = KeyCode { mask: 0, code: 4 };
-pub const KILL_CLIENT_CODE: KeyCode = KeyCode { mask: 0, code: 5 };
-pub const ADD_WORKSPACE_CODE: KeyCode = KeyCode { mask: 0, code: 6 };
+pub const EXIT_CODE: KeyCode = KeyCode {
+    mask: 0,
+    code: 0,
+    release: false,
+};
+pub const LAYOUT_CHANGE_CODE: KeyCode = KeyCode {
+    mask: 0,
+    code: 1,
+    release: false,
+};
+pub const WORKSPACE_CHANGE_CODE: KeyCode = KeyCode {
+    mask: 0,
+    code: 2,
+    release: false,
+};
+pub const SCREEN_CHANGE_CODE: KeyCode = KeyCode {
+    mask: 0,
+    code: 3,
+    release: false,
+};
+pub const FOCUS_CHANGE_CODE: KeyCode = KeyCode {
+    mask: 0,
+    code: 4,
+    release: false,
+};
+pub const KILL_CLIENT_CODE: KeyCode = KeyCode {
+    mask: 0,
+    code: 5,
+    release: false,
+};
+pub const ADD_WORKSPACE_CODE: KeyCode = KeyCode {
+    mask: 0,
+    code: 6,
+    release: false,
+};
+pub const TOGGLE_FLOATING_CODE: KeyCode = KeyCode {
+    mask: 0,
+    code: 7,
+    release: false,
+};
 
 pub fn simple_screen(n: usize) -> Screen {
     Screen::new(
@@ -31,6 +65,11 @@ pub fn simple_screen(n: usize) -> Screen {
     )
 }
 
+// Known atoms have the same ID for every MockXConn
+pub fn atom(name: &str) -> u32 {
+    MockXConn::new(vec![], vec![]).intern_atom(name).unwrap()
+}
+
 fn layouts() -> Vec<Layout> {
     vec![Layout::new("t", LayoutConf::default(), side_stack, 1, 0.6)]
 }
@@ -39,32 +78,41 @@ pub fn test_bindings() -> KeyBindings {
     let mut bindings = HashMap::new();
     bindings.insert(
         EXIT_CODE,
-        Box::new(|wm: &mut WindowManager| wm.exit()) as FireAndForget,
+        (Box::new(|wm: &mut WindowManager| wm.exit()) as FireAndForget).into(),
     );
     bindings.insert(
         LAYOUT_CHANGE_CODE,
-        Box::new(|wm: &mut WindowManager| wm.cycle_layout(Forward)) as FireAndForget,
+        (Box::new(|wm: &mut WindowManager| wm.cycle_layout(Forward)) as FireAndForget).into(),
     );
     bindings.insert(
         WORKSPACE_CHANGE_CODE,
-        Box::new(|wm: &mut WindowManager| wm.focus_workspace(&Selector::Index(1))) as FireAndForget,
+        (Box::new(|wm: &mut WindowManager| wm.focus_workspace(&Selector::Index(1)))
+            as FireAndForget)
+            .into(),
     );
     bindings.insert(
         ADD_WORKSPACE_CODE,
-        Box::new(|wm: &mut WindowManager| wm.push_workspace(Workspace::new("new", layouts())))
-            as FireAndForget,
+        (Box::new(|wm: &mut WindowManager| wm.push_workspace(Workspace::new("new", layouts())))
+            as FireAndForget)
+            .into(),
     );
     bindings.insert(
         SCREEN_CHANGE_CODE,
-        Box::new(|wm: &mut WindowManager| wm.cycle_screen(Forward)) as FireAndForget,
+        (Box::new(|wm: &mut WindowManager| wm.cycle_screen(Forward)) as FireAndForget).into(),
     );
     bindings.insert(
         FOCUS_CHANGE_CODE,
-        Box::new(|wm: &mut WindowManager| wm.cycle_client(Forward)) as FireAndForget,
+        (Box::new(|wm: &mut WindowManager| wm.cycle_client(Forward)) as FireAndForget).into(),
     );
     bindings.insert(
         KILL_CLIENT_CODE,
-        Box::new(|wm: &mut WindowManager| wm.kill_client()) as FireAndForget,
+        (Box::new(|wm: &mut WindowManager| wm.kill_client()) as FireAndForget).into(),
+    );
+    bindings.insert(
+        TOGGLE_FLOATING_CODE,
+        (Box::new(|wm: &mut WindowManager| wm.toggle_client_floating(&Selector::Focused))
+            as FireAndForget)
+            .into(),
     );
 
     bindings
@@ -2,7 +2,7 @@ use penrose::{
     client::Client,
     data_types::WinId,
     hooks::Hook,
-    xconnection::{MockXConn, XEvent},
+    xconnection::{MockXConn, XError, XErrorKind, XEvent},
     {Config, WindowManager},
 };
 
@@ -63,6 +63,14 @@ impl Hook for TestHook {
         self.mark_called("focus_change");
     }
 
+    fn floating_change(&mut self, _: &mut WindowManager, _: WinId, _: bool) {
+        self.mark_called("floating_change");
+    }
+
+    fn x_error(&mut self, _: &mut WindowManager, _: &XError) {
+        self.mark_called("x_error");
+    }
+
     fn startup(&mut self, _: &mut WindowManager) {
         self.mark_called("startup");
     }
@@ -135,7 +143,8 @@ hook_test!(
             id: 1,
             ignore: false
         },
-        XEvent::KeyPress(common::KILL_CLIENT_CODE)
+        XEvent::KeyPress(common::KILL_CLIENT_CODE),
+        XEvent::Destroy { id: 1 }
     ]
 );
 
@@ -146,12 +155,12 @@ hook_test!(
     vec![
         XEvent::PropertyNotify {
             id: 1,
-            atom: "WM_NAME".to_string(),
+            atom: common::atom("WM_NAME"),
             is_root: false
         },
         XEvent::PropertyNotify {
             id: 1,
-            atom: "_NET_WM_NAME".to_string(),
+            atom: common::atom("_NET_WM_NAME"),
             is_root: false
         },
     ]
@@ -208,6 +217,20 @@ hook_test!(
         XEvent::KeyPress(common::FOCUS_CHANGE_CODE)]
 );
 
+hook_test!(
+    expected_calls => 2, // toggled to floating and back again
+    "floating_change",
+    test_floating_change_hooks,
+    vec![
+        XEvent::MapRequest {
+            id: 1,
+            ignore: false
+        },
+        XEvent::KeyPress(common::TOGGLE_FLOATING_CODE),
+        XEvent::KeyPress(common::TOGGLE_FLOATING_CODE),
+    ]
+);
+
 hook_test!(
     expected_calls => 1,
     "startup",
@@ -221,3 +244,16 @@ hook_test!(
     test_event_handled_hooks,
     vec![XEvent::ScreenChange]
 );
+
+hook_test!(
+    expected_calls => 1,
+    "x_error",
+    test_x_error_hooks,
+    vec![XEvent::Error(XError {
+        kind: XErrorKind::Window,
+        resource_id: 1,
+        major_code: 12,
+        minor_code: 0,
+        sequence: 0,
+    })]
+);